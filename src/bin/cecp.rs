@@ -0,0 +1,18 @@
+use std::io::{self, BufRead, Write};
+use rschess::cecp::Xboard;
+
+// runs the engine for XBoard, WinBoard and other GUIs speaking CECP, e.g. xboard -fcp ./cecp
+fn main() -> Result<(), &'static str> {
+    let mut xboard = Xboard::new();
+    let mut stdout = io::stdout();
+
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|_| "Could not read from stdin")?;
+        let Some(out) = xboard.handle(&line) else { break };
+        for line in out {
+            writeln!(stdout, "{line}").map_err(|_| "Could not write to stdout")?;
+        }
+        stdout.flush().map_err(|_| "Could not write to stdout")?;
+    }
+    Ok(())
+}
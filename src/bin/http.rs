@@ -0,0 +1,10 @@
+use std::env;
+use rschess::http::serve;
+
+// serves the JSON API and live games over WebSocket, e.g. ./http 127.0.0.1:8080 and then
+// curl -d '{"fen": "..."}' localhost:8080/legal-moves, see src/http.rs for the endpoints
+fn main() -> Result<(), &'static str> {
+    let address = env::args().nth(1).unwrap_or("127.0.0.1:8080".into());
+    println!("Listening on {address}");
+    serve(address.as_str())
+}
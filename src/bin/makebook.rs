@@ -0,0 +1,34 @@
+use std::{env, fs};
+use rschess::{pgn, polyglot::BookBuilder};
+
+// builds a polyglot book out of a PGN collection
+// usage: makebook <games.pgn> <book.bin> [max plies, default 20] [min games per move, default 1]
+fn main() -> Result<(), &'static str> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        return Err("usage: makebook <games.pgn> <book.bin> [max plies] [min games]");
+    }
+
+    let max_ply = args.get(3).map_or(Ok(20), |arg| arg.parse()).map_err(|_| "Invalid max plies")?;
+    let min_games = args.get(4).map_or(Ok(1), |arg| arg.parse()).map_err(|_| "Invalid min games")?;
+
+    // PGN files are often latin-1, the moves themselves are plain ascii either way
+    let bytes = fs::read(&args[1]).map_err(|_| "Could not read PGN file")?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    let mut builder = BookBuilder::new(max_ply);
+    builder.set_min_games(min_games);
+
+    let (mut added, mut skipped) = (0, 0);
+    for game in pgn::parse_games(&text) {
+        match game.and_then(|game| builder.add_game(&game)) {
+            Ok(()) => added += 1,
+            Err(_) => skipped += 1,
+        }
+    }
+
+    let book = builder.build();
+    book.save(&args[2])?;
+    println!("{added} games read, {skipped} skipped, {} book entries written to {}", book.len(), args[2]);
+    Ok(())
+}
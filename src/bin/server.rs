@@ -0,0 +1,10 @@
+use std::env;
+use rschess::server::serve;
+
+// hosts games for clients on this machine or the network, e.g. ./server 0.0.0.0:7878 and then
+// nc localhost 7878, see src/server.rs for the protocol
+fn main() -> Result<(), &'static str> {
+    let address = env::args().nth(1).unwrap_or("127.0.0.1:7878".into());
+    println!("Listening on {address}");
+    serve(address.as_str())
+}
@@ -0,0 +1,26 @@
+use std::env;
+use rschess::{board::Board, clock::{Clock, TimeControl}, game::Game, tui, variant::Variant};
+
+// plays games in the terminal, e.g. ./tui atomic 300+2 or ./tui "<fen>", every argument is
+// optional and may be a variant, a PGN time control or a FEN
+fn main() -> Result<(), &'static str> {
+    let mut board = Board::new();
+    let mut control = None;
+    let mut fen = String::new();
+    for arg in env::args().skip(1) {
+        if let Some(variant) = Variant::from_name(&arg) {
+            board.set_variant(variant);
+        } else if let Ok(parsed) = TimeControl::parse(&arg) {
+            control = Some(parsed);
+        } else {
+            fen = arg;
+        }
+    }
+    board.init(&fen)?;
+
+    let mut game = Game::from_board(board);
+    if let Some(control) = control {
+        game.set_clock(Clock::new(control));
+    }
+    tui::run(game).map_err(|_| "Could not run the terminal UI")
+}
@@ -1,7 +1,8 @@
 use std::{fmt::Display, ops::Not};
-use crate::pieces::{Pieces::*, Piece};
+use crate::{pieces::{Pieces::{self, *}, Piece}, render::Renderer, variant::Variant};
 
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     Black,
     White,
@@ -11,7 +12,7 @@ impl Not for Color {
     fn not(self) -> Self::Output {
         match self {
             Self::Black => Self::White,
-            Self::White => Self::White,
+            Self::White => Self::Black,
         }
     }
 
@@ -32,6 +33,12 @@ impl Display for Color {
 #[derive(Clone, Copy)]
 pub struct Square(u8, Option<Piece>);
 
+impl Default for Square {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Square {
     pub fn new() -> Self { // only used to initialize board
         Self(0, None)
@@ -43,8 +50,8 @@ impl Square {
         }
 
         Self (
-            ((x as u8) << 4) + y as u8,
-            None 
+            (x << 4) + y,
+            None
         )
     }
 
@@ -58,11 +65,11 @@ impl Square {
 
     #[allow(unused)]
     pub fn get_coords_string(&self) -> String {
-        format!("{}{}", ((self.0 >> 4) as u8 + b'A') as char, (self.0 & 0x0F) + 1)
+        format!("{}{}", ((self.0 >> 4) + b'A') as char, (self.0 & 0x0F) + 1)
     }
 
     pub fn get_color(&self) -> Color {
-        return if (((self.0 & 0xF0) >> 4) ^ (self.0 & 0x0F)) & 1 == 0 {
+        if (((self.0 & 0xF0) >> 4) ^ (self.0 & 0x0F)) & 1 == 0 {
            Black
         } else {
            White
@@ -70,7 +77,7 @@ impl Square {
     }
 
     pub fn get_piece(&self) -> Option<Piece> {
-        self.1.clone()
+        self.1
     }
 
     pub fn set_piece(&mut self, piece: Piece) {
@@ -107,7 +114,7 @@ impl Square {
 
         let r = chars.next().unwrap();
 
-        (c as u8 - b'A' << 4) + r.to_digit(10).expect("Invalid coord string") as u8 - 1
+        ((c as u8 - b'a') << 4) + r.to_digit(10).expect("Invalid coord string") as u8 - 1
     }
 
     pub fn string_to_tuple(coord: &str) -> (u8, u8) {
@@ -125,21 +132,144 @@ impl Square {
         (coord >> 4, coord & 0x0F)
     }
 
+    // non-panicking counterpart of string_to_u8 for untrusted input (FEN, UCI, etc)
+    pub fn parse_u8(coord: &str) -> Option<u8> {
+        let &[c, r] = coord.as_bytes() else { return None };
+        let (c, r) = (c.to_ascii_lowercase(), r);
+        if !(b'a'..=b'h').contains(&c) || !(b'1'..=b'8').contains(&r) {
+            return None;
+        }
+
+        Some(((c - b'a') << 4) + (r - b'1'))
+    }
+
+    // lowercase algebraic name of a u8 coordinate, as used by FEN and UCI
+    pub fn u8_to_string(coord: u8) -> String {
+        format!("{}{}", ((coord >> 4) + b'a') as char, (coord & 0x0F) + 1)
+    }
+}
+
+// castling right bits, KQkq
+pub const CASTLE_WHITE_KING: u8 = 0b1000;
+pub const CASTLE_WHITE_QUEEN: u8 = 0b0100;
+pub const CASTLE_BLACK_KING: u8 = 0b0010;
+pub const CASTLE_BLACK_QUEEN: u8 = 0b0001;
+
+// in the same order as Board::castling_rooks
+const CASTLE_BITS: [u8; 4] = [CASTLE_WHITE_KING, CASTLE_WHITE_QUEEN, CASTLE_BLACK_KING, CASTLE_BLACK_QUEEN];
+
+// where king and rook end up after castling, the same in chess960 as in classical chess
+const CASTLE_KING_SIDE: (u8, u8) = (6, 5);
+const CASTLE_QUEEN_SIDE: (u8, u8) = (2, 3);
+
+// knight placements for chess960 position numbers, on the five files left after bishops and queen
+const KNIGHTS_960: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: u8, // same (x << 4) + y encoding as Square
+    pub to: u8,
+    pub promotion: Option<Pieces>,
+    pub drop: Option<Pieces>, // crazyhouse, a piece from the pocket placed on an empty square, from is the same as to
+}
+
+impl Move {
+    pub fn new(from: u8, to: u8) -> Self {
+        Move { from, to, promotion: None, drop: None }
+    }
+
+    pub fn new_drop(r#type: Pieces, to: u8) -> Self {
+        Move { from: to, to, promotion: None, drop: Some(r#type) }
+    }
+
+    // parse long algebraic notation as used by UCI: e2e4, e7e8q, and drops like N@f3
+    pub fn from_uci(notation: &str) -> Result<Self, &'static str> {
+        if notation.len() == 4 && notation.as_bytes()[1] == b'@' {
+            let r#type = Piece::from_char(notation.as_bytes()[0] as char).filter(|piece| piece.r#type != King).ok_or("Invalid drop piece")?;
+            let to = Square::parse_u8(&notation[2..4]).ok_or("Invalid drop square")?;
+            return Ok(Move::new_drop(r#type.r#type, to));
+        }
+
+        if !notation.is_ascii() || !(4..=5).contains(&notation.len()) {
+            return Err("Move must be in long algebraic notation: e2e4, e7e8q, etc");
+        }
+
+        let from = Square::parse_u8(&notation[0..2]).ok_or("Invalid start square")?;
+        let to = Square::parse_u8(&notation[2..4]).ok_or("Invalid end square")?;
+        let promotion = match notation[4..].chars().next() {
+            None => None,
+            Some(c) => match Piece::from_char(c) {
+                // kings only in antichess, the board checks that
+                Some(piece) if piece.r#type != Pawn => Some(piece.r#type),
+                _ => return Err("Invalid promotion piece"),
+            },
+        };
+
+        Ok(Move { from, to, promotion, drop: None })
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(r#type) = self.drop {
+            return write!(f, "{}@{}", Piece::from(r#type, White).get_char(), Square::u8_to_string(self.to));
+        }
+
+        write!(f, "{}{}", Square::u8_to_string(self.from), Square::u8_to_string(self.to))?;
+        if let Some(r#type) = self.promotion {
+            write!(f, "{}", Piece::from(r#type, Black).get_char())?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Copy)]
-pub struct Board([Square; 64], u16); // (Board datastructure, enpassantable: black left 8 bits, white right 8)
+pub struct Board {
+    squares: [Square; 64],
+    enpassantable: u16, // black right 8 bits, white left 8 bits
+    turn: Color,
+    castling: u8, // see CASTLE_* bits
+    castling_rooks: [u8; 4], // file of the rook each castling right belongs to, in CASTLE_BITS order
+    chess960: bool, // castling moves are written as the king taking its own rook
+    variant: Variant,
+    pockets: [[u8; 6]; 2], // crazyhouse, pieces in hand by Color and Pieces
+    promoted: u64, // crazyhouse, bit per square index of pieces that were pawns, they go back to the pocket as pawns
+    checks: [u8; 2], // three-check, checks given by Color
+    halfmove: u16, // plies since the last capture or pawn move
+    fullmove: u16,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Board {
     pub fn new() -> Self {
-        let mut board = Board([Square::new(); 64], 1); //seems inefficient 
-        
+        let mut board = Board {
+            squares: [Square::new(); 64], //seems inefficient
+            enpassantable: 0,
+            turn: White,
+            castling: 0,
+            castling_rooks: [7, 0, 7, 0],
+            chess960: false,
+            variant: Variant::Standard,
+            pockets: [[0; 6]; 2],
+            promoted: 0,
+            checks: [0; 2],
+            halfmove: 0,
+            fullmove: 1,
+        };
+
         for i in 0..8 {
             for j in 0..8 {
                 *board.get_index_mut(i, j) = Square::from(i, j);
             }
         }
-        
+
         board
     }
 
@@ -153,7 +283,7 @@ impl Board {
                 32, 33, 34, 35, 36, 37, 38, 39,
                 40. 41. 42. 43. 44. 45, 46, 47,
                 48, 49, 50, 51, 52, 53, 54, 55,
-                56, 57, 58, 59, 60, 61, 62, 63,  
+                56, 57, 58, 59, 60, 61, 62, 63,
             ]
 
             y * 8 + x
@@ -162,10 +292,10 @@ impl Board {
         if  index > 63 {
             panic!("Invalid index parameter");
         }
-        
-        &mut self.0[index as usize]
+
+        &mut self.squares[index as usize]
     }
-    
+
     pub fn get_index(&self, x: u8, y: u8) -> &Square {
         /*
             [
@@ -176,7 +306,7 @@ impl Board {
                 32, 33, 34, 35, 36, 37, 38, 39,
                 40. 41. 42. 43. 44. 45, 46, 47,
                 48, 49, 50, 51, 52, 53, 54, 55,
-                56, 57, 58, 59, 60, 61, 62, 63,  
+                56, 57, 58, 59, 60, 61, 62, 63,
             ]
 
             y * 8 + x
@@ -185,11 +315,25 @@ impl Board {
         if  index > 63 {
             panic!("Invalid index parameter");
         }
-        
-        &self.0[index as usize]
+
+        &self.squares[index as usize]
     }
-    // init pieces
-    pub fn init(&mut self, _state: &str) { // impl state 
+
+    // init pieces, either in the starting position (empty state) or from a FEN string
+    pub fn init(&mut self, state: &str) -> Result<(), &'static str> {
+        if !state.trim().is_empty() {
+            return self.set_fen(state);
+        }
+
+        let (variant, chess960) = (self.variant, self.chess960);
+        *self = Board::new();
+        self.variant = variant;
+        self.chess960 = chess960;
+        if variant.get_starting_fen() != STARTING_FEN {
+            return self.set_fen(variant.get_starting_fen());
+        }
+        self.castling = CASTLE_WHITE_KING | CASTLE_WHITE_QUEEN | CASTLE_BLACK_KING | CASTLE_BLACK_QUEEN;
+
         // init pawns
         for i in 0..8 {
             self.get_index_mut(i, 1).set_piece(Piece::from(Pawn, White)); // white pawns
@@ -221,39 +365,534 @@ impl Board {
         // init kings
         self.get_index_mut(4, 0).set_piece(Piece::from(King, White));
         self.get_index_mut(4, 7).set_piece(Piece::from(King, Black));
+
+        Ok(())
+    }
+
+    // chess960 starting position by its number, 518 is the classical setup
+    pub fn init_960(&mut self, number: u16) -> Result<(), &'static str> {
+        if number > 959 {
+            return Err("Chess960 positions are numbered 0 to 959");
+        }
+
+        let mut rank = [None; 8];
+        let mut n = number as usize;
+        rank[n % 4 * 2 + 1] = Some(Bishop); // light squares
+        n /= 4;
+        rank[n % 4 * 2] = Some(Bishop); // dark squares
+        n /= 4;
+
+        // the rest go on the files still empty, counted from the a-file
+        let empty = |rank: &[Option<Pieces>; 8]| (0..8).filter(|&x| rank[x].is_none()).collect::<Vec<_>>();
+        rank[empty(&rank)[n % 6]] = Some(Queen);
+        n /= 6;
+        let files = empty(&rank);
+        let (first, second) = KNIGHTS_960[n];
+        rank[files[first]] = Some(Knight);
+        rank[files[second]] = Some(Knight);
+        let files = empty(&rank);
+        for (x, r#type) in files.into_iter().zip([Rook, King, Rook]) {
+            rank[x] = Some(r#type);
+        }
+
+        let variant = self.variant;
+        *self = Board::new();
+        self.variant = variant;
+        self.chess960 = true;
+        self.castling = CASTLE_WHITE_KING | CASTLE_WHITE_QUEEN | CASTLE_BLACK_KING | CASTLE_BLACK_QUEEN;
+        for (x, r#type) in rank.into_iter().enumerate() {
+            let x = x as u8;
+            let r#type = r#type.unwrap();
+            self.get_index_mut(x, 0).set_piece(Piece::from(r#type, White));
+            self.get_index_mut(x, 1).set_piece(Piece::from(Pawn, White));
+            self.get_index_mut(x, 6).set_piece(Piece::from(Pawn, Black));
+            self.get_index_mut(x, 7).set_piece(Piece::from(r#type, Black));
+        }
+
+        let rooks: Vec<u8> = (0..8).filter(|&x| self.get_index(x, 0).get_piece().is_some_and(|piece| piece.r#type == Rook)).collect();
+        self.castling_rooks = [rooks[1], rooks[0], rooks[1], rooks[0]];
+        Ok(())
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    // chess960 rules for castling and its notation, positions set up from a FEN keep this setting
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    pub fn get_variant(&self) -> Variant {
+        self.variant
+    }
+
+    // like chess960, positions set up from a FEN or the starting position keep the variant
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+    }
+
+    // crazyhouse, how many pieces of each type the side can drop, by Pieces
+    pub fn get_pocket(&self, color: Color) -> [u8; 6] {
+        self.pockets[color as usize]
+    }
+
+    // three-check, how many times the side has given check
+    pub fn get_checks(&self, color: Color) -> u8 {
+        self.checks[color as usize]
+    }
+
+    // file of the rook the side can still castle with, if it has the right
+    pub fn get_castling_rook(&self, color: Color, king_side: bool) -> Option<u8> {
+        let index = if color == White { 0 } else { 2 } + if king_side { 0 } else { 1 };
+        (self.castling & CASTLE_BITS[index] != 0).then_some(self.castling_rooks[index])
+    }
+
+    // where the king and the rook end up, by file
+    pub fn get_castling_targets(king_side: bool) -> (u8, u8) {
+        if king_side { CASTLE_KING_SIDE } else { CASTLE_QUEEN_SIDE }
+    }
+
+    // the move that castles to this side, written as this board writes castling moves,
+    // whether it is legal right now isn't checked
+    pub fn get_castling_move(&self, color: Color, king_side: bool) -> Option<Move> {
+        let rook_x = self.get_castling_rook(color, king_side)?;
+        let king = self.get_king(color)?;
+        let (_, y) = Square::u8_to_tuple(king);
+        let to_x = if self.chess960 { rook_x } else { Self::get_castling_targets(king_side).0 };
+        Some(Move::new(king, (to_x << 4) + y))
+    }
+
+    // classical castling moves the king two squares, chess960 castling takes its own rook
+    pub fn is_castling(&self, r#move: Move) -> bool {
+        let (start_x, start_y) = Square::u8_to_tuple(r#move.from);
+        let (x, y) = Square::u8_to_tuple(r#move.to);
+        let Some(piece) = self.get_index(start_x, start_y).get_piece().filter(|piece| piece.r#type == King) else { return false };
+        if self.chess960 {
+            start_y == y && self.get_index(x, y).get_piece() == Some(Piece::from(Rook, piece.color))
+        } else {
+            start_y == y && start_x.abs_diff(x) == 2
+        }
     }
 
     pub fn r#move(&mut self, start: &str, end: &str) -> Result<(), &'static str> {
-        let (start_x, start_y) = Square::string_to_tuple(start);
+        self.play(Move::new(Square::string_to_u8(start), Square::string_to_u8(end)))
+    }
+
+    // validate and play a move, pawns reaching the last rank promote to a queen unless told otherwise
+    pub fn play(&mut self, r#move: Move) -> Result<(), &'static str> {
+        if self.get_variant_outcome().is_some() {
+            return Err("Game is over");
+        }
+
+        if let Some(r#type) = r#move.drop {
+            if r#move.from != r#move.to || r#move.promotion.is_some() || !self.can_drop(r#type, r#move.to) || !self.is_safe_move(r#move) {
+                return Err("Illegal drop");
+            }
+            self.apply_move(r#move);
+            return Ok(());
+        }
+
+        let (start_x, start_y) = Square::u8_to_tuple(r#move.from);
         let Some(piece) = self.get_index(start_x, start_y).get_piece() else { return Err("No piece at start") };
-        if piece.get_moves(&mut self.clone(), &self.get_index(start_x, start_y)).contains(&Square::string_to_u8(end)) {
-            let (x, y) = Square::string_to_tuple(end); // target square x and y
-            self.get_index_mut(x, y).set_piece(piece);
+        if piece.color != self.turn {
+            return Err("Not your turn");
+        }
+
+        let (_, end_y) = Square::u8_to_tuple(r#move.to);
+        if r#move.promotion.is_some() && (piece.r#type != Pawn || !matches!(end_y, 0 | 7)) {
+            return Err("Only pawns reaching the last rank can promote");
+        }
+        if r#move.promotion == Some(King) && self.variant != Variant::Antichess {
+            return Err("Pawns can only promote to a king in antichess");
+        }
+        if self.variant == Variant::Antichess && !self.is_capture(r#move) && self.has_capture() {
+            return Err("Captures are forced in antichess");
+        }
 
-            // check/set pawn en passantable
+        if piece.get_moves(&mut self.clone(), self.get_index(start_x, start_y)).contains(&r#move.to) {
+            self.apply_move(r#move);
+            return Ok(())
+        }
+        Err("Illegal move")
+    }
+
+    // play a move without checking that it is legal, for moves that came out of get_legal_moves
+    pub fn apply_move(&mut self, r#move: Move) {
+        let mover = self.turn;
+        if let Some(r#type) = r#move.drop {
+            let (x, y) = Square::u8_to_tuple(r#move.to);
+            let count = &mut self.pockets[mover as usize][r#type as usize];
+            *count = count.saturating_sub(1);
+            self.get_index_mut(x, y).set_piece(Piece::from(r#type, mover));
             self.clear_epassantable(None);
-            if y == 3 && start_y == 1 && piece.r#type == Pawn {
-                self.set_enpassantable(piece.color, x);
+            self.halfmove += 1;
+        } else if !self.apply_board_move(r#move) {
+            return;
+        }
+
+        if self.variant == Variant::ThreeCheck && self.in_check(!mover) {
+            self.checks[mover as usize] += 1;
+        }
+
+        if self.turn == Black {
+            self.fullmove += 1;
+        }
+        self.turn = !self.turn;
+    }
+
+    // moves a piece, false if there is none on the start square
+    fn apply_board_move(&mut self, r#move: Move) -> bool {
+        let (start_x, start_y) = Square::u8_to_tuple(r#move.from);
+        let (x, y) = Square::u8_to_tuple(r#move.to); // target square x and y
+        let Some(mut piece) = self.get_index(start_x, start_y).get_piece() else { return false };
+        let castling = self.is_castling(r#move);
+        let mut captured = self.get_index(x, y).get_piece().filter(|_| !castling).map(|piece| piece.r#type);
+        if captured.is_some() && self.promoted & 1 << (y * 8 + x) != 0 {
+            captured = Some(Pawn);
+        }
+
+        // crazyhouse remembers which pieces were pawns, wherever they move
+        let was_promoted = self.promoted & 1 << (start_y * 8 + start_x) != 0;
+        self.promoted &= !(1 << (start_y * 8 + start_x) | 1 << (y * 8 + x));
+
+        let pawn_move = piece.r#type == Pawn; // the piece is the promoted one after this
+        if pawn_move {
+            // a diagonal move onto an empty square is an en passant capture
+            if start_x != x && captured.is_none() {
+                self.get_index_mut(x, start_y).remove_piece();
+                captured = Some(Pawn);
             }
 
-            let (x, y) = self.get_index(start_x, start_y).get_coords_tuple();
-            self.get_index_mut(x, y).remove_piece();
-            return Ok(())
+            if y == 0 || y == 7 {
+                piece.r#type = r#move.promotion.unwrap_or(Queen);
+                self.promoted |= 1 << (y * 8 + x);
+            }
+        } else if was_promoted {
+            self.promoted |= 1 << (y * 8 + x);
+        }
+
+        if castling {
+            // king and rook go to the same squares whichever files they started on
+            let king_side = x > start_x;
+            let (king_x, rook_to_x) = Self::get_castling_targets(king_side);
+            let rook_x = if self.chess960 {
+                x
+            } else {
+                self.get_castling_rook(piece.color, king_side).unwrap_or(if king_side { 7 } else { 0 })
+            };
+            let rook = self.get_index(rook_x, y).get_piece();
+            self.get_index_mut(rook_x, y).remove_piece();
+            self.get_index_mut(start_x, start_y).remove_piece();
+            self.get_index_mut(king_x, y).set_piece(piece);
+            if let Some(rook) = rook {
+                self.get_index_mut(rook_to_x, y).set_piece(rook);
+            }
+        } else {
+            self.get_index_mut(x, y).set_piece(piece);
+            self.get_index_mut(start_x, start_y).remove_piece();
+        }
+
+        if self.variant == Variant::Crazyhouse {
+            if let Some(r#type) = captured {
+                self.pockets[piece.color as usize][r#type as usize] += 1;
+            }
+        }
+
+        if self.variant == Variant::Atomic && captured.is_some() {
+            self.explode(x, y);
+        }
+
+        // check/set pawn en passantable, horde pawns stepping up from the first rank can't be taken en passant
+        self.clear_epassantable(None);
+        if piece.r#type == Pawn && start_y.abs_diff(y) == 2 && matches!(start_y, 1 | 6) {
+            self.set_enpassantable(piece.color, x);
+        }
+
+        self.update_castling(piece, r#move.from, r#move.to);
+        self.halfmove = if captured.is_some() || pawn_move { 0 } else { self.halfmove + 1 };
+        true
+    }
+
+    // atomic, the capturing piece and every piece but pawns next to the target square are gone
+    fn explode(&mut self, x: u8, y: u8) {
+        self.get_index_mut(x, y).remove_piece();
+        for (dx, dy) in Piece::OFFSETS_KING {
+            let (tx, ty) = (x as i8 + dx, y as i8 + dy);
+            if !(0..8).contains(&tx) || !(0..8).contains(&ty) {
+                continue;
+            }
+
+            let (tx, ty) = (tx as u8, ty as u8);
+            let Some(piece) = self.get_index(tx, ty).get_piece().filter(|piece| piece.r#type != Pawn) else { continue };
+            self.get_index_mut(tx, ty).remove_piece();
+            let coord = (tx << 4) + ty;
+            self.update_castling(piece, coord, coord);
+        }
+    }
+
+    // every legal move for the side to move, with each promotion piece listed separately
+    pub fn get_legal_moves(&self) -> Vec<Move> {
+        self.generate_moves(|_| true)
+    }
+
+    // number of move sequences of the given length, for checking move generation against known
+    // counts, lines stop early once a variant's game is over
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if self.get_variant_outcome().is_some() {
+            return 0;
+        }
+
+        let moves = self.get_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.into_iter().map(|r#move| {
+            let mut board = *self;
+            board.apply_move(r#move);
+            board.perft(depth - 1)
+        }).sum()
+    }
+
+    // captures and promotions, the moves worth looking at first
+    pub fn get_legal_captures(&self) -> Vec<Move> {
+        self.generate_moves(|r#move| self.is_capture(r#move) || self.is_promotion(r#move))
+    }
+
+    // everything get_legal_captures leaves out
+    pub fn get_legal_quiets(&self) -> Vec<Move> {
+        self.generate_moves(|r#move| !self.is_capture(r#move) && !self.is_promotion(r#move))
+    }
+
+    fn generate_moves(&self, filter: impl Fn(Move) -> bool) -> Vec<Move> {
+        if self.variant == Variant::Antichess {
+            let captures = self.generate_unforced_moves(|r#move| self.is_capture(r#move));
+            if !captures.is_empty() {
+                return captures.into_iter().filter(|&r#move| filter(r#move)).collect();
+            }
+        }
+        self.generate_unforced_moves(filter)
+    }
+
+    // antichess, whether there is a capture to be made
+    fn has_capture(&self) -> bool {
+        !self.generate_unforced_moves(|r#move| self.is_capture(r#move)).is_empty()
+    }
+
+    // every move the filter lets through, without forcing captures
+    fn generate_unforced_moves(&self, filter: impl Fn(Move) -> bool) -> Vec<Move> {
+        let mut moves = Vec::new();
+        if self.get_variant_outcome().is_some() {
+            return moves;
+        }
+
+        for square in self.squares {
+            let Some(piece) = square.get_piece() else { continue };
+            if piece.color != self.turn {
+                continue;
+            }
+
+            for to in piece.get_pseudo_moves(self, &square) {
+                let r#move = Move::new(square.get_coords_u8(), to);
+                if !filter(r#move) || !self.is_safe_move(r#move) {
+                    continue;
+                }
+
+                if self.is_promotion(r#move) {
+                    for promotion in [Queen, Rook, Bishop, Knight, King] {
+                        if promotion != King || self.variant == Variant::Antichess {
+                            moves.push(Move { promotion: Some(promotion), ..r#move });
+                        }
+                    }
+                } else {
+                    moves.push(r#move);
+                }
+            }
+        }
+
+        if self.variant == Variant::Crazyhouse {
+            for r#type in [Queen, Rook, Bishop, Knight, Pawn] {
+                for square in self.squares {
+                    let r#move = Move::new_drop(r#type, square.get_coords_u8());
+                    if self.can_drop(r#type, r#move.to) && filter(r#move) && self.is_safe_move(r#move) {
+                        moves.push(r#move);
+                    }
+                }
+            }
+        }
+
+        moves
+    }
+
+    // crazyhouse, pieces go from the pocket onto any empty square, except pawns onto the first or last rank
+    fn can_drop(&self, r#type: Pieces, to: u8) -> bool {
+        let (x, y) = Square::u8_to_tuple(to);
+        self.variant == Variant::Crazyhouse
+            && r#type != King
+            && self.pockets[self.turn as usize][r#type as usize] > 0
+            && self.get_index(x, y).get_piece().is_none()
+            && !(r#type == Pawn && matches!(y, 0 | 7))
+    }
+
+    // whether the move keeps the mover's own king out of check
+    pub fn is_safe_move(&self, r#move: Move) -> bool {
+        let (x, y) = Square::u8_to_tuple(r#move.from);
+        let color = match r#move.drop {
+            Some(_) => self.turn,
+            None => match self.get_index(x, y).get_piece() {
+                Some(piece) => piece.color,
+                None => return false,
+            },
+        };
+        let mut ephemeral_board = *self;
+        ephemeral_board.apply_move(r#move);
+
+        // atomic, blowing up the enemy king wins on the spot, blowing up your own never works
+        if self.variant == Variant::Atomic {
+            return ephemeral_board.get_king(color).is_some() && (ephemeral_board.get_king(!color).is_none() || !ephemeral_board.in_check(color));
+        }
+        !ephemeral_board.in_check(color)
+    }
+
+    // full legality check for moves from untrusted sources, like transposition tables or clients
+    pub fn is_legal(&self, r#move: Move) -> bool {
+        let mut board = *self;
+        board.play(r#move).is_ok()
+    }
+
+    pub fn is_promotion(&self, r#move: Move) -> bool {
+        let (x, y) = Square::u8_to_tuple(r#move.from);
+        matches!(r#move.to & 0x0F, 0 | 7) && self.get_index(x, y).get_piece().is_some_and(|piece| piece.r#type == Pawn)
+    }
+
+    /*
+        to calc if a square is attacked, search in the straight and diagonal directions and stop when you hit a piece or an edge.
+        if the piece intersected is a bishop (for diagonal) or a rook (for straight) or a queen (for both) of the attacking color, it is attacked.
+        knights, kings and pawns only need to be looked for on the squares they could attack from.
+    */
+    pub fn is_attacked(&self, coord: u8, by: Color) -> bool {
+        let (x, y) = Square::u8_to_tuple(coord);
+        let piece_at = |dx: i8, dy: i8| -> Option<Piece> {
+            let (x, y) = (x as i8 + dx, y as i8 + dy);
+            if !(0..8).contains(&x) || !(0..8).contains(&y) {
+                return None;
+            }
+            self.get_index(x as u8, y as u8).get_piece().filter(|piece| piece.color == by)
+        };
+
+        if Piece::OFFSETS_KNIGHT.iter().any(|&(dx, dy)| piece_at(dx, dy).is_some_and(|piece| piece.r#type == Knight)) {
+            return true;
+        }
+
+        // atomic kings can't capture, so they don't attack anything
+        let kings_attack = self.variant != Variant::Atomic;
+        if kings_attack && Piece::OFFSETS_KING.iter().any(|&(dx, dy)| piece_at(dx, dy).is_some_and(|piece| piece.r#type == King)) {
+            return true;
+        }
+
+        // pawns attack forwards, so look backwards from the attacking side's point of view
+        let behind = match by {
+            White => -1,
+            Black => 1,
+        };
+        if [-1, 1].into_iter().any(|dx| piece_at(dx, behind).is_some_and(|piece| piece.r#type == Pawn)) {
+            return true;
+        }
+
+        for (dx, dy) in Piece::OFFSETS_KING {
+            let diagonal = dx != 0 && dy != 0;
+            for i in 1..8 {
+                let (tx, ty) = (x as i8 + dx * i, y as i8 + dy * i);
+                if !(0..8).contains(&tx) || !(0..8).contains(&ty) {
+                    break;
+                }
+
+                let Some(piece) = self.get_index(tx as u8, ty as u8).get_piece() else { continue };
+                if piece.color == by && (piece.r#type == Queen || piece.r#type == if diagonal { Bishop } else { Rook }) {
+                    return true;
+                }
+                break;
+            }
+        }
+
+        false
+    }
+
+    // captures include en passant, the target square is empty for those
+    pub fn is_capture(&self, r#move: Move) -> bool {
+        let (start_x, start_y) = Square::u8_to_tuple(r#move.from);
+        let (x, y) = Square::u8_to_tuple(r#move.to);
+        (self.get_index(x, y).get_piece().is_some() && !self.is_castling(r#move))
+            || (start_x != x && self.get_index(start_x, start_y).get_piece().is_some_and(|piece| piece.r#type == Pawn))
+    }
+
+    pub fn get_king(&self, color: Color) -> Option<u8> {
+        self.squares.iter()
+            .find(|square| square.get_piece().is_some_and(|piece| piece.r#type == King && piece.color == color))
+            .map(|square| square.get_coords_u8())
+    }
+
+    // there is no check in antichess, and atomic kings next to each other can't be captured
+    // since that would blow up the capturing side's king as well
+    pub fn in_check(&self, color: Color) -> bool {
+        let Some(king) = self.get_king(color) else { return false };
+        match self.variant {
+            Variant::Antichess => false,
+            Variant::Atomic if self.get_king(!color).is_some_and(|other| Self::are_adjacent(king, other)) => false,
+            _ => self.is_attacked(king, !color),
+        }
+    }
+
+    fn are_adjacent(a: u8, b: u8) -> bool {
+        let ((ax, ay), (bx, by)) = (Square::u8_to_tuple(a), Square::u8_to_tuple(b));
+        ax.abs_diff(bx) <= 1 && ay.abs_diff(by) <= 1
+    }
+
+    // moving the king gives up both castling rights, any move from or to a castling rook's
+    // square gives up the right that goes with it
+    fn update_castling(&mut self, piece: Piece, start: u8, end: u8) {
+        if piece.r#type == King {
+            self.castling &= !match piece.color {
+                White => CASTLE_WHITE_KING | CASTLE_WHITE_QUEEN,
+                Black => CASTLE_BLACK_KING | CASTLE_BLACK_QUEEN,
+            };
+        }
+
+        for (i, bit) in CASTLE_BITS.into_iter().enumerate() {
+            let rook = (self.castling_rooks[i] << 4) + if i < 2 { 0 } else { 7 };
+            if start == rook || end == rook {
+                self.castling &= !bit;
+            }
         }
-        return Err("Illegal move")
-        // check legal move here
-        // std::mem::swap(&mut self.0[(end >> 4) as usize][(end & 0x0F) as usize].1, &mut self.0[(start >> 4) as usize][(start & 0x0F) as usize].1);
-        // self.0[(start >> 4) as usize][(start & 0x0F) as usize].1 = None;
     }
 
     pub fn get_state(&self) -> [Square; 64] {
-        self.0
+        self.squares
+    }
+
+    pub fn get_turn(&self) -> Color {
+        self.turn
+    }
+
+    pub fn get_castling(&self) -> u8 {
+        self.castling
+    }
+
+    pub fn get_halfmove(&self) -> u16 {
+        self.halfmove
+    }
+
+    pub fn get_fullmove(&self) -> u16 {
+        self.fullmove
     }
 
     pub fn get_enpassantable(&self, color: Color) -> Vec<u8> {
         let mut squares = match color {
-            Black => self.1 as u8, // drop leftmost 8 bits
-            White => (self.1 >> 8) as u8 // shift leftmost 8 bits to be rightmost 8 bits, drop empty bits
+            Black => self.enpassantable as u8, // drop leftmost 8 bits
+            White => (self.enpassantable >> 8) as u8 // shift leftmost 8 bits to be rightmost 8 bits, drop empty bits
         };
 
         let mut out: Vec<u8> = Vec::new();
@@ -270,33 +909,382 @@ impl Board {
             Black => x,
             White => x + 8
         } as u32);
-        if self.1 & bit == bit {
+        if self.enpassantable & bit == bit {
             unreachable!("Requested enpassant square is already enpassantable. This sould be impossible.");
         }
-        self.1 += bit;
+        self.enpassantable += bit;
     }
 
     pub fn clear_epassantable(&mut self, color: Option<Color>) {
         match color {
             // clear bits
-            Some(Black) => self.1 &= !0xFF00,
-            Some(White) => self.1 &= !0x00FF,
-            None => self.1 = 0
+            Some(Black) => self.enpassantable &= !0x00FF,
+            Some(White) => self.enpassantable &= !0xFF00,
+            None => self.enpassantable = 0
         }
     }
 }
 
-impl Display for Board {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "  ╔═════════════════╗  ")?;
-        for y in 0..8 {
-            write!(f, "{} ║ ", 8 - y)?;
+impl Board {
+    pub fn get_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for y in (0..8).rev() {
+            let mut empty = 0;
             for x in 0..8 {
-                write!(f, "{} ", self.get_state()[(7 - y) * 8 + x].get_char())?;
+                match self.get_index(x, y).get_piece() {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push((b'0' + empty) as char);
+                            empty = 0;
+                        }
+                        fen.push(piece.get_char());
+                        if self.variant == Variant::Crazyhouse && self.promoted & 1 << (y * 8 + x) != 0 {
+                            fen.push('~');
+                        }
+                    },
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                fen.push((b'0' + empty) as char);
+            }
+            if y > 0 {
+                fen.push('/');
+            }
+        }
+
+        // crazyhouse pockets, white's pieces first
+        if self.variant == Variant::Crazyhouse {
+            fen.push('[');
+            for color in [White, Black] {
+                for r#type in [Queen, Rook, Bishop, Knight, Pawn] {
+                    for _ in 0..self.pockets[color as usize][r#type as usize] {
+                        fen.push(Piece::from(r#type, color).get_char());
+                    }
+                }
+            }
+            fen.push(']');
+        }
+
+        fen.push_str(match self.turn {
+            White => " w ",
+            Black => " b ",
+        });
+
+        if self.castling == 0 {
+            fen.push('-');
+        }
+        for (i, (bit, c)) in CASTLE_BITS.into_iter().zip(['K', 'Q', 'k', 'q']).enumerate() {
+            if self.castling & bit == 0 {
+                continue;
+            }
+
+            // X-FEN, the file is only given when another rook stands further out on the same side
+            let (color, y) = if i < 2 { (White, 0) } else { (Black, 7) };
+            let rook_x = self.castling_rooks[i];
+            let outer = if i % 2 == 0 { rook_x + 1..8 } else { 0..rook_x };
+            let ambiguous = outer.into_iter().any(|x| self.get_index(x, y).get_piece() == Some(Piece::from(Rook, color)));
+            if ambiguous {
+                let file = (b'a' + rook_x) as char;
+                fen.push(if color == White { file.to_ascii_uppercase() } else { file });
+            } else {
+                fen.push(c);
+            }
+        }
+
+        // the target square is behind the pawn that just moved two squares
+        let target = match self.get_enpassantable(!self.turn).first() {
+            Some(&x) => Square::u8_to_string((x << 4) + if self.turn == White { 5 } else { 2 }),
+            None => "-".into(),
+        };
+
+        fen.push_str(&format!(" {target}"));
+        // three-check, the checks each side still has to give
+        if self.variant == Variant::ThreeCheck {
+            fen.push_str(&format!(" {}+{}", 3u8.saturating_sub(self.checks[White as usize]), 3u8.saturating_sub(self.checks[Black as usize])));
+        }
+        fen.push_str(&format!(" {} {}", self.halfmove, self.fullmove));
+        fen
+    }
+
+    pub fn set_fen(&mut self, fen: &str) -> Result<(), &'static str> {
+        let mut fields = fen.split_whitespace();
+        let mut board = Board::new();
+
+        // crazyhouse pockets come in brackets after the placement, or as a ninth rank
+        let placement = fields.next().ok_or("FEN is empty")?;
+        let (placement, pocket) = match placement.split_once('[') {
+            Some((placement, pocket)) => (placement, Some(pocket.strip_suffix(']').ok_or("Invalid pocket in FEN")?)),
+            None => (placement, None),
+        };
+        let mut ranks: Vec<&str> = placement.split('/').collect();
+        let pocket = match pocket {
+            None if ranks.len() == 9 => ranks.pop(),
+            pocket => pocket,
+        };
+        if ranks.len() != 8 {
+            return Err("FEN piece placement must have 8 ranks");
+        }
+
+        for c in pocket.unwrap_or("").chars() {
+            let piece = Piece::from_char(c).filter(|piece| piece.r#type != King).ok_or("Invalid piece in FEN pocket")?;
+            board.pockets[piece.color as usize][piece.r#type as usize] += 1;
+        }
+
+        for (i, rank) in ranks.iter().enumerate() {
+            let y = 7 - i as u8;
+            let mut x = 0;
+            for c in rank.chars() {
+                // a promoted piece, crazyhouse puts it back into the pocket as a pawn
+                if c == '~' {
+                    if x == 0 {
+                        return Err("Invalid promoted piece in FEN");
+                    }
+                    board.promoted |= 1 << (y * 8 + x - 1);
+                    continue;
+                }
+
+                if let Some(skip) = c.to_digit(10) {
+                    x += skip as u8;
+                    continue;
+                }
+
+                let piece = Piece::from_char(c).ok_or("Invalid piece in FEN")?;
+                if x > 7 {
+                    return Err("FEN rank has more than 8 files");
+                }
+                board.get_index_mut(x, y).set_piece(piece);
+                x += 1;
+            }
+
+            if x != 8 {
+                return Err("FEN rank does not have 8 files");
             }
-            write!(f, "║\n")?;
         }
-        writeln!(f, "  ╚═════════════════╝\n    A B C D E F G H")?;
+
+        board.turn = match fields.next() {
+            Some("w") | None => White,
+            Some("b") => Black,
+            _ => return Err("Invalid side to move in FEN"),
+        };
+
+        // KQkq for the outermost rooks, or the rook's file as in X-FEN and Shredder-FEN
+        board.chess960 = self.chess960;
+        board.variant = self.variant;
+        for c in fields.next().unwrap_or("-").chars() {
+            if c == '-' {
+                continue;
+            }
+
+            let color = if c.is_ascii_uppercase() { White } else { Black };
+            let y = if color == White { 0 } else { 7 };
+            let king_x = board.get_king(color).map(Square::u8_to_tuple).filter(|&(_, king_y)| king_y == y).map_or(4, |(x, _)| x);
+            let is_rook = |x: u8| board.get_index(x, y).get_piece() == Some(Piece::from(Rook, color));
+
+            let (king_side, rook_x) = match c.to_ascii_lowercase() {
+                'k' => (true, (king_x + 1..8).rev().find(|&x| is_rook(x)).unwrap_or(7)),
+                'q' => (false, (0..king_x).find(|&x| is_rook(x)).unwrap_or(0)),
+                file @ 'a'..='h' => {
+                    let x = file as u8 - b'a';
+                    if x == king_x {
+                        return Err("Invalid castling rights in FEN");
+                    }
+                    board.chess960 = true;
+                    (x > king_x, x)
+                },
+                _ => return Err("Invalid castling rights in FEN"),
+            };
+
+            let index = if color == White { 0 } else { 2 } + if king_side { 0 } else { 1 };
+            board.castling |= CASTLE_BITS[index];
+            board.castling_rooks[index] = rook_x;
+            if king_x != 4 || rook_x != if king_side { 7 } else { 0 } {
+                board.chess960 = true;
+            }
+        }
+
+        // castling doesn't exist in antichess
+        if board.variant == Variant::Antichess {
+            board.castling = 0;
+        }
+
+        match fields.next().unwrap_or("-") {
+            "-" => (),
+            target => {
+                let (x, _) = Square::u8_to_tuple(Square::parse_u8(target).ok_or("Invalid en passant square in FEN")?);
+                board.set_enpassantable(!board.turn, x);
+            }
+        }
+
+        // three-check, either the checks left to give before the clocks or, after them, the checks
+        // given so far as in +1+0
+        let mut fields = fields.peekable();
+        if let Some(checks) = fields.next_if(|field| field.contains('+')) {
+            let (white, black) = parse_checks(checks)?;
+            board.checks = [3u8.saturating_sub(black), 3u8.saturating_sub(white)];
+        }
+
+        board.halfmove = fields.next().unwrap_or("0").parse().map_err(|_| "Invalid halfmove clock in FEN")?;
+        board.fullmove = fields.next().unwrap_or("1").parse().map_err(|_| "Invalid fullmove number in FEN")?;
+
+        if let Some(checks) = fields.next() {
+            let (white, black) = parse_checks(checks.strip_prefix('+').ok_or("Invalid checks in FEN")?)?;
+            board.checks = [black, white];
+        }
+
+        *self = board;
         Ok(())
     }
 }
+
+// the two numbers of a three-check field like 3+2, white's first
+fn parse_checks(field: &str) -> Result<(u8, u8), &'static str> {
+    let (white, black) = field.split_once('+').ok_or("Invalid checks in FEN")?;
+    let parse = |count: &str| count.parse::<u8>().ok().filter(|&count| count <= 3).ok_or("Invalid checks in FEN");
+    Ok((parse(white)?, parse(black)?))
+}
+
+// see Renderer for other ways of drawing it
+impl Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Renderer::default().render(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_board(fen: &str) -> Board {
+        let mut board = Board::new();
+        board.init(fen).unwrap();
+        board
+    }
+
+    #[test]
+    fn counts_the_halfmove_clock() {
+        let mut board = get_board("4k3/P7/8/8/8/8/8/R3K3 w - - 10 40");
+        board.apply_move(Move::from_uci("a1a2").unwrap());
+        assert_eq!(board.get_halfmove(), 11);
+        board.apply_move(Move::from_uci("e8d7").unwrap());
+        assert_eq!(board.get_halfmove(), 12);
+
+        // promotions are pawn moves too
+        board.apply_move(Move::from_uci("a7a8n").unwrap());
+        assert_eq!(board.get_halfmove(), 0);
+        assert_eq!(board.get_fen(), "N7/3k4/8/8/8/8/R7/4K3 b - - 0 41");
+    }
+
+    fn assert_perft(variant: Variant, fen: &str, counts: &[u64]) {
+        let mut board = Board::new();
+        board.set_variant(variant);
+        board.init(fen).unwrap();
+        for (depth, &count) in counts.iter().enumerate() {
+            assert_eq!(board.perft(depth as u32 + 1), count, "{variant:?} {fen} depth {}", depth + 1);
+        }
+    }
+
+    #[test]
+    fn perft_standard() {
+        assert_perft(Variant::Standard, "", &[20, 400, 8902, 197281]);
+        assert_perft(Variant::Standard, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2039, 97862]);
+        assert_perft(Variant::Standard, "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn perft_atomic() {
+        assert_perft(Variant::Atomic, "", &[20, 400, 8902, 197326]);
+    }
+
+    #[test]
+    fn perft_antichess() {
+        assert_perft(Variant::Antichess, "", &[20, 400, 8067, 153299]);
+    }
+
+    #[test]
+    fn perft_horde() {
+        assert_perft(Variant::Horde, "", &[8, 128, 1274, 23310]);
+    }
+
+    #[test]
+    fn chess960_positions() {
+        let mut board = Board::new();
+        board.init_960(518).unwrap();
+        assert_eq!(board.get_fen(), STARTING_FEN);
+        assert!(board.is_chess960());
+        assert_eq!(board.get_hash(), get_board("").get_hash());
+        // and the starting position keeps the setting, like a FEN does
+        board.init("").unwrap();
+        assert!(board.is_chess960());
+
+        board.init_960(0).unwrap();
+        assert_eq!(board.get_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!((board.get_castling_rook(White, true), board.get_castling_rook(Black, false)), (Some(7), Some(5)));
+        board.init_960(959).unwrap();
+        assert_eq!(board.get_fen(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1");
+        assert!(board.init_960(960).is_err());
+
+        // every number gives a different position with the king between its rooks and bishops on both colors
+        let mut ranks = std::collections::HashSet::new();
+        for number in 0..960 {
+            board.init_960(number).unwrap();
+            let rank = board.get_fen().split('/').next().unwrap().to_string();
+            let (rooks, king) = (rank.match_indices('r').map(|(x, _)| x).collect::<Vec<_>>(), rank.find('k').unwrap());
+            let bishops = rank.match_indices('b').map(|(x, _)| x % 2).collect::<Vec<_>>();
+            assert!(rooks[0] < king && king < rooks[1] && bishops[0] != bishops[1], "{number} {rank}");
+            ranks.insert(rank);
+        }
+        assert_eq!(ranks.len(), 960);
+    }
+
+    #[test]
+    fn chess960_castling_rights() {
+        // Shredder-FEN gives the files of the rooks
+        let mut board = get_board("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1");
+        assert!(board.is_chess960());
+        assert_eq!(board.get_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        board.set_fen("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w Hf - 0 1").unwrap();
+        assert_eq!(board.get_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w Kq - 0 1");
+
+        // X-FEN only gives the file when KQkq would mean another rook further out
+        let board = get_board("1r2k1rr/8/8/8/8/8/8/RR2K1R1 w BGg - 0 1");
+        assert_eq!((board.get_castling_rook(White, false), board.get_castling_rook(White, true)), (Some(1), Some(6)));
+        assert_eq!((board.get_castling_rook(Black, false), board.get_castling_rook(Black, true)), (None, Some(6)));
+        assert_eq!(board.get_fen(), "1r2k1rr/8/8/8/8/8/8/RR2K1R1 w KBg - 0 1");
+        assert_eq!(get_board("1r2k1rr/8/8/8/8/8/8/RR2K1R1 w Qk - 0 1").get_fen(), "1r2k1rr/8/8/8/8/8/8/RR2K1R1 w Qk - 0 1");
+        assert_eq!(get_board("1r2k1rr/8/8/8/8/8/8/RR2K1R1 w Qk - 0 1").get_castling_rook(Black, true), Some(7));
+
+        assert!(get_board("4k3/8/8/8/8/8/8/4K3 w - - 0 1").set_fen("4k3/8/8/8/8/8/8/4K3 w E - 0 1").is_err());
+    }
+
+    #[test]
+    fn chess960_castling() {
+        // king and rook next to each other, castling is the king taking its own rook
+        let mut board = get_board("rk5r/pppppppp/8/8/8/8/PPPPPPPP/RK5R w KQkq - 0 1");
+        assert!(board.is_chess960());
+        let (queen_side, king_side) = (Move::from_uci("b1a1").unwrap(), Move::from_uci("b1h1").unwrap());
+        assert_eq!(board.get_castling_move(White, false), Some(queen_side));
+        assert!(board.is_castling(queen_side) && !board.is_castling(Move::from_uci("b1c1").unwrap()));
+        let moves = board.get_legal_moves();
+        assert!(moves.contains(&queen_side) && moves.contains(&king_side) && moves.contains(&Move::from_uci("b1c1").unwrap()));
+
+        let mut castled = board;
+        castled.play(queen_side).unwrap();
+        assert_eq!(castled.get_fen(), "rk5r/pppppppp/8/8/8/8/PPPPPPPP/2KR3R b kq - 1 1");
+        castled.play(Move::from_uci("b8h8").unwrap()).unwrap();
+        assert_eq!(castled.get_fen(), "r4rk1/pppppppp/8/8/8/8/PPPPPPPP/2KR3R w - - 2 2");
+
+        // the king can't castle through the rook's destination when it is taken
+        board.set_fen("rk5r/pppppppp/8/8/8/8/PPPPPPPP/RK1N3R w KQkq - 0 1").unwrap();
+        assert!(!board.get_legal_moves().contains(&queen_side));
+        assert_eq!(board.play(queen_side), Err("Illegal move"));
+    }
+
+    #[test]
+    fn perft_chess960() {
+        assert_perft(Variant::Standard, "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", &[21, 528, 12189, 326672]);
+        assert_perft(Variant::Standard, "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", &[21, 807, 18002]);
+    }
+}
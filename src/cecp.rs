@@ -0,0 +1,434 @@
+use std::time::Duration;
+use crate::{
+    board::{Board, Color::{self, *}, Move},
+    game::Game,
+    search::{search_with_table, Limits, SearchResult, DEFAULT_HASH_MB},
+    tt::TranspositionTable,
+    variant::Variant,
+};
+
+// engine side of the Chess Engine Communication Protocol, as spoken by XBoard and WinBoard,
+// see src/bin/cecp.rs for running it over stdin and stdout. Searches block until they finish,
+// so ? is ignored and moves come when the time allocated for them is up
+
+// the variant names XBoard uses, antichess is closest to what it calls suicide
+fn get_variant_name(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Standard => "normal",
+        Variant::Crazyhouse => "crazyhouse",
+        Variant::ThreeCheck => "3check",
+        Variant::KingOfTheHill => "kingofthehill",
+        Variant::Atomic => "atomic",
+        Variant::Antichess => "suicide",
+        Variant::Horde => "horde",
+    }
+}
+
+// base time in minutes, or minutes:seconds
+fn parse_base(base: &str) -> Option<Duration> {
+    let (minutes, seconds) = base.split_once(':').unwrap_or((base, "0"));
+    Some(Duration::from_secs(minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?))
+}
+
+// time and otim come in centiseconds
+fn parse_centiseconds(time: &str) -> Option<Duration> {
+    let centiseconds = time.parse::<i64>().ok()?;
+    Some(Duration::from_millis(centiseconds.max(0) as u64 * 10))
+}
+
+pub struct Xboard {
+    game: Game,
+    chess960: bool, // variant fischerandom
+    force: bool, // only check and record moves, the engine plays neither side
+    engine: Color, // the side the engine plays when not in force mode
+    level: Option<(u32, Duration)>, // moves per session, 0 for all of them, and the increment
+    time: Option<Duration>, // engine's clock
+    opponent_time: Option<Duration>,
+    movetime: Option<Duration>, // st, exact time per move
+    depth: Option<u8>, // sd
+    threads: usize,
+    post: bool, // send thinking output
+    tt: TranspositionTable,
+}
+
+impl Default for Xboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Xboard {
+    pub fn new() -> Self {
+        Xboard {
+            game: Game::new(),
+            chess960: false,
+            force: false,
+            engine: Black,
+            level: None,
+            time: None,
+            opponent_time: None,
+            movetime: None,
+            depth: None,
+            threads: 1,
+            post: false,
+            tt: TranspositionTable::new(DEFAULT_HASH_MB),
+        }
+    }
+
+    pub fn get_board(&self) -> &Board {
+        self.game.get_board()
+    }
+
+    // handle one command from the GUI, returning the lines to send back, None once told to quit
+    pub fn handle(&mut self, line: &str) -> Option<Vec<String>> {
+        let mut out = Vec::new();
+        let line = line.trim();
+        let (command, args) = line.split_once(char::is_whitespace).map_or((line, ""), |(command, args)| (command, args.trim()));
+
+        match command {
+            "quit" => return None,
+            "" | "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name" | "rating" | "ics" | "?" | "draw" | "hint" | "bk" | "." => (),
+            "protover" => {
+                let variants: Vec<&str> = Variant::ALL.iter().map(|&variant| get_variant_name(variant)).chain(["fischerandom"]).collect();
+                out.push(format!(
+                    "feature myname=\"RSChess\" ping=1 setboard=1 usermove=1 playother=1 san=0 time=1 draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 memory=1 smp=1 variants=\"{}\"",
+                    variants.join(",")
+                ));
+                out.push("feature done=1".into());
+            },
+            "ping" => out.push(format!("pong {args}")),
+            "new" => {
+                self.chess960 = false;
+                self.reset(Variant::Standard, "");
+                self.force = false;
+                self.engine = Black;
+                self.movetime = None;
+                self.depth = None;
+                self.tt.clear();
+            },
+            "variant" => {
+                self.chess960 = args == "fischerandom";
+                match Variant::ALL.into_iter().find(|&variant| get_variant_name(variant) == args) {
+                    Some(variant) => {
+                        self.reset(variant, "");
+                    },
+                    None if self.chess960 => {
+                        self.reset(Variant::Standard, "");
+                    },
+                    None => out.push(format!("Error (unsupported variant): {args}")),
+                }
+            },
+            "setboard" => {
+                let variant = self.game.get_board().get_variant();
+                if !self.reset(variant, args) {
+                    out.push(format!("tellusererror Illegal position: {args}"));
+                }
+            },
+            "force" => self.force = true,
+            "go" => {
+                self.force = false;
+                self.engine = self.game.get_board().get_turn();
+                self.think(&mut out);
+            },
+            "playother" => {
+                self.force = false;
+                self.engine = !self.game.get_board().get_turn();
+            },
+            "usermove" => self.user_move(args, &mut out),
+            "undo" => {
+                let _ = self.game.undo();
+            },
+            "remove" => {
+                let _ = self.game.undo();
+                let _ = self.game.undo();
+            },
+            "result" => self.force = true,
+            "level" => {
+                let mut fields = args.split_whitespace();
+                let moves = fields.next().and_then(|moves| moves.parse().ok());
+                let base = fields.next().and_then(parse_base);
+                let increment = fields.next().and_then(|increment| increment.parse::<f64>().ok()).filter(|increment| *increment >= 0.0);
+                match (moves, base, increment) {
+                    (Some(moves), Some(base), Some(increment)) => {
+                        self.level = Some((moves, Duration::from_secs_f64(increment)));
+                        self.time = Some(base);
+                        self.opponent_time = Some(base);
+                        self.movetime = None;
+                    },
+                    _ => out.push(format!("Error (invalid level): {args}")),
+                }
+            },
+            "st" => match args.parse::<f64>().ok().filter(|seconds| *seconds > 0.0) {
+                Some(seconds) => self.movetime = Some(Duration::from_secs_f64(seconds)),
+                None => out.push(format!("Error (invalid time): {args}")),
+            },
+            "sd" => match args.parse() {
+                Ok(depth) => self.depth = Some(depth),
+                Err(_) => out.push(format!("Error (invalid depth): {args}")),
+            },
+            "time" => self.time = parse_centiseconds(args),
+            "otim" => self.opponent_time = parse_centiseconds(args),
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "memory" => match args.parse() {
+                Ok(megabytes) => self.tt.resize(megabytes),
+                Err(_) => out.push(format!("Error (invalid memory): {args}")),
+            },
+            "cores" => match args.parse::<usize>() {
+                Ok(threads) => self.threads = threads.max(1),
+                Err(_) => out.push(format!("Error (invalid cores): {args}")),
+            },
+            // without usermove=1 moves come on their own
+            _ if Move::from_uci(command).is_ok() || self.game.get_board().parse_san(command).is_ok() => self.user_move(command, &mut out),
+            _ => out.push(format!("Error (unknown command): {command}")),
+        }
+
+        Some(out)
+    }
+
+    // a fresh game of the variant, from the FEN or the variant's starting position, false if the FEN doesn't work
+    fn reset(&mut self, variant: Variant, fen: &str) -> bool {
+        let mut board = Board::new();
+        board.set_variant(variant);
+        board.set_chess960(self.chess960);
+        if board.init(fen).is_err() {
+            return false;
+        }
+        self.game = Game::from_board(board);
+        true
+    }
+
+    fn user_move(&mut self, notation: &str, out: &mut Vec<String>) {
+        let board = self.game.get_board();
+        // xboard sends chess960 castling as O-O, which parses as SAN
+        let Some(r#move) = Move::from_uci(notation).ok().or_else(|| board.parse_san(notation).ok()) else {
+            out.push(format!("Illegal move: {notation}"));
+            return;
+        };
+
+        if self.game.play(r#move).is_err() {
+            out.push(format!("Illegal move: {notation}"));
+            return;
+        }
+
+        if self.game.is_over() {
+            self.report_result(out);
+        } else if !self.force && self.game.get_board().get_turn() == self.engine {
+            self.think(out);
+        }
+    }
+
+    // search the position and play the best move
+    fn think(&mut self, out: &mut Vec<String>) {
+        if self.game.is_over() {
+            self.report_result(out);
+            return;
+        }
+
+        let board = *self.game.get_board();
+        let result = search_with_table(&board, &self.get_limits(&board), &mut self.tt);
+        if self.post {
+            out.push(Self::get_thinking(&result));
+        }
+
+        let Some(r#move) = result.best_move else { return };
+        let notation = if board.is_chess960() && board.is_castling(r#move) { board.get_san(r#move) } else { r#move.to_string() };
+        if self.game.play(r#move).is_err() {
+            return;
+        }
+        out.push(format!("move {}", notation.trim_end_matches(['+', '#'])));
+
+        // repetitions and the 50 move rule are claimed as soon as the engine's move allows it
+        if self.game.is_over() || self.game.claim_draw().is_ok() {
+            self.report_result(out);
+        }
+    }
+
+    fn get_limits(&self, board: &Board) -> Limits {
+        let mut limits = Limits { depth: self.depth, threads: self.threads, ..Default::default() };
+        if let Some(movetime) = self.movetime {
+            limits.movetime = Some(movetime);
+        } else if let Some(time) = self.time {
+            let (moves, increment) = self.level.unwrap_or((0, Duration::ZERO));
+            let (engine, opponent) = (Some(time), self.opponent_time.or(Some(time)));
+            (limits.wtime, limits.btime) = if self.engine == White { (engine, opponent) } else { (opponent, engine) };
+            (limits.winc, limits.binc) = (Some(increment), Some(increment));
+            // moves until the next session, counted from the start of the game
+            if moves > 0 {
+                limits.movestogo = Some(moves - (board.get_fullmove() as u32 - 1) % moves);
+            }
+        }
+        limits
+    }
+
+    // ply score time nodes pv, with the time in centiseconds
+    fn get_thinking(result: &SearchResult) -> String {
+        let centiseconds = result.nodes * 100 / result.nps.max(1);
+        let pv: Vec<String> = result.pv.iter().map(Move::to_string).collect();
+        format!("{} {} {} {} {}", result.depth, result.score, centiseconds, result.nodes, pv.join(" "))
+    }
+
+    fn report_result(&mut self, out: &mut Vec<String>) {
+        if let Some(termination) = self.game.get_termination() {
+            out.push(format!("{} {{{termination}}}", self.game.get_result_token()));
+        }
+        self.force = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::STARTING_FEN, search::MATE};
+
+    // a shallow search with a small table, so the engine answers quickly
+    fn get_engine() -> Xboard {
+        let mut engine = Xboard::new();
+        for command in ["xboard", "protover 2", "memory 1", "new", "sd 2"] {
+            engine.handle(command).unwrap();
+        }
+        engine
+    }
+
+    fn handle(engine: &mut Xboard, command: &str) -> Vec<String> {
+        engine.handle(command).unwrap()
+    }
+
+    #[test]
+    fn features() {
+        let mut engine = Xboard::new();
+        let out = handle(&mut engine, "protover 2");
+        assert_eq!(out.len(), 2);
+        for feature in ["myname=\"RSChess\"", "ping=1", "setboard=1", "usermove=1", "sigint=0", "variants=\"normal,crazyhouse,3check,kingofthehill,atomic,suicide,horde,fischerandom\""] {
+            assert!(out[0].starts_with("feature ") && out[0].contains(feature), "{feature}");
+        }
+        assert_eq!(out[1], "feature done=1");
+
+        assert_eq!(handle(&mut engine, "ping 12"), ["pong 12"]);
+        assert_eq!(handle(&mut engine, "accepted usermove"), Vec::<String>::new());
+        assert_eq!(handle(&mut engine, "frobnicate"), ["Error (unknown command): frobnicate"]);
+        assert_eq!(engine.handle("quit"), None);
+    }
+
+    #[test]
+    fn replies_to_moves() {
+        let mut engine = get_engine();
+        let out = handle(&mut engine, "usermove e2e4");
+        assert_eq!(out.len(), 1);
+        let reply = Move::from_uci(out[0].strip_prefix("move ").unwrap()).unwrap();
+        assert_eq!(engine.game.get_moves().len(), 2);
+        assert_eq!(engine.game.get_moves()[1], reply);
+
+        // moves also come on their own, and in SAN
+        assert_eq!(handle(&mut engine, "d2d4").len(), 1);
+        assert_eq!(handle(&mut engine, "Nc3").len(), 1);
+        assert_eq!(engine.game.get_moves().len(), 6);
+
+        assert_eq!(handle(&mut engine, "usermove e2e5"), ["Illegal move: e2e5"]);
+        assert_eq!(handle(&mut engine, "usermove Qxh8"), ["Illegal move: Qxh8"]);
+        assert_eq!(engine.game.get_moves().len(), 6);
+    }
+
+    #[test]
+    fn force_and_undo() {
+        let mut engine = get_engine();
+        handle(&mut engine, "force");
+        for r#move in ["e2e4", "e7e5", "g1f3"] {
+            assert_eq!(handle(&mut engine, &format!("usermove {move}")), Vec::<String>::new());
+        }
+        assert_eq!(engine.get_board().get_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        handle(&mut engine, "undo");
+        assert_eq!(engine.get_board().get_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        handle(&mut engine, "remove");
+        assert_eq!(engine.get_board().get_fen(), STARTING_FEN);
+        // nothing left to take back
+        assert_eq!(handle(&mut engine, "undo"), Vec::<String>::new());
+
+        // go plays the side to move, and keeps playing it
+        let out = handle(&mut engine, "go");
+        assert!(out.len() == 1 && out[0].starts_with("move "));
+        assert_eq!(engine.engine, White);
+        assert_eq!(handle(&mut engine, "usermove e7e5").len(), 1);
+        assert_eq!(engine.game.get_moves().len(), 3);
+
+        // playother waits for the opponent's move
+        handle(&mut engine, "force");
+        handle(&mut engine, "playother");
+        assert_eq!(engine.engine, White);
+        assert_eq!(handle(&mut engine, "usermove b8c6").len(), 1);
+    }
+
+    #[test]
+    fn clocks() {
+        let mut engine = get_engine();
+        assert_eq!(handle(&mut engine, "level 40 5 2"), Vec::<String>::new());
+        assert_eq!((engine.level, engine.time), (Some((40, Duration::from_secs(2))), Some(Duration::from_secs(300))));
+        handle(&mut engine, "time 3000");
+        handle(&mut engine, "otim 1000");
+
+        let limits = engine.get_limits(engine.get_board());
+        assert_eq!((limits.btime, limits.wtime), (Some(Duration::from_secs(30)), Some(Duration::from_secs(10))));
+        assert_eq!((limits.binc, limits.movestogo, limits.depth), (Some(Duration::from_secs(2)), Some(40), Some(2)));
+
+        // the moves left count down to the next session
+        handle(&mut engine, "setboard 4k3/8/8/8/8/8/8/4K3 b - - 0 45");
+        assert_eq!(engine.get_limits(engine.get_board()).movestogo, Some(36));
+        handle(&mut engine, "setboard 4k3/8/8/8/8/8/8/4K3 b - - 0 41");
+        assert_eq!(engine.get_limits(engine.get_board()).movestogo, Some(40));
+
+        handle(&mut engine, "level 0 2:30 0.5");
+        assert_eq!((engine.level, engine.time), (Some((0, Duration::from_millis(500))), Some(Duration::from_secs(150))));
+        assert_eq!(engine.get_limits(engine.get_board()).movestogo, None);
+        assert_eq!(handle(&mut engine, "level 40 x 0"), ["Error (invalid level): 40 x 0"]);
+        handle(&mut engine, "time -20");
+        assert_eq!(engine.time, Some(Duration::ZERO));
+
+        handle(&mut engine, "st 2");
+        let limits = engine.get_limits(engine.get_board());
+        assert_eq!((limits.movetime, limits.btime), (Some(Duration::from_secs(2)), None));
+        assert_eq!(handle(&mut engine, "st 0"), ["Error (invalid time): 0"]);
+    }
+
+    #[test]
+    fn setboard() {
+        let mut engine = get_engine();
+        assert_eq!(handle(&mut engine, "setboard 6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1"), Vec::<String>::new());
+        assert_eq!(handle(&mut engine, "setboard 6k1/5ppp/8/8 w - - 0 1"), ["tellusererror Illegal position: 6k1/5ppp/8/8 w - - 0 1"]);
+        assert_eq!(engine.get_board().get_fen(), "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+
+        // the engine mates and reports the result
+        handle(&mut engine, "post");
+        let out = handle(&mut engine, "go");
+        assert_eq!(out.len(), 3);
+        // the search stops at depth 1 once it sees the mate
+        assert!(out[0].starts_with(&format!("1 {} ", MATE - 1)) && out[0].ends_with(" d1d8"), "{}", out[0]);
+        assert_eq!(out[1..], ["move d1d8", "1-0 {checkmate}"]);
+        assert_eq!(handle(&mut engine, "go"), ["1-0 {checkmate}"]);
+    }
+
+    #[test]
+    fn variants() {
+        let mut engine = get_engine();
+        handle(&mut engine, "variant suicide");
+        assert_eq!(engine.get_board().get_variant(), Variant::Antichess);
+        handle(&mut engine, "variant crazyhouse");
+        assert_eq!(engine.get_board().get_fen(), Variant::Crazyhouse.get_starting_fen());
+        // setboard keeps the variant
+        handle(&mut engine, "setboard 4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1");
+        assert_eq!(engine.get_board().get_variant(), Variant::Crazyhouse);
+        assert_eq!(handle(&mut engine, "variant shogi"), ["Error (unsupported variant): shogi"]);
+        assert_eq!(engine.get_board().get_variant(), Variant::Crazyhouse);
+
+        // chess960 castling comes as O-O
+        handle(&mut engine, "variant fischerandom");
+        assert!(engine.get_board().is_chess960() && engine.get_board().get_variant() == Variant::Standard);
+        handle(&mut engine, "force");
+        handle(&mut engine, "setboard rk5r/pppppppp/8/8/8/8/PPPPPPPP/RK5R w KQkq - 0 1");
+        assert_eq!(handle(&mut engine, "usermove O-O-O"), Vec::<String>::new());
+        assert_eq!(engine.get_board().get_fen(), "rk5r/pppppppp/8/8/8/8/PPPPPPPP/2KR3R b kq - 1 1");
+
+        handle(&mut engine, "new");
+        assert!(!engine.get_board().is_chess960() && engine.get_board().get_fen() == STARTING_FEN);
+    }
+}
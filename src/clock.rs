@@ -0,0 +1,446 @@
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use crate::{board::{Board, Color::{self, *}}, search::Limits, variant::Outcome};
+
+// chess clock for both players, with increments, delays and controls in several stages
+
+// where the clock gets the time from, anything that only ever goes forward will do
+pub trait TimeSource: Send + Sync {
+    fn now(&self) -> Duration; // since an arbitrary but fixed point
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RealTime {
+    start: Instant,
+}
+
+impl RealTime {
+    pub fn new() -> Self {
+        RealTime { start: Instant::now() }
+    }
+}
+
+impl Default for RealTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for RealTime {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// time that only moves when told to, clones share the same time so one can be handed to a clock
+// and the other kept to advance it
+#[derive(Clone, Debug, Default)]
+pub struct ManualTime {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualTime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Fischer, // the increment is added after every move, sudden death when there is none
+    Bronstein, // the time a move took is given back afterwards, up to the delay
+    SimpleDelay, // the clock only starts counting down once the delay has passed, the US delay
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stage {
+    pub moves: Option<u32>, // moves to make before the next stage, None for the rest of the game
+    pub time: Duration, // added to the clock when the stage starts
+    pub increment: Duration, // the increment or the delay, depending on the mode
+    pub mode: Mode,
+}
+
+// the last stage repeats if it has a move count, like 40 moves in 90 minutes over and over
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeControl {
+    stages: Vec<Stage>,
+}
+
+impl TimeControl {
+    pub fn new(stages: Vec<Stage>) -> Result<Self, &'static str> {
+        if stages.is_empty() {
+            return Err("Time control needs at least one stage");
+        }
+        Ok(TimeControl { stages })
+    }
+
+    pub fn sudden_death(time: Duration) -> Self {
+        Self::fischer(time, Duration::ZERO)
+    }
+
+    pub fn fischer(time: Duration, increment: Duration) -> Self {
+        TimeControl { stages: vec![Stage { moves: None, time, increment, mode: Mode::Fischer }] }
+    }
+
+    pub fn bronstein(time: Duration, delay: Duration) -> Self {
+        TimeControl { stages: vec![Stage { moves: None, time, increment: delay, mode: Mode::Bronstein }] }
+    }
+
+    pub fn simple_delay(time: Duration, delay: Duration) -> Self {
+        TimeControl { stages: vec![Stage { moves: None, time, increment: delay, mode: Mode::SimpleDelay }] }
+    }
+
+    // PGN TimeControl tag syntax, in seconds: 300+2, or 40/5400+30:1800+30 for 90 minutes for 40 moves
+    // and 30 minutes for the rest with 30 seconds added per move throughout
+    pub fn parse(control: &str) -> Result<Self, &'static str> {
+        let parse_seconds = |seconds: &str| seconds.parse::<f64>().ok().filter(|seconds| *seconds >= 0.0).map(Duration::from_secs_f64);
+        let stages = control.trim().split(':').map(|stage| {
+            let (moves, rest) = match stage.split_once('/') {
+                Some((moves, rest)) => (Some(moves.parse().ok().filter(|&moves| moves > 0).ok_or("Invalid move count in time control")?), rest),
+                None => (None, stage),
+            };
+            let (time, increment) = rest.split_once('+').unwrap_or((rest, "0"));
+            Ok(Stage {
+                moves,
+                time: parse_seconds(time).ok_or("Invalid time in time control")?,
+                increment: parse_seconds(increment).ok_or("Invalid increment in time control")?,
+                mode: Mode::Fischer,
+            })
+        }).collect::<Result<Vec<_>, _>>()?;
+        Self::new(stages)
+    }
+
+    pub fn get_stages(&self) -> &[Stage] {
+        &self.stages
+    }
+}
+
+// back in PGN TimeControl syntax, delays can't be told apart from increments there
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            if let Some(moves) = stage.moves {
+                write!(f, "{moves}/")?;
+            }
+            write!(f, "{}", stage.time.as_secs_f64())?;
+            if !stage.increment.is_zero() {
+                write!(f, "+{}", stage.increment.as_secs_f64())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Clock {
+    control: TimeControl,
+    source: Arc<dyn TimeSource>,
+    remaining: [Duration; 2], // by Color, not counting the move in progress
+    stages: [usize; 2], // the stage each side is in, by Color
+    moves: [u32; 2], // moves made in the current stage, by Color
+    turn: Color, // whose clock is running or would be
+    running_since: Option<Duration>, // None while paused
+    spent: Duration, // on the move in progress before the last pause
+    flag: Option<Color>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Self::with_source(control, Arc::new(RealTime::new()))
+    }
+
+    pub fn with_source(control: TimeControl, source: Arc<dyn TimeSource>) -> Self {
+        let time = control.stages[0].time;
+        Clock {
+            control,
+            source,
+            remaining: [time, time],
+            stages: [0, 0],
+            moves: [0, 0],
+            turn: White,
+            running_since: None,
+            spent: Duration::ZERO,
+            flag: None,
+        }
+    }
+
+    pub fn get_time_control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    // start the clock of the side to move, or pick up where it was paused
+    pub fn start(&mut self, turn: Color) {
+        if self.flag.is_some() || self.running_since.is_some() {
+            return;
+        }
+        if turn != self.turn {
+            self.turn = turn;
+            self.spent = Duration::ZERO;
+        }
+        self.running_since = Some(self.source.now());
+    }
+
+    // the time used so far on this move is kept for when the clock starts again
+    pub fn pause(&mut self) {
+        self.spent = self.get_move_time();
+        self.running_since = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    pub fn get_turn(&self) -> Color {
+        self.turn
+    }
+
+    // the side to move made its move: charge the time it took, add the increment and hand over to the other side
+    pub fn press(&mut self) -> Result<(), &'static str> {
+        if self.check_flag().is_some() {
+            return Err("Flag has fallen");
+        }
+
+        let color = self.turn;
+        let took = self.get_move_time();
+        let stage = *self.get_stage(color);
+        let remaining = &mut self.remaining[color as usize];
+        *remaining = remaining.saturating_sub(Self::get_charged(&stage, took));
+        match stage.mode {
+            Mode::Fischer => *remaining += stage.increment,
+            Mode::Bronstein => *remaining += took.min(stage.increment),
+            Mode::SimpleDelay => (),
+        }
+
+        self.moves[color as usize] += 1;
+        if stage.moves.is_some_and(|moves| self.moves[color as usize] >= moves) {
+            self.moves[color as usize] = 0;
+            self.stages[color as usize] += 1;
+            self.remaining[color as usize] += self.get_stage(color).time;
+        }
+
+        self.turn = !color;
+        self.spent = Duration::ZERO;
+        if self.running_since.is_some() {
+            self.running_since = Some(self.source.now());
+        }
+        Ok(())
+    }
+
+    // what is left on the side's clock right now, a delay that hasn't run out yet isn't counted
+    pub fn get_remaining(&self, color: Color) -> Duration {
+        let remaining = self.remaining[color as usize];
+        if color != self.turn {
+            return remaining;
+        }
+        remaining.saturating_sub(Self::get_charged(self.get_stage(color), self.get_move_time()))
+    }
+
+    // stops the clock once the side to move runs out of time, and keeps reporting that side afterwards
+    pub fn check_flag(&mut self) -> Option<Color> {
+        if self.flag.is_none() && self.get_remaining(self.turn).is_zero() {
+            self.flag = Some(self.turn);
+            self.pause();
+        }
+        self.flag
+    }
+
+    // running out of time loses, unless the opponent couldn't possibly checkmate anymore
+    pub fn get_flag_outcome(&mut self, board: &Board) -> Option<Outcome> {
+        let flagged = self.check_flag()?;
+        Some(if board.has_insufficient_material(!flagged) { Outcome::Draw } else { Outcome::Win(!flagged) })
+    }
+
+    // the clock as search limits for the side to move, delays count as increments there
+    pub fn get_limits(&self) -> Limits {
+        let stage = self.get_stage(self.turn);
+        Limits {
+            wtime: Some(self.get_remaining(White)),
+            btime: Some(self.get_remaining(Black)),
+            winc: Some(self.get_stage(White).increment),
+            binc: Some(self.get_stage(Black).increment),
+            movestogo: stage.moves.map(|moves| moves - self.moves[self.turn as usize]),
+            ..Default::default()
+        }
+    }
+
+    fn get_stage(&self, color: Color) -> &Stage {
+        let stages = &self.control.stages;
+        &stages[self.stages[color as usize].min(stages.len() - 1)]
+    }
+
+    fn get_move_time(&self) -> Duration {
+        self.spent + self.running_since.map_or(Duration::ZERO, |since| self.source.now().saturating_sub(since))
+    }
+
+    // how much of the time a move took comes off the clock
+    fn get_charged(stage: &Stage, took: Duration) -> Duration {
+        match stage.mode {
+            Mode::SimpleDelay => took.saturating_sub(stage.increment),
+            _ => took,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    // a running clock with white to move, and the time that drives it
+    fn get_clock(control: TimeControl) -> (Clock, ManualTime) {
+        let time = ManualTime::new();
+        let mut clock = Clock::with_source(control, Arc::new(time.clone()));
+        clock.start(White);
+        (clock, time)
+    }
+
+    fn get_board(fen: &str) -> Board {
+        let mut board = Board::new();
+        board.init(fen).unwrap();
+        board
+    }
+
+    #[test]
+    fn sudden_death() {
+        let (mut clock, time) = get_clock(TimeControl::sudden_death(seconds(60)));
+        time.advance(seconds(10));
+        assert_eq!(clock.get_remaining(White), seconds(50));
+        clock.press().unwrap();
+        assert_eq!(clock.get_turn(), Black);
+
+        time.advance(seconds(5));
+        assert_eq!((clock.get_remaining(White), clock.get_remaining(Black)), (seconds(50), seconds(55)));
+    }
+
+    #[test]
+    fn fischer_increment() {
+        let (mut clock, time) = get_clock(TimeControl::fischer(seconds(60), seconds(2)));
+        time.advance(seconds(10));
+        clock.press().unwrap();
+        time.advance(seconds(1));
+        clock.press().unwrap();
+        assert_eq!((clock.get_remaining(White), clock.get_remaining(Black)), (seconds(52), seconds(61)));
+    }
+
+    #[test]
+    fn bronstein_refunds_up_to_the_delay() {
+        let (mut clock, time) = get_clock(TimeControl::bronstein(seconds(60), seconds(5)));
+        time.advance(seconds(3));
+        assert_eq!(clock.get_remaining(White), seconds(57));
+        clock.press().unwrap();
+        assert_eq!(clock.get_remaining(White), seconds(60));
+
+        time.advance(seconds(8));
+        clock.press().unwrap();
+        assert_eq!(clock.get_remaining(Black), seconds(57));
+    }
+
+    #[test]
+    fn simple_delay_waits_before_counting_down() {
+        let (mut clock, time) = get_clock(TimeControl::simple_delay(seconds(60), seconds(5)));
+        time.advance(seconds(3));
+        assert_eq!(clock.get_remaining(White), seconds(60));
+        clock.press().unwrap();
+        assert_eq!(clock.get_remaining(White), seconds(60));
+
+        time.advance(seconds(7));
+        assert_eq!(clock.get_remaining(Black), seconds(58));
+        clock.press().unwrap();
+        assert_eq!(clock.get_remaining(Black), seconds(58));
+    }
+
+    #[test]
+    fn stages_roll_over() {
+        let (mut clock, time) = get_clock(TimeControl::parse("40/5400+30:1800+30").unwrap());
+        for _ in 0..39 {
+            for _ in 0..2 {
+                time.advance(seconds(10));
+                clock.press().unwrap();
+            }
+        }
+        assert_eq!(clock.get_remaining(White), seconds(5400 + 39 * 20));
+        assert_eq!(clock.get_limits().movestogo, Some(1));
+
+        // the 40th move brings the second stage's 30 minutes, which lasts the rest of the game
+        time.advance(seconds(10));
+        clock.press().unwrap();
+        assert_eq!(clock.get_remaining(White), seconds(5400 + 40 * 20 + 1800));
+        assert_eq!(clock.get_remaining(Black), seconds(5400 + 39 * 20));
+        time.advance(seconds(10));
+        clock.press().unwrap();
+        assert_eq!(clock.get_remaining(Black), seconds(5400 + 40 * 20 + 1800));
+        assert_eq!(clock.get_limits().movestogo, None);
+
+        time.advance(seconds(10));
+        clock.press().unwrap();
+        assert_eq!(clock.get_remaining(White), seconds(5400 + 41 * 20 + 1800));
+    }
+
+    #[test]
+    fn pausing_stops_the_time() {
+        let (mut clock, time) = get_clock(TimeControl::sudden_death(seconds(60)));
+        time.advance(seconds(10));
+        clock.pause();
+        assert!(!clock.is_running());
+        time.advance(seconds(100));
+        assert_eq!(clock.get_remaining(White), seconds(50));
+
+        // the move goes on from where it was
+        clock.start(White);
+        time.advance(seconds(5));
+        clock.press().unwrap();
+        assert_eq!((clock.get_remaining(White), clock.get_remaining(Black)), (seconds(45), seconds(60)));
+    }
+
+    #[test]
+    fn detects_the_flag() {
+        let (mut clock, time) = get_clock(TimeControl::sudden_death(seconds(10)));
+        time.advance(seconds(9));
+        assert_eq!(clock.check_flag(), None);
+        time.advance(seconds(2));
+        assert_eq!(clock.check_flag(), Some(White));
+        assert_eq!(clock.get_remaining(White), Duration::ZERO);
+        assert!(!clock.is_running());
+        assert_eq!(clock.press(), Err("Flag has fallen"));
+
+        // the clock stays stopped
+        clock.start(Black);
+        time.advance(seconds(5));
+        assert_eq!((clock.check_flag(), clock.get_remaining(Black)), (Some(White), seconds(10)));
+    }
+
+    #[test]
+    fn flag_outcome() {
+        let board = get_board("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        let (mut clock, time) = get_clock(TimeControl::sudden_death(seconds(10)));
+        assert_eq!(clock.get_flag_outcome(&board), None);
+        time.advance(seconds(10));
+        assert_eq!(clock.get_flag_outcome(&board), Some(Outcome::Draw)); // a lone king can't mate
+
+        let (mut clock, time) = get_clock(TimeControl::sudden_death(seconds(10)));
+        clock.press().unwrap();
+        time.advance(seconds(10));
+        assert_eq!(clock.get_flag_outcome(&board), Some(Outcome::Win(White)));
+        assert_eq!(clock.get_flag_outcome(&get_board("")), Some(Outcome::Win(White)));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::board::{Board, Move};
+
+// extended position description, the first four FEN fields followed by operations like
+// bm Nf3; id "WAC.001"; as used by test suites and position databases
+
+// opcodes whose operands are SAN moves on the position, pv is a line played one move after another
+const MOVE_OPCODES: [&str; 5] = ["am", "bm", "pm", "sm", "pv"];
+// opcodes that take a single number, ce (centipawn evaluation) is the only signed one
+const NUMBER_OPCODES: [&str; 8] = ["acd", "acn", "acs", "ce", "dm", "fmvn", "hmvc", "rc"];
+
+#[derive(Clone)]
+pub struct Epd {
+    pub board: Board, // hmvc and fmvn set its clocks, they start at 0 and 1 otherwise
+    pub operations: Vec<(String, Vec<String>)>, // in line order, opcode and operands with the quotes removed
+}
+
+impl Epd {
+    pub fn new(board: Board) -> Self {
+        Epd { board, operations: Vec::new() }
+    }
+
+    pub fn get_operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    // replaces the operation if it is already there, moves and numbers are checked like when parsing
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) -> Result<(), &'static str> {
+        check_operation(&self.board, opcode, &operands)?;
+        match self.operations.iter_mut().find(|(name, _)| name == opcode) {
+            Some((_, existing)) => *existing = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+        Ok(())
+    }
+
+    pub fn remove_operation(&mut self, opcode: &str) {
+        self.operations.retain(|(name, _)| name != opcode);
+    }
+
+    // the first operand of id, c0 to c9 and other single string operations
+    pub fn get_text(&self, opcode: &str) -> Option<&str> {
+        self.get_operation(opcode)?.first().map(String::as_str)
+    }
+
+    pub fn get_number(&self, opcode: &str) -> Option<i64> {
+        self.get_text(opcode)?.parse().ok()
+    }
+
+    pub fn get_id(&self) -> Option<&str> {
+        self.get_text("id")
+    }
+
+    // c0 is the primary comment, c1 to c9 the secondary ones
+    pub fn get_comment(&self, number: u8) -> Option<&str> {
+        self.get_text(&format!("c{number}"))
+    }
+
+    // operands of bm, am, pm, sm or pv as moves, the pv moves follow each other
+    pub fn get_moves(&self, opcode: &str) -> Option<Vec<Move>> {
+        parse_moves(&self.board, opcode, self.get_operation(opcode)?).ok()
+    }
+
+    // writes the moves in SAN, they must be legal on the board, one after another for pv
+    pub fn set_moves(&mut self, opcode: &str, moves: &[Move]) -> Result<(), &'static str> {
+        let mut board = self.board;
+        let mut operands = Vec::new();
+        for &r#move in moves {
+            if !board.get_legal_moves().contains(&r#move) {
+                return Err("Illegal move in EPD operation");
+            }
+            operands.push(board.get_san(r#move));
+            if opcode == "pv" {
+                board.apply_move(r#move);
+            }
+        }
+        self.set_operation(opcode, operands)
+    }
+}
+
+fn is_opcode(opcode: &str) -> bool {
+    opcode.len() <= 15
+        && opcode.starts_with(|c: char| c.is_ascii_alphabetic())
+        && opcode.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_moves(board: &Board, opcode: &str, operands: &[String]) -> Result<Vec<Move>, &'static str> {
+    let mut board = *board;
+    let mut moves = Vec::new();
+    for operand in operands {
+        let r#move = board.parse_san(operand)?;
+        if opcode == "pv" {
+            board.apply_move(r#move);
+        }
+        moves.push(r#move);
+    }
+    Ok(moves)
+}
+
+fn check_operation(board: &Board, opcode: &str, operands: &[String]) -> Result<(), &'static str> {
+    if !is_opcode(opcode) {
+        return Err("Invalid EPD opcode");
+    }
+    // there's no escaping them inside a quoted string
+    if operands.iter().any(|operand| operand.contains('"')) {
+        return Err("EPD operands can't contain quotes");
+    }
+
+    if MOVE_OPCODES.contains(&opcode) {
+        parse_moves(board, opcode, operands)?;
+    } else if NUMBER_OPCODES.contains(&opcode) {
+        let valid = match operands {
+            [number] if opcode == "ce" => number.parse::<i64>().is_ok(),
+            [number] => number.parse::<u64>().is_ok(),
+            _ => false,
+        };
+        if !valid {
+            return Err("Invalid number in EPD operation");
+        }
+    }
+    Ok(())
+}
+
+// splits the operations into opcode and operands, semicolons inside quoted strings don't end them
+fn get_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, &'static str> {
+    let mut operations = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                if !token.is_empty() {
+                    return Err("Invalid quoted string in EPD");
+                }
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    token.push(c);
+                }
+                if !closed {
+                    return Err("Unterminated string in EPD");
+                }
+                tokens.push(std::mem::take(&mut token));
+            },
+            ';' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                if tokens.is_empty() {
+                    return Err("Empty EPD operation");
+                }
+                let opcode = tokens.remove(0);
+                operations.push((opcode, std::mem::take(&mut tokens)));
+            },
+            c if c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            },
+            c => token.push(c),
+        }
+    }
+
+    if !token.is_empty() || !tokens.is_empty() {
+        return Err("EPD operation must end with a semicolon");
+    }
+    Ok(operations)
+}
+
+// parse a single line, positions are in standard chess, with chess960 castling rights as in X-FEN
+pub fn parse_epd(line: &str) -> Result<Epd, &'static str> {
+    let mut fields = Vec::new();
+    let mut rest = line.trim_start();
+    while fields.len() < 4 {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err("EPD must start with the four FEN fields");
+        }
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    let operations = get_operations(rest)?;
+    let number = |opcode: &str, default: &'static str| {
+        operations.iter().find(|(name, _)| name == opcode).and_then(|(_, operands)| operands.first().map(String::as_str)).unwrap_or(default)
+    };
+    let fen = format!("{} {} {}", fields.join(" "), number("hmvc", "0"), number("fmvn", "1"));
+
+    let mut epd = Epd::new(Board::new());
+    epd.board.init(&fen)?;
+    for (opcode, operands) in &operations {
+        check_operation(&epd.board, opcode, operands)?;
+    }
+    epd.operations = operations;
+    Ok(epd)
+}
+
+// one position per line, blank lines are skipped
+pub fn parse_epds(text: &str) -> Vec<Result<Epd, &'static str>> {
+    text.lines().filter(|line| !line.trim().is_empty()).map(parse_epd).collect()
+}
+
+impl std::fmt::Display for Epd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the FEN without the halfmove clock and fullmove number
+        let fen = self.board.get_fen();
+        let fields: Vec<&str> = fen.split(' ').collect();
+        write!(f, "{}", fields[..fields.len() - 2].join(" "))?;
+
+        for (opcode, operands) in &self.operations {
+            write!(f, " {opcode}")?;
+            for operand in operands {
+                // id and the comments are always quoted, other operands only when they have to be
+                let quoted = opcode == "id" || opcode.len() == 2 && opcode.starts_with('c') && opcode.as_bytes()[1].is_ascii_digit()
+                    || operand.is_empty() || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"');
+                if quoted {
+                    write!(f, " \"{operand}\"")?;
+                } else {
+                    write!(f, " {operand}")?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAC: &str = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#;
+
+    #[test]
+    fn round_trips() {
+        let epd = parse_epd(WAC).unwrap();
+        assert_eq!(epd.get_id(), Some("WAC.001"));
+        assert_eq!(epd.get_moves("bm"), Some(vec![Move::from_uci("g3g6").unwrap()]));
+        assert_eq!(epd.to_string(), WAC);
+        assert_eq!(parse_epd(&epd.to_string()).unwrap().operations, epd.operations);
+
+        let line = r#"4k3/8/8/8/8/8/8/4K2R w K - c0 "first; second"; c1 "a \"quote\""; ce -35; acd 12;"#;
+        assert!(parse_epd(line).is_err());
+        let line = r#"4k3/8/8/8/8/8/8/4K2R w K - c0 "first; second"; ce -35; acd 12;"#;
+        let epd = parse_epd(line).unwrap();
+        assert_eq!(epd.get_comment(0), Some("first; second"));
+        assert_eq!(epd.get_number("ce"), Some(-35));
+        assert_eq!(epd.get_number("acd"), Some(12));
+        assert_eq!(epd.to_string(), line);
+    }
+
+    #[test]
+    fn rejects_bad_operations() {
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K2R w K - bm Rh9;").err(), Some("Invalid target square"));
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K2R w K - bm Ra8;").err(), Some("Illegal move"));
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K2R w K - acd -1;").err(), Some("Invalid number in EPD operation"));
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K2R w K - bm Rh8").err(), Some("EPD operation must end with a semicolon"));
+        assert_eq!(parse_epd(r#"4k3/8/8/8/8/8/8/4K2R w K - id "open;"#).err(), Some("Unterminated string in EPD"));
+        assert_eq!(parse_epd("4k3/8/8/8 w K - bm Rh8;").err(), Some("FEN piece placement must have 8 ranks"));
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K2R w").err(), Some("EPD must start with the four FEN fields"));
+
+        let mut epd = parse_epd("4k3/8/8/8/8/8/8/4K2R w K -").unwrap();
+        assert_eq!(epd.set_moves("bm", &[Move::from_uci("h1h7").unwrap()]), Ok(()));
+        assert_eq!(epd.set_moves("bm", &[Move::from_uci("h1a8").unwrap()]), Err("Illegal move in EPD operation"));
+        assert_eq!(epd.set_operation("id", vec!["say \"hi\"".to_string()]), Err("EPD operands can't contain quotes"));
+        assert_eq!(epd.set_operation("1d", vec![]), Err("Invalid EPD opcode"));
+        assert_eq!(epd.get_text("bm"), Some("Rh7"));
+    }
+
+    #[test]
+    fn plays_the_pv() {
+        let mut epd = parse_epd("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - pv Bb5 a6 Ba4 Nf6 O-O;").unwrap();
+        let moves: Vec<String> = epd.get_moves("pv").unwrap().iter().map(ToString::to_string).collect();
+        assert_eq!(moves, ["f1b5", "a7a6", "b5a4", "g8f6", "e1g1"]);
+
+        // each move is read in the position the ones before it lead to
+        assert!(parse_epd("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - pv Bb5 Bb5;").is_err());
+
+        let moves = ["f1c4", "f8c5", "e1g1"].map(|uci| Move::from_uci(uci).unwrap());
+        epd.set_moves("pv", &moves).unwrap();
+        assert_eq!(epd.get_operation("pv"), Some(&["Bc4".to_string(), "Bc5".to_string(), "O-O".to_string()][..]));
+        assert_eq!(epd.set_moves("bm", &moves), Err("Illegal move in EPD operation"));
+        epd.remove_operation("pv");
+        assert_eq!(epd.get_operation("pv"), None);
+    }
+
+    #[test]
+    fn move_counters() {
+        let epd = parse_epd("4k3/8/8/8/8/8/8/4K2R b K - hmvc 17; fmvn 42;").unwrap();
+        assert_eq!((epd.board.get_halfmove(), epd.board.get_fullmove()), (17, 42));
+        let epd = parse_epd("4k3/8/8/8/8/8/8/4K2R b K -").unwrap();
+        assert_eq!((epd.board.get_halfmove(), epd.board.get_fullmove()), (0, 1));
+        // the counters stay operations, and aren't written twice
+        assert_eq!(parse_epd("4k3/8/8/8/8/8/8/4K2R b K - hmvc 17; fmvn 42;").unwrap().to_string(), "4k3/8/8/8/8/8/8/4K2R b K - hmvc 17; fmvn 42;");
+    }
+
+    #[test]
+    fn parses_files() {
+        let epds = parse_epds(&format!("{WAC}\n\n4k3/8/8/8/8/8/8/4K2R w K - bm Ra8;\n"));
+        assert_eq!(epds.len(), 2);
+        assert!(epds[0].is_ok() && epds[1].is_err());
+    }
+}
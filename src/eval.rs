@@ -0,0 +1,480 @@
+use crate::{board::{Board, Color::{self, *}}, pieces::{Piece, Pieces::*}, variant::Variant};
+
+// handcrafted evaluation, every term is a (middlegame, endgame) pair that is blended by game phase
+
+pub type Pair = (i32, i32);
+
+// game phase contributed by each piece, 24 is the starting position and 0 is a pawn endgame
+const PHASE: [i32; 6] = [0, 2, 1, 1, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+// typical number of reachable squares, mobility is scored relative to these
+const MOBILITY_BASE: [i32; 6] = [0, 7, 4, 7, 14, 0];
+
+const DIRECTIONS_ROOK: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const DIRECTIONS_BISHOP: [(i8, i8); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Weights {
+    pub material: [Pair; 6], // indexed by Pieces
+    pub psqt_mg: [[i32; 64]; 6], // from white's point of view, a8 first and h1 last
+    pub psqt_eg: [[i32; 64]; 6],
+    pub mobility: [Pair; 6], // per reachable square above or below MOBILITY_BASE
+    pub bishop_pair: Pair,
+    pub pawn_shield: Pair, // per own pawn one or two squares in front of the king
+    pub open_king_file: Pair, // per file next to the king without an own pawn
+    pub king_attacker: Pair, // per enemy piece attacking the squares around the king
+    pub doubled_pawn: Pair,
+    pub isolated_pawn: Pair,
+    pub passed_pawn: [Pair; 8], // by rank, counted from the pawn's own side
+    pub tempo: i32, // bonus for having the move
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        DEFAULT_WEIGHTS
+    }
+}
+
+// score of each term from white's point of view, already blended by phase
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Evaluation {
+    pub material: i32,
+    pub psqt: i32,
+    pub mobility: i32,
+    pub king_safety: i32,
+    pub pawn_structure: i32,
+    pub phase: i32, // MAX_PHASE in the opening down to 0 in a pawn endgame
+    pub total: i32,
+}
+
+// centipawns from the side to move's point of view
+pub fn evaluate(board: &Board, weights: &Weights) -> i32 {
+    let total = evaluate_terms(board, weights).total;
+    weights.tempo + if board.get_turn() == White { total } else { -total }
+}
+
+// breakdown of the evaluation for displaying who's better and why
+pub fn evaluate_terms(board: &Board, weights: &Weights) -> Evaluation {
+    let mut material = (0, 0);
+    let mut psqt = (0, 0);
+    let mut mobility = (0, 0);
+    let mut phase = 0;
+    let mut bishops = [0, 0];
+    let mut king_attackers = [0, 0];
+
+    let kings = [board.get_king(Black), board.get_king(White)];
+
+    for square in board.get_state() {
+        let Some(piece) = square.get_piece() else { continue };
+        let (x, y) = square.get_coords_tuple();
+        let index = piece.r#type as usize;
+        let sign = if piece.color == White { 1 } else { -1 };
+
+        // tables are written from white's side with rank 8 first
+        let table_index = match piece.color {
+            White => (7 - y as usize) * 8 + x as usize,
+            Black => y as usize * 8 + x as usize,
+        };
+
+        add(&mut material, weights.material[index], sign);
+        add(&mut psqt, (weights.psqt_mg[index][table_index], weights.psqt_eg[index][table_index]), sign);
+        phase += PHASE[index];
+
+        if piece.r#type == Bishop {
+            bishops[piece.color as usize] += 1;
+        }
+
+        if matches!(piece.r#type, Knight | Bishop | Rook | Queen) {
+            let enemy_king = kings[(!piece.color) as usize];
+            let (count, attacks_king) = get_mobility(board, piece, x, y, enemy_king);
+            add(&mut mobility, scale(weights.mobility[index], count - MOBILITY_BASE[index]), sign);
+            if attacks_king {
+                king_attackers[(!piece.color) as usize] += 1;
+            }
+        }
+    }
+
+    // crazyhouse pieces in hand count as material, they can come back at any time
+    for color in [White, Black] {
+        for (index, &count) in board.get_pocket(color).iter().enumerate() {
+            add(&mut material, scale(weights.material[index], count as i32), if color == White { 1 } else { -1 });
+        }
+    }
+
+    for color in [White, Black] {
+        if bishops[color as usize] >= 2 {
+            add(&mut material, weights.bishop_pair, if color == White { 1 } else { -1 });
+        }
+    }
+
+    let mut king_safety = (0, 0);
+    for color in [White, Black] {
+        let Some(king) = kings[color as usize] else { continue };
+        let sign = if color == White { 1 } else { -1 };
+        add(&mut king_safety, get_king_safety(board, weights, color, king, king_attackers[color as usize]), sign);
+    }
+
+    let pawn_structure = get_pawn_structure(board, weights);
+
+    let phase = phase.min(MAX_PHASE);
+    let taper = |(mg, eg): Pair| (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE;
+
+    let mut evaluation = Evaluation {
+        material: taper(material),
+        psqt: taper(psqt),
+        mobility: taper(mobility),
+        king_safety: taper(king_safety),
+        pawn_structure: taper(pawn_structure),
+        phase,
+        total: 0,
+    };
+    // antichess is won by giving everything away
+    if board.get_variant() == Variant::Antichess {
+        evaluation.material = -evaluation.material;
+    }
+    evaluation.total = evaluation.material + evaluation.psqt + evaluation.mobility + evaluation.king_safety + evaluation.pawn_structure;
+    evaluation
+}
+
+fn add(total: &mut Pair, (mg, eg): Pair, sign: i32) {
+    total.0 += mg * sign;
+    total.1 += eg * sign;
+}
+
+fn scale((mg, eg): Pair, factor: i32) -> Pair {
+    (mg * factor, eg * factor)
+}
+
+// pseudo-legal squares the piece can reach, and whether any of them touch the enemy king
+fn get_mobility(board: &Board, piece: Piece, x: u8, y: u8, enemy_king: Option<u8>) -> (i32, bool) {
+    let near_king = |tx: i8, ty: i8| enemy_king.is_some_and(|king| {
+        let (kx, ky) = ((king >> 4) as i8, (king & 0x0F) as i8);
+        (kx - tx).abs() <= 1 && (ky - ty).abs() <= 1
+    });
+
+    let mut count = 0;
+    let mut attacks_king = false;
+    let mut visit = |tx: i8, ty: i8| -> bool { // returns whether a slider can keep going
+        if !(0..8).contains(&tx) || !(0..8).contains(&ty) {
+            return false;
+        }
+
+        attacks_king |= near_king(tx, ty);
+        match board.get_index(tx as u8, ty as u8).get_piece() {
+            Some(other) => {
+                if other.color != piece.color {
+                    count += 1;
+                }
+                false
+            },
+            None => {
+                count += 1;
+                true
+            },
+        }
+    };
+
+    let (x, y) = (x as i8, y as i8);
+    if piece.r#type == Knight {
+        for (dx, dy) in Piece::OFFSETS_KNIGHT {
+            visit(x + dx, y + dy);
+        }
+        return (count, attacks_king);
+    }
+
+    let directions: &[(i8, i8)] = match piece.r#type {
+        Rook => &DIRECTIONS_ROOK,
+        Bishop => &DIRECTIONS_BISHOP,
+        _ => &Piece::OFFSETS_KING, // queen, every direction
+    };
+
+    for (dx, dy) in directions {
+        let mut i = 1;
+        while visit(x + dx * i, y + dy * i) {
+            i += 1;
+        }
+    }
+
+    (count, attacks_king)
+}
+
+fn get_king_safety(board: &Board, weights: &Weights, color: Color, king: u8, attackers: i32) -> Pair {
+    let (kx, ky) = ((king >> 4) as i8, (king & 0x0F) as i8);
+    let forward = if color == White { 1 } else { -1 };
+    let mut safety = scale(weights.king_attacker, attackers);
+
+    for file in (kx - 1)..=(kx + 1) {
+        if !(0..8).contains(&file) {
+            continue;
+        }
+
+        let own_pawn = |rank: i8| (0..8).contains(&rank) && board.get_index(file as u8, rank as u8).get_piece()
+            .is_some_and(|piece| piece.r#type == Pawn && piece.color == color);
+
+        if own_pawn(ky + forward) || own_pawn(ky + 2 * forward) {
+            add(&mut safety, weights.pawn_shield, 1);
+        }
+
+        if !(0..8).any(own_pawn) {
+            add(&mut safety, weights.open_king_file, 1);
+        }
+    }
+
+    safety
+}
+
+// doubled, isolated and passed pawns, from white's point of view
+fn get_pawn_structure(board: &Board, weights: &Weights) -> Pair {
+    let mut pawns = [[0u8; 8]; 2]; // rank bitmask per file for each color
+    for square in board.get_state() {
+        if let Some(piece) = square.get_piece().filter(|piece| piece.r#type == Pawn) {
+            let (x, y) = square.get_coords_tuple();
+            pawns[piece.color as usize][x as usize] |= 1 << y;
+        }
+    }
+
+    let mut structure = (0, 0);
+    for color in [White, Black] {
+        let sign = if color == White { 1 } else { -1 };
+        let own = &pawns[color as usize];
+        let enemy = &pawns[(!color) as usize];
+
+        for file in 0..8 {
+            if own[file] == 0 {
+                continue;
+            }
+
+            let count = own[file].count_ones() as i32;
+            add(&mut structure, scale(weights.doubled_pawn, count - 1), sign);
+
+            let neighbours = (file.saturating_sub(1)..=(file + 1).min(7)).filter(|&f| f != file);
+            if neighbours.clone().all(|f| own[f] == 0) {
+                add(&mut structure, scale(weights.isolated_pawn, count), sign);
+            }
+
+            for rank in 0..8 {
+                if own[file] & (1 << rank) == 0 {
+                    continue;
+                }
+
+                // squares in front of the pawn, from its own side
+                let ahead: u8 = match color {
+                    White => !((2u16 << rank) - 1) as u8,
+                    Black => ((1u16 << rank) - 1) as u8,
+                };
+                let blocked = (file.saturating_sub(1)..=(file + 1).min(7)).any(|f| enemy[f] & ahead != 0);
+                if !blocked {
+                    let relative_rank = if color == White { rank } else { 7 - rank };
+                    add(&mut structure, weights.passed_pawn[relative_rank], sign);
+                }
+            }
+        }
+    }
+
+    structure
+}
+
+// tables are laid out in Pieces order: pawn, rook, knight, bishop, queen, king
+pub const DEFAULT_WEIGHTS: Weights = Weights {
+    material: [(82, 94), (477, 512), (337, 281), (365, 297), (1025, 936), (0, 0)],
+    psqt_mg: [
+        [
+              0,   0,   0,   0,   0,   0,   0,   0,
+             98, 134,  61,  95,  68, 126,  34, -11,
+             -6,   7,  26,  31,  65,  56,  25, -20,
+            -14,  13,   6,  21,  23,  12,  17, -23,
+            -27,  -2,  -5,  12,  17,   6,  10, -25,
+            -26,  -4,  -4, -10,   3,   3,  33, -12,
+            -35,  -1, -20, -23, -15,  24,  38, -22,
+              0,   0,   0,   0,   0,   0,   0,   0,
+        ],
+        [
+             32,  42,  32,  51,  63,   9,  31,  43,
+             27,  32,  58,  62,  80,  67,  26,  44,
+             -5,  19,  26,  36,  17,  45,  61,  16,
+            -24, -11,   7,  26,  24,  35,  -8, -20,
+            -36, -26, -12,  -1,   9,  -7,   6, -23,
+            -45, -25, -16, -17,   3,   0,  -5, -33,
+            -44, -16, -20,  -9,  -1,  11,  -6, -71,
+            -19, -13,   1,  17,  16,   7, -37, -26,
+        ],
+        [
+           -167, -89, -34, -49,  61, -97, -15, -107,
+            -73, -41,  72,  36,  23,  62,   7,  -17,
+            -47,  60,  37,  65,  84, 129,  73,   44,
+             -9,  17,  19,  53,  37,  69,  18,   22,
+            -13,   4,  16,  13,  28,  19,  21,   -8,
+            -23,  -9,  12,  10,  19,  17,  25,  -16,
+            -29, -53, -12,  -3,  -1,  18, -14,  -19,
+           -105, -21, -58, -33, -17, -28, -19,  -23,
+        ],
+        [
+            -29,   4, -82, -37, -25, -42,   7,  -8,
+            -26,  16, -18, -13,  30,  59,  18, -47,
+            -16,  37,  43,  40,  35,  50,  37,  -2,
+             -4,   5,  19,  50,  37,  37,   7,  -2,
+             -6,  13,  13,  26,  34,  12,  10,   4,
+              0,  15,  15,  15,  14,  27,  18,  10,
+              4,  15,  16,   0,   7,  21,  33,   1,
+            -33,  -3, -14, -21, -13, -12, -39, -21,
+        ],
+        [
+            -28,   0,  29,  12,  59,  44,  43,  45,
+            -24, -39,  -5,   1, -16,  57,  28,  54,
+            -13, -17,   7,   8,  29,  56,  47,  57,
+            -27, -27, -16, -16,  -1,  17,  -2,   1,
+             -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+            -14,   2, -11,  -2,  -5,   2,  14,   5,
+            -35,  -8,  11,   2,   8,  15,  -3,   1,
+             -1, -18,  -9,  10, -15, -25, -31, -50,
+        ],
+        [
+            -65,  23,  16, -15, -56, -34,   2,  13,
+             29,  -1, -20,  -7,  -8,  -4, -38, -29,
+             -9,  24,   2, -16, -20,   6,  22, -22,
+            -17, -20, -12, -27, -30, -25, -14, -36,
+            -49,  -1, -27, -39, -46, -44, -33, -51,
+            -14, -14, -22, -46, -44, -30, -15, -27,
+              1,   7,  -8, -64, -43, -16,   9,   8,
+            -15,  36,  12, -54,   8, -28,  24,  14,
+        ],
+    ],
+    psqt_eg: [
+        [
+              0,   0,   0,   0,   0,   0,   0,   0,
+            178, 173, 158, 134, 147, 132, 165, 187,
+             94, 100,  85,  67,  56,  53,  82,  84,
+             32,  24,  13,   5,  -2,   4,  17,  17,
+             13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+              4,   7,  -6,   1,   0,  -5,  -1,  -8,
+             13,   8,   8,  10,  13,   0,   2,  -7,
+              0,   0,   0,   0,   0,   0,   0,   0,
+        ],
+        [
+             13,  10,  18,  15,  12,  12,   8,   5,
+             11,  13,  13,  11,  -3,   3,   8,   3,
+              7,   7,   7,   5,   4,  -3,  -5,  -3,
+              4,   3,  13,   1,   2,   1,  -1,   2,
+              3,   5,   8,   4,  -5,  -6,  -8, -11,
+             -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+             -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+             -9,   2,   3,  -1,  -5, -13,   4, -20,
+        ],
+        [
+            -58, -38, -13, -28, -31, -27, -63, -99,
+            -25,  -8, -25,  -2,  -9, -25, -24, -52,
+            -24, -20,  10,   9,  -1,  -9, -19, -41,
+            -17,   3,  22,  22,  22,  11,   8, -18,
+            -18,  -6,  16,  25,  16,  17,   4, -18,
+            -23,  -3,  -1,  15,  10,  -3, -20, -22,
+            -42, -20, -10,  -5,  -2, -20, -23, -44,
+            -29, -51, -23, -15, -22, -18, -50, -64,
+        ],
+        [
+            -14, -21, -11,  -8,  -7,  -9, -17, -24,
+             -8,  -4,   7, -12,  -3, -13,  -4, -14,
+              2,  -8,   0,  -1,  -2,   6,   0,   4,
+             -3,   9,  12,   9,  14,  10,   3,   2,
+             -6,   3,  13,  19,   7,  10,  -3,  -9,
+            -12,  -3,   8,  10,  13,   3,  -7, -15,
+            -14, -18,  -7,  -1,   4,  -9, -15, -27,
+            -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+        ],
+        [
+             -9,  22,  22,  27,  27,  19,  10,  20,
+            -17,  20,  32,  41,  58,  25,  30,   0,
+            -20,   6,   9,  49,  47,  35,  19,   9,
+              3,  22,  24,  45,  57,  40,  57,  36,
+            -18,  28,  19,  47,  31,  34,  39,  23,
+            -16, -27,  15,   6,   9,  17,  10,   5,
+            -22, -23, -30, -16, -16, -23, -36, -32,
+            -33, -28, -22, -43,  -5, -32, -20, -41,
+        ],
+        [
+            -74, -35, -18, -18, -11,  15,   4, -17,
+            -12,  17,  14,  17,  17,  38,  23,  11,
+             10,  17,  23,  15,  20,  45,  44,  13,
+             -8,  22,  24,  27,  26,  33,  26,   3,
+            -18,  -4,  21,  24,  27,  23,   9, -11,
+            -19,  -3,  11,  21,  23,  16,   7,  -9,
+            -27, -11,   4,  13,  14,   4,  -5, -17,
+            -53, -34, -21, -11, -28, -14, -24, -43,
+        ],
+    ],
+    mobility: [(0, 0), (2, 4), (4, 4), (5, 5), (1, 2), (0, 0)],
+    bishop_pair: (30, 50),
+    pawn_shield: (12, 0),
+    open_king_file: (-15, 0),
+    king_attacker: (-20, -5),
+    doubled_pawn: (-10, -20),
+    isolated_pawn: (-10, -15),
+    passed_pawn: [(0, 0), (5, 10), (5, 15), (10, 25), (20, 45), (35, 75), (60, 120), (0, 0)],
+    tempo: 10,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the same position with the colors swapped and the board flipped top to bottom
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| text.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect::<String>();
+        let placement = fields[0].split('/').rev().map(swap_case).collect::<Vec<_>>().join("/");
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+        castling.sort_by_key(|c| (c.is_ascii_lowercase(), *c != 'K' && *c != 'k'));
+        let en_passant = fields[3].replace('3', "x").replace('6', "3").replace('x', "6");
+        format!("{placement} {turn} {} {en_passant} {} {}", castling.into_iter().collect::<String>(), fields[4], fields[5])
+    }
+
+    fn get_board(fen: &str) -> Board {
+        let mut board = Board::new();
+        board.init(fen).unwrap();
+        board
+    }
+
+    #[test]
+    fn symmetric() {
+        let weights = Weights::default();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR b KQkq c6 0 4",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 2 8",
+            "6k1/5ppp/8/8/8/8/1B3PPP/1B4K1 b - - 0 40",
+        ] {
+            let (board, mirrored) = (get_board(fen), get_board(&mirror(fen)));
+            let (terms, mirrored_terms) = (evaluate_terms(&board, &weights), evaluate_terms(&mirrored, &weights));
+            assert_eq!(
+                mirrored_terms,
+                Evaluation {
+                    material: -terms.material,
+                    psqt: -terms.psqt,
+                    mobility: -terms.mobility,
+                    king_safety: -terms.king_safety,
+                    pawn_structure: -terms.pawn_structure,
+                    phase: terms.phase,
+                    total: -terms.total,
+                },
+                "{fen}"
+            );
+            // and the side to move sees the same score
+            assert_eq!(evaluate(&board, &weights), evaluate(&mirrored, &weights), "{fen}");
+        }
+    }
+
+    #[test]
+    fn start_position() {
+        let weights = Weights::default();
+        let board = get_board("");
+        let terms = evaluate_terms(&board, &weights);
+        assert_eq!(terms, Evaluation { phase: MAX_PHASE, ..Default::default() });
+        assert_eq!(evaluate(&board, &weights), weights.tempo);
+
+        // a pawn up in the opening is worth the middlegame value of a pawn
+        let board = get_board("rnbqkbnr/ppp1pppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(evaluate_terms(&board, &weights).material, weights.material[Pawn as usize].0);
+    }
+}
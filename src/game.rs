@@ -0,0 +1,630 @@
+use std::fmt::Display;
+use crate::{
+    board::{Board, Color::{self, *}, Move},
+    clock::Clock,
+    variant::{Outcome, Variant},
+};
+
+// a game between two players: the position, the moves that led there, the clock and how it ended
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Player {
+    pub name: String,
+    pub rating: Option<u16>,
+}
+
+impl Player {
+    pub fn new(name: &str) -> Self {
+        Player { name: name.into(), rating: None }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Variant, // the variant's own way of ending, like three checks or an exploded king
+    InsufficientMaterial, // neither side can checkmate anymore
+    FivefoldRepetition,
+    SeventyFiveMoves,
+    ThreefoldRepetition, // claimed
+    FiftyMoves, // claimed
+    Agreement,
+    Resignation,
+    Timeout, // a draw when the opponent couldn't have won anyway
+    Aborted,
+}
+
+impl Termination {
+    // for the PGN Termination tag
+    pub fn get_pgn_name(&self) -> &'static str {
+        match self {
+            Termination::Timeout => "time forfeit",
+            Termination::Aborted => "abandoned",
+            _ => "normal",
+        }
+    }
+}
+
+impl Display for Termination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::Variant => "variant ending",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::FivefoldRepetition => "fivefold repetition",
+            Termination::SeventyFiveMoves => "75 move rule",
+            Termination::ThreefoldRepetition => "threefold repetition",
+            Termination::FiftyMoves => "50 move rule",
+            Termination::Agreement => "agreement",
+            Termination::Resignation => "resignation",
+            Termination::Timeout => "timeout",
+            Termination::Aborted => "aborted",
+        })
+    }
+}
+
+// the result token as written at the end of a PGN game, * while it is still going or if it was aborted
+pub fn get_result_token(outcome: Option<Outcome>) -> &'static str {
+    match outcome {
+        Some(Outcome::Win(White)) => "1-0",
+        Some(Outcome::Win(Black)) => "0-1",
+        Some(Outcome::Draw) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+#[derive(Clone)]
+pub struct Game {
+    pub white: Player,
+    pub black: Player,
+    pub tags: Vec<(String, String)>, // Event, Site, Date and anything else that goes into the PGN
+    start: Board,
+    board: Board,
+    moves: Vec<Move>,
+    hashes: Vec<u64>, // every position so far, the start included, for repetitions
+    clock: Option<Clock>,
+    result: Option<Outcome>,
+    termination: Option<Termination>,
+    draw_offer: Option<Color>, // who offered a draw that hasn't been answered yet
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Self {
+        let mut board = Board::new();
+        board.init("").unwrap();
+        Self::from_board(board)
+    }
+
+    // a game starting from any position, of whatever variant the board is set to, already over if
+    // the position ends it
+    pub fn from_board(board: Board) -> Self {
+        let mut game = Game {
+            white: Player::default(),
+            black: Player::default(),
+            tags: Vec::new(),
+            start: board,
+            board,
+            moves: Vec::new(),
+            hashes: vec![board.get_hash()],
+            clock: None,
+            result: None,
+            termination: None,
+            draw_offer: None,
+        };
+        if let Some((outcome, termination)) = game.get_automatic_ending() {
+            game.finish(outcome, termination);
+        }
+        game
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_start(&self) -> &Board {
+        &self.start
+    }
+
+    pub fn get_moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn get_result(&self) -> Option<Outcome> {
+        self.result
+    }
+
+    pub fn get_termination(&self) -> Option<Termination> {
+        self.termination
+    }
+
+    pub fn get_result_token(&self) -> &'static str {
+        get_result_token(self.result)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.termination.is_some()
+    }
+
+    pub fn get_player(&self, color: Color) -> &Player {
+        match color {
+            White => &self.white,
+            Black => &self.black,
+        }
+    }
+
+    // the clock starts running for the side to move right away
+    pub fn set_clock(&mut self, mut clock: Clock) {
+        clock.start(self.board.get_turn());
+        self.clock = Some(clock);
+    }
+
+    pub fn get_clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    pub fn get_clock_mut(&mut self) -> Option<&mut Clock> {
+        self.clock.as_mut()
+    }
+
+    pub fn play(&mut self, r#move: Move) -> Result<(), &'static str> {
+        if self.is_over() || self.check_flag() {
+            return Err("Game is over");
+        }
+
+        let mover = self.board.get_turn();
+        let mut board = self.board;
+        board.play(r#move)?;
+        if self.clock.as_mut().is_some_and(|clock| clock.press().is_err()) {
+            self.check_flag();
+            return Err("Game is over");
+        }
+        self.board = board;
+        self.moves.push(r#move);
+        self.hashes.push(self.board.get_hash());
+
+        // moving instead of answering turns the offer down, an offer made before moving still stands
+        if self.draw_offer == Some(!mover) {
+            self.draw_offer = None;
+        }
+
+        if let Some((outcome, termination)) = self.get_automatic_ending() {
+            self.finish(outcome, termination);
+        }
+        Ok(())
+    }
+
+    // take back the last move, the game goes on even if that move had ended it. The clock isn't wound
+    // back: the mover keeps paying for the undone move (its time, increment and place in the stage)
+    // and their clock runs again from zero, the time the opponent spent on a reply isn't charged
+    pub fn undo(&mut self) -> Result<Move, &'static str> {
+        let r#move = self.moves.pop().ok_or("No moves to take back")?;
+        self.hashes.pop();
+        self.board = self.start;
+        for &r#move in &self.moves {
+            self.board.apply_move(r#move);
+        }
+        self.result = None;
+        self.termination = None;
+        self.draw_offer = None;
+
+        // also starts it again after a game ending move stopped it
+        if let Some(clock) = &mut self.clock {
+            clock.pause();
+            clock.start(self.board.get_turn());
+        }
+        Ok(r#move)
+    }
+
+    pub fn play_san(&mut self, san: &str) -> Result<(), &'static str> {
+        self.play(self.board.parse_san(san)?)
+    }
+
+    // endings that don't need anyone to claim them
+    fn get_automatic_ending(&self) -> Option<(Outcome, Termination)> {
+        if let Some(outcome) = self.board.get_variant_outcome() {
+            return Some((outcome, Termination::Variant));
+        }
+
+        if self.board.get_legal_moves().is_empty() {
+            let outcome = self.board.get_outcome_without_moves();
+            let termination = match outcome {
+                _ if self.board.get_variant() == Variant::Antichess => Termination::Variant,
+                Outcome::Draw => Termination::Stalemate,
+                Outcome::Win(_) => Termination::Checkmate,
+            };
+            return Some((outcome, termination));
+        }
+
+        if self.board.has_insufficient_material(White) && self.board.has_insufficient_material(Black) {
+            return Some((Outcome::Draw, Termination::InsufficientMaterial));
+        }
+        if self.get_repetitions() >= 5 {
+            return Some((Outcome::Draw, Termination::FivefoldRepetition));
+        }
+        if self.board.get_halfmove() >= 150 {
+            return Some((Outcome::Draw, Termination::SeventyFiveMoves));
+        }
+        None
+    }
+
+    // how often the current position has come up, this time included
+    pub fn get_repetitions(&self) -> usize {
+        let hash = self.board.get_hash();
+        self.hashes.iter().filter(|&&previous| previous == hash).count()
+    }
+
+    // the draw either player could claim right now
+    pub fn get_claimable_draw(&self) -> Option<Termination> {
+        if self.is_over() {
+            None
+        } else if self.get_repetitions() >= 3 {
+            Some(Termination::ThreefoldRepetition)
+        } else if self.board.get_halfmove() >= 100 {
+            Some(Termination::FiftyMoves)
+        } else {
+            None
+        }
+    }
+
+    pub fn claim_draw(&mut self) -> Result<(), &'static str> {
+        let termination = self.get_claimable_draw().ok_or("No draw to claim")?;
+        self.finish(Outcome::Draw, termination);
+        Ok(())
+    }
+
+    pub fn resign(&mut self, color: Color) -> Result<(), &'static str> {
+        if self.is_over() {
+            return Err("Game is over");
+        }
+        self.finish(Outcome::Win(!color), Termination::Resignation);
+        Ok(())
+    }
+
+    // offering a draw when the opponent already did accepts theirs
+    pub fn offer_draw(&mut self, color: Color) -> Result<(), &'static str> {
+        if self.is_over() {
+            return Err("Game is over");
+        }
+        if self.draw_offer == Some(!color) {
+            return self.accept_draw(color);
+        }
+        self.draw_offer = Some(color);
+        Ok(())
+    }
+
+    pub fn get_draw_offer(&self) -> Option<Color> {
+        self.draw_offer
+    }
+
+    pub fn accept_draw(&mut self, color: Color) -> Result<(), &'static str> {
+        if self.is_over() {
+            return Err("Game is over");
+        }
+        if self.draw_offer != Some(!color) {
+            return Err("No draw offer to accept");
+        }
+        self.finish(Outcome::Draw, Termination::Agreement);
+        Ok(())
+    }
+
+    pub fn decline_draw(&mut self, color: Color) -> Result<(), &'static str> {
+        if self.draw_offer != Some(!color) {
+            return Err("No draw offer to decline");
+        }
+        self.draw_offer = None;
+        Ok(())
+    }
+
+    // calling a game off without a result, only before both sides have made a move
+    pub fn abort(&mut self) -> Result<(), &'static str> {
+        if self.is_over() {
+            return Err("Game is over");
+        }
+        if self.moves.len() >= 2 {
+            return Err("Game can only be aborted before both sides have moved");
+        }
+        self.termination = Some(Termination::Aborted);
+        self.stop_clock();
+        Ok(())
+    }
+
+    // ends the game if the side to move ran out of time, true if the game is over because of it
+    pub fn check_flag(&mut self) -> bool {
+        let Some(outcome) = self.clock.as_mut().and_then(|clock| clock.get_flag_outcome(&self.board)) else { return false };
+        if !self.is_over() {
+            self.finish(outcome, Termination::Timeout);
+        }
+        true
+    }
+
+    fn finish(&mut self, outcome: Outcome, termination: Termination) {
+        self.result = Some(outcome);
+        self.termination = Some(termination);
+        self.draw_offer = None;
+        self.stop_clock();
+    }
+
+    fn stop_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.pause();
+        }
+    }
+
+    // the game in PGN, with the seven tag roster first and the moves in SAN
+    pub fn to_pgn(&self) -> String {
+        let get_tag = |name: &str, default: &str| -> String {
+            self.tags.iter().find(|(tag, _)| tag == name).map_or(default.into(), |(_, value)| value.clone())
+        };
+        let name = |player: &Player| if player.name.is_empty() { "?".to_string() } else { player.name.clone() };
+
+        let mut tags = vec![
+            ("Event".to_string(), get_tag("Event", "?")),
+            ("Site".to_string(), get_tag("Site", "?")),
+            ("Date".to_string(), get_tag("Date", "????.??.??")),
+            ("Round".to_string(), get_tag("Round", "?")),
+            ("White".to_string(), name(&self.white)),
+            ("Black".to_string(), name(&self.black)),
+            ("Result".to_string(), self.get_result_token().to_string()),
+        ];
+        for (color, player) in [("White", &self.white), ("Black", &self.black)] {
+            if let Some(rating) = player.rating {
+                tags.push((format!("{color}Elo"), rating.to_string()));
+            }
+        }
+
+        if self.start.get_variant() != Variant::Standard {
+            tags.push(("Variant".into(), self.start.get_variant().get_pgn_name().into()));
+        } else if self.start.is_chess960() {
+            tags.push(("Variant".into(), "Chess960".into()));
+        }
+        let mut standard_start = Board::new();
+        standard_start.set_variant(self.start.get_variant());
+        standard_start.init("").unwrap();
+        if self.start.get_fen() != standard_start.get_fen() {
+            tags.push(("SetUp".into(), "1".into()));
+            tags.push(("FEN".into(), self.start.get_fen()));
+        }
+        if let Some(clock) = &self.clock {
+            tags.push(("TimeControl".into(), clock.get_time_control().to_string()));
+        }
+        if let Some(termination) = self.termination {
+            tags.push(("Termination".into(), termination.get_pgn_name().into()));
+        }
+        for (name, value) in &self.tags {
+            if !tags.iter().any(|(tag, _)| tag == name) {
+                tags.push((name.clone(), value.clone()));
+            }
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in tags {
+            pgn.push_str(&format!("[{name} \"{}\"]\n", value.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+        pgn.push('\n');
+
+        // movetext lines are kept under 80 characters
+        let mut tokens = Vec::new();
+        let mut board = self.start;
+        for (i, &r#move) in self.moves.iter().enumerate() {
+            if board.get_turn() == White {
+                tokens.push(format!("{}.", board.get_fullmove()));
+            } else if i == 0 {
+                tokens.push(format!("{}...", board.get_fullmove()));
+            }
+            tokens.push(board.get_san(r#move));
+            board.apply_move(r#move);
+        }
+        tokens.push(self.get_result_token().into());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() >= 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+    use crate::clock::{ManualTime, TimeControl};
+    use super::*;
+
+    fn get_game(fen: &str) -> Game {
+        let mut board = Board::new();
+        board.init(fen).unwrap();
+        Game::from_board(board)
+    }
+
+    fn play(game: &mut Game, moves: &str) {
+        for san in moves.split_whitespace() {
+            game.play_san(san).unwrap();
+        }
+    }
+
+    #[test]
+    fn resign() {
+        let mut game = Game::new();
+        play(&mut game, "e4");
+        game.resign(White).unwrap();
+        assert_eq!((game.get_result(), game.get_termination()), (Some(Outcome::Win(Black)), Some(Termination::Resignation)));
+        assert_eq!(game.get_result_token(), "0-1");
+        assert_eq!(game.play_san("e5"), Err("Game is over"));
+        assert_eq!(game.resign(Black), Err("Game is over"));
+    }
+
+    #[test]
+    fn draw_offers() {
+        let mut game = Game::new();
+        assert_eq!(game.accept_draw(Black), Err("No draw offer to accept"));
+        game.offer_draw(White).unwrap();
+        assert_eq!(game.get_draw_offer(), Some(White));
+        assert_eq!(game.accept_draw(White), Err("No draw offer to accept")); // not your own
+        game.decline_draw(Black).unwrap();
+        assert_eq!(game.get_draw_offer(), None);
+
+        // an offer made before moving stands, answering with a move turns it down
+        game.offer_draw(White).unwrap();
+        play(&mut game, "e4");
+        assert_eq!(game.get_draw_offer(), Some(White));
+        play(&mut game, "e5");
+        assert_eq!(game.get_draw_offer(), None);
+
+        game.offer_draw(White).unwrap();
+        game.accept_draw(Black).unwrap();
+        assert_eq!((game.get_result(), game.get_termination()), (Some(Outcome::Draw), Some(Termination::Agreement)));
+
+        // offering back accepts
+        let mut game = Game::new();
+        game.offer_draw(Black).unwrap();
+        game.offer_draw(White).unwrap();
+        assert_eq!(game.get_termination(), Some(Termination::Agreement));
+    }
+
+    #[test]
+    fn repetitions() {
+        let mut game = Game::new();
+        play(&mut game, "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1");
+        assert_eq!(game.get_claimable_draw(), None);
+        assert_eq!(game.claim_draw(), Err("No draw to claim"));
+        play(&mut game, "Ng8");
+        assert_eq!(game.get_repetitions(), 3);
+        assert_eq!(game.get_claimable_draw(), Some(Termination::ThreefoldRepetition));
+
+        // unclaimed, the game goes on until the fifth time
+        play(&mut game, "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1");
+        assert!(!game.is_over());
+        play(&mut game, "Ng8");
+        assert_eq!((game.get_result(), game.get_termination()), (Some(Outcome::Draw), Some(Termination::FivefoldRepetition)));
+
+        let mut game = Game::new();
+        play(&mut game, "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1 Ng8");
+        game.claim_draw().unwrap();
+        assert_eq!((game.get_result(), game.get_termination()), (Some(Outcome::Draw), Some(Termination::ThreefoldRepetition)));
+    }
+
+    #[test]
+    fn move_rules() {
+        let mut game = get_game("4k3/8/8/8/8/8/8/R3K3 w - - 98 80");
+        play(&mut game, "Ra2");
+        assert_eq!(game.get_claimable_draw(), None);
+        play(&mut game, "Kd8");
+        assert_eq!(game.get_claimable_draw(), Some(Termination::FiftyMoves));
+        game.claim_draw().unwrap();
+        assert_eq!((game.get_result(), game.get_termination()), (Some(Outcome::Draw), Some(Termination::FiftyMoves)));
+
+        let mut game = get_game("4k3/8/8/8/8/8/8/R3K3 w - - 148 100");
+        play(&mut game, "Ra2");
+        assert!(!game.is_over());
+        play(&mut game, "Kd8");
+        assert_eq!((game.get_result(), game.get_termination()), (Some(Outcome::Draw), Some(Termination::SeventyFiveMoves)));
+    }
+
+    #[test]
+    fn abort() {
+        let mut game = Game::new();
+        play(&mut game, "e4");
+        game.abort().unwrap();
+        assert_eq!((game.get_result(), game.get_termination()), (None, Some(Termination::Aborted)));
+        assert_eq!(game.get_result_token(), "*");
+        assert_eq!(game.abort(), Err("Game is over"));
+
+        let mut game = Game::new();
+        play(&mut game, "e4 e5");
+        assert_eq!(game.abort(), Err("Game can only be aborted before both sides have moved"));
+        assert!(!game.is_over());
+    }
+
+    fn get_timed_game(control: TimeControl) -> (Game, ManualTime) {
+        let time = ManualTime::new();
+        let mut game = Game::new();
+        game.set_clock(Clock::with_source(control, Arc::new(time.clone())));
+        (game, time)
+    }
+
+    #[test]
+    fn undo_gives_the_clock_back() {
+        let (mut game, time) = get_timed_game(TimeControl::fischer(Duration::from_secs(60), Duration::from_secs(2)));
+        time.advance(Duration::from_secs(5));
+        play(&mut game, "e4");
+        time.advance(Duration::from_secs(1));
+        game.undo().unwrap();
+
+        // white pays for both tries, black's second on a reply doesn't count
+        let clock = game.get_clock().unwrap();
+        assert_eq!((clock.get_turn(), clock.is_running()), (White, true));
+        time.advance(Duration::from_secs(10));
+        play(&mut game, "d4");
+        let clock = game.get_clock().unwrap();
+        assert_eq!((clock.get_remaining(White), clock.get_remaining(Black)), (Duration::from_secs(49), Duration::from_secs(60)));
+        assert_eq!(clock.get_turn(), Black);
+    }
+
+    #[test]
+    fn undo_restarts_the_clock_after_the_game_ended() {
+        let (mut game, time) = get_timed_game(TimeControl::sudden_death(Duration::from_secs(60)));
+        play(&mut game, "e4 e5 Qh5 Nc6 Bc4 Nf6 Qxf7#");
+        assert!(!game.get_clock().unwrap().is_running());
+
+        game.undo().unwrap();
+        assert!(!game.is_over());
+        time.advance(Duration::from_secs(3));
+        let clock = game.get_clock().unwrap();
+        assert_eq!((clock.get_turn(), clock.is_running()), (White, true));
+        assert_eq!((clock.get_remaining(White), clock.get_remaining(Black)), (Duration::from_secs(57), Duration::from_secs(60)));
+
+        play(&mut game, "Qxf7#");
+        assert_eq!(game.get_result(), Some(Outcome::Win(White)));
+        assert_eq!(game.get_clock().unwrap().get_remaining(White), Duration::from_secs(57));
+    }
+
+    #[test]
+    fn to_pgn() {
+        let mut game = Game::new();
+        game.white = Player { name: "Alice".into(), rating: Some(1800) };
+        game.black = Player::new("Bob \"B\"");
+        game.tags.push(("Event".into(), "Club championship".into()));
+        game.tags.push(("Annotator".into(), "Carol".into()));
+        play(&mut game, "e4 e5 Qh5 Nc6 Bc4 Nf6 Qxf7#");
+        assert_eq!(game.get_termination(), Some(Termination::Checkmate));
+        assert_eq!(game.to_pgn(), concat!(
+            "[Event \"Club championship\"]\n",
+            "[Site \"?\"]\n",
+            "[Date \"????.??.??\"]\n",
+            "[Round \"?\"]\n",
+            "[White \"Alice\"]\n",
+            "[Black \"Bob \\\"B\\\"\"]\n",
+            "[Result \"1-0\"]\n",
+            "[WhiteElo \"1800\"]\n",
+            "[Termination \"normal\"]\n",
+            "[Annotator \"Carol\"]\n",
+            "\n",
+            "1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n",
+        ));
+
+        // a game from a position starts with black's move number and keeps the FEN
+        let mut game = get_game("4k3/8/8/8/8/8/4p3/R3K3 b - - 0 30");
+        play(&mut game, "Kd7");
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4p3/R3K3 b - - 0 30\"]\n"));
+        assert!(pgn.ends_with("\n\n30... Kd7 *\n"));
+    }
+}
@@ -0,0 +1,493 @@
+use std::{collections::HashMap, time::Duration};
+use crate::{
+    board::{Board, Color::{self, *}, Move, Square},
+    pieces::Piece,
+    svg::{get_shapes, Shape, PIECE_SIZE, PIECE_STROKE},
+};
+
+// animated GIFs of games, drawn with the same pieces as the SVG diagrams
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub light: [u8; 3],
+    pub dark: [u8; 3],
+    pub light_highlight: [u8; 3], // squares of the last move
+    pub dark_highlight: [u8; 3],
+}
+
+impl Theme {
+    pub const BROWN: Theme = Theme { light: [240, 217, 181], dark: [181, 136, 99], light_highlight: [205, 210, 106], dark_highlight: [170, 162, 58] };
+    pub const GREEN: Theme = Theme { light: [238, 238, 210], dark: [118, 150, 86], light_highlight: [246, 246, 105], dark_highlight: [186, 202, 43] };
+    pub const BLUE: Theme = Theme { light: [222, 227, 230], dark: [140, 162, 173], light_highlight: [195, 216, 135], dark_highlight: [146, 177, 89] };
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::BROWN
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GifRenderer {
+    pub size: u32, // width and height in pixels, rounded down to a multiple of 8
+    pub delay: Duration, // between moves, GIFs count in hundredths of a second
+    pub final_delay: Duration, // on the last position before it starts over
+    pub theme: Theme,
+    pub orientation: Color, // the side at the bottom
+    pub highlight_last_move: bool,
+}
+
+impl Default for GifRenderer {
+    fn default() -> Self {
+        GifRenderer {
+            size: 400,
+            delay: Duration::from_secs(1),
+            final_delay: Duration::from_secs(3),
+            theme: Theme::default(),
+            orientation: White,
+            highlight_last_move: true,
+        }
+    }
+}
+
+// the colors everything is drawn in, pixels along the edges of pieces get a mix of two of them
+const LIGHT: u8 = 0;
+const DARK: u8 = 1;
+const LIGHT_HIGHLIGHT: u8 = 2;
+const DARK_HIGHLIGHT: u8 = 3;
+const WHITE: u8 = 4;
+const BLACK: u8 = 5;
+const BASE_COLORS: usize = 6;
+const MIXES: usize = 3; // a quarter, half and three quarters of the second color
+const SAMPLES: usize = 4; // per pixel in each direction
+
+struct Palette {
+    colors: Vec<[u8; 3]>, // the base colors and then every mix
+}
+
+impl Palette {
+    fn new(theme: &Theme) -> Self {
+        let bases = [theme.light, theme.dark, theme.light_highlight, theme.dark_highlight, [255, 255, 255], [0, 0, 0]];
+        let mut colors = bases.to_vec();
+        for a in 0..BASE_COLORS {
+            for b in a + 1..BASE_COLORS {
+                for mix in 1..=MIXES {
+                    let weight = mix as u32;
+                    colors.push(std::array::from_fn(|i| ((bases[a][i] as u32 * (4 - weight) + bases[b][i] as u32 * weight) / 4) as u8));
+                }
+            }
+        }
+        Palette { colors }
+    }
+
+    // the two most common colors among the samples of a pixel, blended by how often they came up
+    fn get_index(samples: &[u8]) -> u8 {
+        let mut counts = [0; BASE_COLORS];
+        for &sample in samples {
+            counts[sample as usize] += 1;
+        }
+        let mut order: Vec<usize> = (0..BASE_COLORS).collect();
+        order.sort_by_key(|&color| std::cmp::Reverse(counts[color]));
+        let (first, second) = (order[0], order[1]);
+        let mix = (counts[second] * 4 + samples.len() / 2) / (counts[first] + counts[second]).max(1);
+        if mix == 0 || counts[second] == 0 {
+            return first as u8;
+        }
+
+        let (a, b, mix) = if first < second { (first, second, mix) } else { (second, first, 4 - mix) };
+        let pair = (0..a).map(|i| BASE_COLORS - 1 - i).sum::<usize>() + (b - a - 1);
+        (BASE_COLORS + pair * MIXES + mix - 1) as u8
+    }
+}
+
+impl GifRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // every position from the start through each move, the moves have to be legal
+    pub fn render(&self, start: &Board, moves: &[Move]) -> Result<Vec<u8>, &'static str> {
+        let square = (self.size / 8) as usize;
+        if square == 0 {
+            return Err("GIF must be at least 8 pixels wide");
+        }
+        let size = square * 8;
+        let palette = Palette::new(&self.theme);
+        let mut sprites = HashMap::new();
+
+        let mut frames = vec![self.draw(start, None, square, &mut sprites)];
+        let mut board = *start;
+        for &r#move in moves {
+            board.play(r#move)?;
+            frames.push(self.draw(&board, Some(r#move), square, &mut sprites));
+        }
+
+        let mut gif = Vec::new();
+        gif.extend(b"GIF89a");
+        gif.extend((size as u16).to_le_bytes());
+        gif.extend((size as u16).to_le_bytes());
+        let bits = (palette.colors.len() as f32).log2().ceil() as u8; // the table has 2^bits entries
+        gif.extend([0x80 | 0x70 | (bits - 1), 0, 0]);
+        for i in 0..1 << bits {
+            gif.extend(palette.colors.get(i).unwrap_or(&[0, 0, 0]));
+        }
+        // loop forever
+        gif.extend([0x21, 0xff, 0x0b]);
+        gif.extend(b"NETSCAPE2.0");
+        gif.extend([0x03, 0x01, 0x00, 0x00, 0x00]);
+
+        for (i, frame) in frames.iter().enumerate() {
+            let delay = if i + 1 == frames.len() { self.final_delay } else { self.delay };
+            let centiseconds = (delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+            gif.extend([0x21, 0xf9, 0x04, 0x04]); // frames are drawn over the ones before
+            gif.extend(centiseconds.to_le_bytes());
+            gif.extend([0x00, 0x00]);
+
+            // only the part that changed since the last frame
+            let (left, top, width, height) = match i {
+                0 => (0, 0, size, size),
+                _ => get_changed(&frames[i - 1], frame, size).unwrap_or((0, 0, 1, 1)),
+            };
+            gif.push(0x2c);
+            for value in [left, top, width, height] {
+                gif.extend((value as u16).to_le_bytes());
+            }
+            gif.push(0x00);
+
+            let pixels: Vec<u8> = (top..top + height).flat_map(|y| frame[y * size + left..y * size + left + width].iter().copied()).collect();
+            let min_code_size = bits.max(2);
+            gif.push(min_code_size);
+            for block in compress(&pixels, min_code_size).chunks(255) {
+                gif.push(block.len() as u8);
+                gif.extend(block);
+            }
+            gif.push(0x00);
+        }
+        gif.push(0x3b);
+        Ok(gif)
+    }
+
+    // palette indices, row by row from the top
+    fn draw(&self, board: &Board, last: Option<Move>, square: usize, sprites: &mut HashMap<(Piece, u8), Vec<u8>>) -> Vec<u8> {
+        let size = square * 8;
+        let mut frame = vec![0; size * size];
+        for (i, state) in board.get_state().iter().enumerate() {
+            let coord = (((i % 8) as u8) << 4) + (i / 8) as u8;
+            let highlighted = self.highlight_last_move && last.is_some_and(|last| (last.from == coord && last.drop.is_none()) || last.to == coord);
+            let background = match (state.get_color() == White, highlighted) {
+                (true, false) => LIGHT,
+                (false, false) => DARK,
+                (true, true) => LIGHT_HIGHLIGHT,
+                (false, true) => DARK_HIGHLIGHT,
+            };
+
+            let (x, y) = Square::u8_to_tuple(coord);
+            let (column, row) = match self.orientation {
+                White => (x as usize, 7 - y as usize),
+                Black => (7 - x as usize, y as usize),
+            };
+            let sprite = state.get_piece().map(|piece| sprites.entry((piece, background)).or_insert_with(|| draw_piece(piece, background, square)));
+            for dy in 0..square {
+                let start = (row * square + dy) * size + column * square;
+                match &sprite {
+                    Some(sprite) => frame[start..start + square].copy_from_slice(&sprite[dy * square..(dy + 1) * square]),
+                    None => frame[start..start + square].fill(background),
+                }
+            }
+        }
+        frame
+    }
+}
+
+// a piece on a square of the background color, antialiased by sampling every pixel several times
+fn draw_piece(piece: Piece, background: u8, square: usize) -> Vec<u8> {
+    let (fill, detail) = match piece.color {
+        White => (WHITE, BLACK),
+        Black => (BLACK, WHITE),
+    };
+    let shapes = get_shapes(piece.r#type);
+    let scale = square as f32 / PIECE_SIZE;
+    let half_stroke = PIECE_STROKE / 2.0;
+
+    let mut sprite = Vec::with_capacity(square * square);
+    let mut samples = Vec::with_capacity(SAMPLES * SAMPLES);
+    for py in 0..square {
+        for px in 0..square {
+            samples.clear();
+            for sy in 0..SAMPLES {
+                for sx in 0..SAMPLES {
+                    let x = (px as f32 + (sx as f32 + 0.5) / SAMPLES as f32) / scale;
+                    let y = (py as f32 + (sy as f32 + 0.5) / SAMPLES as f32) / scale;
+                    let mut color = background;
+                    for shape in &shapes {
+                        match shape {
+                            Shape::Polygon(points) => {
+                                let edge = (0..points.len()).map(|i| get_distance((x, y), points[i], points[(i + 1) % points.len()])).fold(f32::MAX, f32::min);
+                                if edge < half_stroke {
+                                    color = BLACK;
+                                } else if is_inside((x, y), points) {
+                                    color = fill;
+                                }
+                            },
+                            Shape::Circle(cx, cy, r) => {
+                                let distance = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt();
+                                if (distance - r).abs() < half_stroke {
+                                    color = BLACK;
+                                } else if distance < *r {
+                                    color = fill;
+                                }
+                            },
+                            Shape::Line(a, b) => {
+                                if get_distance((x, y), *a, *b) < half_stroke {
+                                    color = detail;
+                                }
+                            },
+                        }
+                    }
+                    samples.push(color);
+                }
+            }
+            sprite.push(Palette::get_index(&samples));
+        }
+    }
+    sprite
+}
+
+// from the point to the closest point of the segment
+fn get_distance((x, y): (f32, f32), (ax, ay): (f32, f32), (bx, by): (f32, f32)) -> f32 {
+    let (dx, dy) = (bx - ax, by - ay);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 { 0.0 } else { (((x - ax) * dx + (y - ay) * dy) / length).clamp(0.0, 1.0) };
+    ((x - ax - t * dx).powi(2) + (y - ay - t * dy).powi(2)).sqrt()
+}
+
+// even-odd rule
+fn is_inside((x, y): (f32, f32), points: &[(f32, f32)]) -> bool {
+    let mut inside = false;
+    for i in 0..points.len() {
+        let ((x1, y1), (x2, y2)) = (points[i], points[(i + points.len() - 1) % points.len()]);
+        if (y1 > y) != (y2 > y) && x < (x2 - x1) * (y - y1) / (y2 - y1) + x1 {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// left, top, width and height of the smallest rectangle holding every pixel that differs
+fn get_changed(previous: &[u8], frame: &[u8], size: usize) -> Option<(usize, usize, usize, usize)> {
+    let (mut left, mut top, mut right, mut bottom) = (size, size, 0, 0);
+    for y in 0..size {
+        for x in 0..size {
+            if previous[y * size + x] != frame[y * size + x] {
+                (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+            }
+        }
+    }
+    (left <= right).then(|| (left, top, right - left + 1, bottom - top + 1))
+}
+
+// packs codes least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+// GIF flavored LZW, with codes growing up to 12 bits before the table starts over
+fn compress(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut code_size = min_code_size + 1;
+    let mut writer = BitWriter::default();
+
+    writer.write(clear, code_size);
+    let mut pixels = pixels.iter();
+    if let Some(&first) = pixels.next() {
+        let mut prefix = first as u16;
+        for &pixel in pixels {
+            if let Some(&code) = codes.get(&(prefix, pixel)) {
+                prefix = code;
+                continue;
+            }
+            writer.write(prefix, code_size);
+            codes.insert((prefix, pixel), next);
+            prefix = pixel as u16;
+
+            // the decoder adds each code a step later, so the size only grows once the code after it is taken
+            next += 1;
+            if next > 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            if next == 4096 {
+                writer.write(clear, code_size);
+                codes.clear();
+                next = end + 1;
+                code_size = min_code_size + 1;
+            }
+        }
+        writer.write(prefix, code_size);
+    }
+    writer.write(end, code_size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a decoder written from the GIF spec rather than from compress, codes grow once the table fills them
+    fn decompress(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let (clear, end) = (1usize << min_code_size, (1usize << min_code_size) + 1);
+        let reset = || (0..=end).map(|code| vec![code as u8]).collect::<Vec<_>>();
+        let (mut table, mut code_size, mut previous) = (reset(), min_code_size + 1, None::<Vec<u8>>);
+        let (mut position, mut pixels) = (0, Vec::new());
+        loop {
+            let code = (0..code_size as usize).fold(0, |code, bit| {
+                let bit_position = position + bit;
+                code | ((data[bit_position / 8] as usize >> (bit_position % 8)) & 1) << bit
+            });
+            position += code_size as usize;
+
+            if code == clear {
+                (table, code_size, previous) = (reset(), min_code_size + 1, None);
+                continue;
+            }
+            if code == end {
+                return pixels;
+            }
+
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) if code == table.len() => [previous.as_slice(), &previous[..1]].concat(),
+                _ => panic!("code {code} isn't in the table yet"),
+            };
+            pixels.extend(&entry);
+            if let Some(previous) = previous.filter(|_| table.len() < 4096) {
+                table.push([previous.as_slice(), &entry[..1]].concat());
+            }
+            if table.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            previous = Some(entry);
+        }
+    }
+
+    struct Frame {
+        delay: u16,
+        rectangle: (usize, usize, usize, usize),
+        pixels: Vec<u8>,
+    }
+
+    // walks the blocks of the file, decoding every image in it
+    fn get_frames(gif: &[u8]) -> (usize, Vec<Frame>) {
+        assert_eq!(&gif[..6], b"GIF89a");
+        let read = |i: usize| u16::from_le_bytes([gif[i], gif[i + 1]]) as usize;
+        let size = read(6);
+        assert_eq!(read(8), size);
+        let mut i = 13 + 3 * (2 << (gif[10] & 7));
+        let (mut frames, mut delay) = (Vec::new(), 0);
+        let sub_blocks = |i: &mut usize| {
+            let mut data = Vec::new();
+            while gif[*i] != 0 {
+                data.extend(&gif[*i + 1..*i + 1 + gif[*i] as usize]);
+                *i += gif[*i] as usize + 1;
+            }
+            *i += 1;
+            data
+        };
+        loop {
+            match gif[i] {
+                0x21 => {
+                    if gif[i + 1] == 0xf9 {
+                        delay = read(i + 4) as u16;
+                    }
+                    i += 2;
+                    sub_blocks(&mut i);
+                },
+                0x2c => {
+                    let rectangle = (read(i + 1), read(i + 3), read(i + 5), read(i + 7));
+                    assert_eq!(gif[i + 9], 0, "no local color table");
+                    let min_code_size = gif[i + 10];
+                    i += 11;
+                    let pixels = decompress(&sub_blocks(&mut i), min_code_size);
+                    assert_eq!(pixels.len(), rectangle.2 * rectangle.3);
+                    frames.push(Frame { delay, rectangle, pixels });
+                },
+                0x3b => return (size, frames),
+                block => panic!("unexpected block {block:#x}"),
+            }
+        }
+    }
+
+    #[test]
+    fn compresses() {
+        let mut state = 12345u32;
+        let mut random = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        };
+        let noise: Vec<u8> = (0..20000).map(|_| random() % 64).collect();
+        let runs: Vec<u8> = (0..50000).map(|i| (i / 300 % 3) as u8).collect();
+        let few: Vec<u8> = (0..30000).map(|_| random() % 4).collect();
+        for (pixels, min_code_size) in [(vec![], 2), (vec![1], 2), (vec![3, 3, 3, 3, 3], 2), (noise, 6), (runs, 6), (few, 2)] {
+            assert_eq!(decompress(&compress(&pixels, min_code_size), min_code_size), pixels);
+        }
+    }
+
+    #[test]
+    fn animates_games() {
+        let mut board = Board::new();
+        board.init("").unwrap();
+        let moves: Vec<Move> = ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"].iter().map(|uci| Move::from_uci(uci).unwrap()).collect();
+        let renderer = GifRenderer { size: 163, delay: Duration::from_millis(500), ..GifRenderer::new() };
+        let gif = renderer.render(&board, &moves).unwrap();
+
+        let (size, frames) = get_frames(&gif);
+        assert_eq!((size, frames.len()), (160, moves.len() + 1));
+        assert_eq!(frames.iter().map(|frame| frame.delay).collect::<Vec<_>>(), [50, 50, 50, 50, 50, 50, 50, 300]);
+        assert_eq!(frames[0].rectangle, (0, 0, 160, 160));
+
+        // drawing each frame over the last gives every position of the game
+        let mut canvas = vec![0; size * size];
+        let mut sprites = HashMap::new();
+        let mut last = None;
+        for (i, frame) in frames.iter().enumerate() {
+            let (left, top, width, height) = frame.rectangle;
+            for (row, line) in frame.pixels.chunks(width).enumerate() {
+                canvas[(top + row) * size + left..(top + row) * size + left + width].copy_from_slice(line);
+            }
+            if i > 0 {
+                board.play(moves[i - 1]).unwrap();
+                last = Some(moves[i - 1]);
+                // e2e4 changes two squares on the same file, castling four on the same rank
+                assert!(width < size || height < size, "frame {i}");
+            }
+            assert!(canvas == renderer.draw(&board, last, 20, &mut sprites), "frame {i}");
+        }
+
+        assert_eq!(GifRenderer { size: 7, ..GifRenderer::new() }.render(&board, &[]), Err("GIF must be at least 8 pixels wide"));
+        assert_eq!(renderer.render(&board, &[Move::from_uci("e5e4").unwrap()]), Err("Illegal move"));
+    }
+}
@@ -0,0 +1,707 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{mpsc, Arc, Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+use crate::{
+    board::{Board, Move},
+    game::Game,
+    json::{json_object, Json},
+    search::{search, search_with_table, Limits, DEFAULT_HASH_MB},
+    server::Lobby,
+    tt::TranspositionTable,
+    variant::Variant,
+};
+
+// the rules as a web service, so frontends don't have to implement chess themselves. Every endpoint
+// takes a JSON object describing a position and answers with JSON:
+//
+//     {"fen": "...", "variant": "atomic", "chess960": false, "moves": ["e2e4", "e5"]}
+//
+// all of them optional, the starting position of the variant is used without a FEN and the moves,
+// in UCI or SAN, are played from there so that repetitions count. On top of that:
+//
+//     POST /legal-moves                                   the moves in UCI and SAN
+//     POST /move      {"move": "e7e5"}                    the position after the move
+//     POST /status                                        whose turn, check, and whether it's over
+//     POST /analyse   {"depth": 12, "movetime": 1000}     best move, score and line, movetime in ms
+//
+// errors come back with a 4xx status and {"error": "..."}. GET /live upgrades to a WebSocket for
+// playing live games, with one command per text message as described in src/server.rs
+
+const MAX_BODY: usize = 1 << 20;
+const MAX_HEADER: u64 = 16 << 10; // the request line and headers together
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10); // for the request, live games can wait as long as they like
+const MAX_MOVETIME: Duration = Duration::from_secs(30);
+const DEFAULT_MOVETIME: Duration = Duration::from_secs(1);
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// allocated once and kept for every /analyse request, instead of a fresh table per search. Variants
+// share it, Board::get_hash keeps their positions apart
+static TABLE: OnceLock<Mutex<TranspositionTable>> = OnceLock::new();
+
+// answers a request to the JSON API with the status code and body
+pub fn handle_api(method: &str, path: &str, body: &str) -> (u16, Json) {
+    let endpoint = match (method, path) {
+        ("POST", "/legal-moves") => get_legal_moves,
+        ("POST", "/move") => make_move,
+        ("POST", "/status") => get_status,
+        ("POST", "/analyse") => analyse,
+        (_, "/legal-moves" | "/move" | "/status" | "/analyse") => return (405, json_object! { "error" => "Use POST" }),
+        _ => return (404, json_object! { "error" => "Not found" }),
+    };
+
+    let request = match Json::parse(if body.trim().is_empty() { "{}" } else { body }) {
+        Ok(request @ Json::Object(_)) => request,
+        _ => return (400, json_object! { "error" => "Expected a JSON object" }),
+    };
+    match get_game(&request).and_then(|game| endpoint(&request, game)) {
+        Ok(response) => (200, response),
+        Err(message) => (400, json_object! { "error" => message }),
+    }
+}
+
+// the position the request describes, with its moves played
+fn get_game(request: &Json) -> Result<Game, &'static str> {
+    let mut board = Board::new();
+    if let Some(variant) = request.get("variant").filter(|variant| **variant != Json::Null) {
+        board.set_variant(variant.as_str().and_then(Variant::from_name).ok_or("Unknown variant")?);
+    }
+    board.set_chess960(request.get("chess960").and_then(Json::as_bool).unwrap_or(false));
+    board.init(request.get("fen").and_then(Json::as_str).unwrap_or(""))?;
+
+    let mut game = Game::from_board(board);
+    for r#move in request.get("moves").and_then(Json::as_array).unwrap_or_default() {
+        let notation = r#move.as_str().ok_or("Moves must be strings")?;
+        game.play(parse_move(game.get_board(), notation)?)?;
+    }
+    Ok(game)
+}
+
+// UCI or SAN, whichever it is
+fn parse_move(board: &Board, notation: &str) -> Result<Move, &'static str> {
+    Move::from_uci(notation).or_else(|_| board.parse_san(notation)).map_err(|_| "Invalid move")
+}
+
+fn get_move_json(board: &Board, r#move: Move) -> Json {
+    json_object! { "uci" => r#move.to_string(), "san" => board.get_san(r#move) }
+}
+
+fn get_legal_moves(_: &Json, game: Game) -> Result<Json, &'static str> {
+    let board = game.get_board();
+    let moves = if game.is_over() { Vec::new() } else { board.get_legal_moves() };
+    Ok(json_object! { "moves" => moves.into_iter().map(|r#move| get_move_json(board, r#move)).collect::<Vec<_>>() })
+}
+
+fn make_move(request: &Json, mut game: Game) -> Result<Json, &'static str> {
+    let notation = request.get("move").and_then(Json::as_str).ok_or("Missing move")?;
+    let board = *game.get_board();
+    let r#move = parse_move(&board, notation)?;
+    game.play(r#move)?;
+    Ok(json_object! {
+        "move" => get_move_json(&board, r#move),
+        "fen" => game.get_board().get_fen(),
+        "status" => get_status_json(&game),
+    })
+}
+
+fn get_status(_: &Json, game: Game) -> Result<Json, &'static str> {
+    Ok(get_status_json(&game))
+}
+
+fn get_status_json(game: &Game) -> Json {
+    let board = game.get_board();
+    json_object! {
+        "fen" => board.get_fen(),
+        "variant" => board.get_variant().get_name(),
+        "turn" => board.get_turn().to_string().to_lowercase(),
+        "check" => board.in_check(board.get_turn()),
+        "over" => game.is_over(),
+        "result" => game.get_result_token(),
+        "termination" => game.get_termination().map(|termination| termination.to_string()),
+        "claimable_draw" => game.get_claimable_draw().map(|termination| termination.to_string()),
+        "halfmove" => board.get_halfmove(),
+        "fullmove" => board.get_fullmove(),
+    }
+}
+
+// searches until the depth or the time runs out, whichever comes first
+fn analyse(request: &Json, game: Game) -> Result<Json, &'static str> {
+    if game.is_over() {
+        return Err("Game is over");
+    }
+    let board = game.get_board();
+    let depth = request.get("depth").map(|depth| depth.as_u64().filter(|depth| (1..=u8::MAX as u64).contains(depth)).ok_or("Invalid depth")).transpose()?;
+    let movetime = request.get("movetime").map(|movetime| movetime.as_u64().map(Duration::from_millis).ok_or("Invalid movetime")).transpose()?;
+    let limits = Limits {
+        depth: depth.map(|depth| depth as u8),
+        movetime: Some(movetime.unwrap_or(DEFAULT_MOVETIME).min(MAX_MOVETIME)),
+        ..Default::default()
+    };
+
+    // a request that finds the table busy searches with a small one of its own
+    let table = TABLE.get_or_init(|| Mutex::new(TranspositionTable::new(DEFAULT_HASH_MB)));
+    let result = match table.try_lock() {
+        Ok(mut tt) => search_with_table(board, &limits, &mut tt),
+        Err(_) => search(board, &limits),
+    };
+    let mut position = *board;
+    let pv: Vec<Json> = result.pv.iter().map(|&r#move| {
+        let json = get_move_json(&position, r#move);
+        position.apply_move(r#move);
+        json
+    }).collect();
+    Ok(json_object! {
+        "best_move" => result.best_move.map(|r#move| get_move_json(board, r#move)),
+        "score" => result.score,
+        // in moves like UCI's score mate, negative when the side to move is getting mated
+        "mate" => result.mate_in_plies().map(|plies| (plies + plies.signum()) / 2),
+        "depth" => result.depth,
+        "nodes" => result.nodes,
+        "pv" => pv,
+    })
+}
+
+// serve the API and the live games until the listener fails, one thread per connection
+pub fn serve(address: impl ToSocketAddrs) -> Result<(), &'static str> {
+    let listener = TcpListener::bind(address).map_err(|_| "Could not listen on that address")?;
+    let lobby = Lobby::shared();
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let lobby = Arc::clone(&lobby);
+        thread::spawn(move || {
+            let _ = handle_connection(stream, lobby);
+        });
+    }
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>, // names in lowercase
+    body: String,
+}
+
+impl Request {
+    fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+    }
+}
+
+// a line of the request line or headers, which can't go on forever
+fn read_header_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), &'static str> {
+    line.clear();
+    let mut head = Read::take(&mut *reader, MAX_HEADER);
+    head.read_line(line).map_err(|_| "Could not read request")?;
+    if !line.ends_with('\n') {
+        return Err(if head.limit() == 0 { "Request headers too large" } else { "Incomplete request" });
+    }
+    Ok(())
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, &'static str> {
+    let mut line = String::new();
+    read_header_line(reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else { return Err("Invalid request line") };
+    let method = method.to_string();
+    let path = target.split('?').next().unwrap_or(target).to_string();
+
+    let mut headers = Vec::new();
+    let mut size = line.len();
+    loop {
+        read_header_line(reader, &mut line)?;
+        size += line.len();
+        if size as u64 > MAX_HEADER {
+            return Err("Request headers too large");
+        }
+        let Some((name, value)) = line.trim_end().split_once(':') else { break };
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+
+    let mut request = Request { method, path, headers, body: String::new() };
+    let length = request.get_header("content-length").map_or(Ok(0), str::parse).map_err(|_| "Invalid Content-Length")?;
+    if length > MAX_BODY {
+        return Err("Request body too large");
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|_| "Could not read request body")?;
+    request.body = String::from_utf8(body).map_err(|_| "Request body isn't UTF-8")?;
+    Ok(request)
+}
+
+fn get_reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+// connections are closed after every response, browsers on other origins are allowed in
+fn respond(stream: &mut TcpStream, status: u16, body: Option<&Json>) -> std::io::Result<()> {
+    let body = body.map_or(String::new(), ToString::to_string);
+    write!(
+        stream,
+        "HTTP/1.1 {status} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\nConnection: close\r\n\r\n{body}",
+        get_reason(status),
+        body.len(),
+    )?;
+    stream.flush()
+}
+
+fn handle_connection(mut stream: TcpStream, lobby: Arc<Mutex<Lobby>>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = match read_request(&mut reader) {
+        Ok(request) => request,
+        Err(message) => return respond(&mut stream, 400, Some(&json_object! { "error" => message })),
+    };
+
+    if request.method == "OPTIONS" {
+        return respond(&mut stream, 204, None);
+    }
+    if request.path == "/live" {
+        let Some(key) = request.get_header("sec-websocket-key").filter(|_| request.method == "GET") else {
+            return respond(&mut stream, 400, Some(&json_object! { "error" => "Expected a WebSocket upgrade" }));
+        };
+        let accept = base64(&sha1(format!("{key}{WEBSOCKET_GUID}").as_bytes()));
+        write!(stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n")?;
+        stream.set_read_timeout(None)?;
+        return handle_websocket(stream, reader, lobby);
+    }
+
+    let (status, response) = handle_api(&request.method, &request.path, &request.body);
+    respond(&mut stream, status, Some(&response))
+}
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+// server frames are never masked or fragmented
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend((length as u16).to_be_bytes());
+        },
+        length => {
+            frame.push(127);
+            frame.extend((length as u64).to_be_bytes());
+        },
+    }
+    frame.extend(payload);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+// the next frame as its fin bit, opcode and unmasked payload
+fn read_frame(reader: &mut impl Read) -> std::io::Result<(bool, u8, Vec<u8>)> {
+    let invalid = |message| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let length = match header[1] & 0x7f {
+        126 => {
+            let mut length = [0; 2];
+            reader.read_exact(&mut length)?;
+            u16::from_be_bytes(length) as usize
+        },
+        127 => {
+            let mut length = [0; 8];
+            reader.read_exact(&mut length)?;
+            u64::from_be_bytes(length) as usize
+        },
+        length => length as usize,
+    };
+    if length > MAX_BODY {
+        return Err(invalid("WebSocket frame too large"));
+    }
+    // clients have to mask everything they send
+    if header[1] & 0x80 == 0 {
+        return Err(invalid("Unmasked WebSocket frame"));
+    }
+    let mut mask = [0; 4];
+    reader.read_exact(&mut mask)?;
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((header[0] & 0x80 != 0, header[0] & 0x0f, payload))
+}
+
+// every text message holds lobby commands, and everything the lobby sends goes out as a text message
+fn handle_websocket(stream: TcpStream, mut reader: impl Read, lobby: Arc<Mutex<Lobby>>) -> std::io::Result<()> {
+    let (sender, receiver) = mpsc::channel::<String>();
+    let writer = Arc::new(Mutex::new(stream));
+    let messages = Arc::clone(&writer);
+    thread::spawn(move || {
+        for message in receiver {
+            if write_frame(&mut *messages.lock().unwrap(), OPCODE_TEXT, message.as_bytes()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let client = lobby.lock().unwrap().connect(sender);
+    let mut message = Vec::new();
+    let result = loop {
+        let (fin, opcode, payload) = match read_frame(&mut reader) {
+            Ok(frame) => frame,
+            Err(error) => break Err(error),
+        };
+        match opcode {
+            OPCODE_TEXT | OPCODE_CONTINUATION => message.extend(payload),
+            OPCODE_PING => {
+                write_frame(&mut *writer.lock().unwrap(), OPCODE_PONG, &payload)?;
+                continue;
+            },
+            OPCODE_CLOSE => break write_frame(&mut *writer.lock().unwrap(), OPCODE_CLOSE, &payload),
+            _ => continue,
+        }
+        if !fin {
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&message).into_owned();
+        message.clear();
+        let mut lobby = lobby.lock().unwrap();
+        for line in text.lines() {
+            lobby.handle(client, line);
+        }
+    };
+    lobby.lock().unwrap().disconnect(client);
+    result
+}
+
+// only needed for the WebSocket handshake
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([chunk[i * 4], chunk[i * 4 + 1], chunk[i * 4 + 2], chunk[i * 4 + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, temp);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (i, state) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - i * 6) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example from RFC 6455
+    const SAMPLE_KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+    const SAMPLE_ACCEPT: &str = "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=";
+
+    fn post(path: &str, body: &str) -> Json {
+        let (status, response) = handle_api("POST", path, body);
+        assert_eq!(status, 200, "{response}");
+        response
+    }
+
+    fn get_error(method: &str, path: &str, body: &str) -> (u16, String) {
+        let (status, response) = handle_api(method, path, body);
+        (status, response.get("error").and_then(Json::as_str).unwrap().to_string())
+    }
+
+    // clients mask their frames, with a fixed mask here
+    fn write_client_frame(stream: &mut impl Write, fin: bool, opcode: u8, payload: &[u8]) {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+        match payload.len() {
+            length @ 0..=125 => frame.push(0x80 | length as u8),
+            length => {
+                frame.push(0x80 | 126);
+                frame.extend((length as u16).to_be_bytes());
+            },
+        }
+        frame.extend(mask);
+        frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+        stream.write_all(&frame).unwrap();
+    }
+
+    fn read_server_frame(reader: &mut impl Read) -> (u8, Vec<u8>) {
+        let mut header = [0; 2];
+        reader.read_exact(&mut header).unwrap();
+        assert_eq!(header[0] & 0xf0, 0x80, "server frames are final");
+        assert_eq!(header[1] & 0x80, 0, "server frames aren't masked");
+        let length = match header[1] {
+            126 => {
+                let mut length = [0; 2];
+                reader.read_exact(&mut length).unwrap();
+                u16::from_be_bytes(length) as usize
+            },
+            length => length as usize,
+        };
+        let mut payload = vec![0; length];
+        reader.read_exact(&mut payload).unwrap();
+        (header[0] & 0x0f, payload)
+    }
+
+    // skips the text messages that come before the one expected
+    fn read_message(reader: &mut impl Read, expected: &str) {
+        loop {
+            let (opcode, payload) = read_server_frame(reader);
+            assert_eq!(opcode, OPCODE_TEXT);
+            if String::from_utf8(payload).unwrap() == expected {
+                return;
+            }
+        }
+    }
+
+    fn connect() -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let _ = handle_connection(stream, Lobby::shared());
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        stream
+    }
+
+    #[test]
+    fn legal_moves() {
+        let response = post("/legal-moves", "");
+        let moves = response.get("moves").and_then(Json::as_array).unwrap();
+        assert_eq!(moves.len(), 20);
+        assert!(moves.iter().any(|r#move| r#move.get("uci").and_then(Json::as_str) == Some("g1f3") && r#move.get("san").and_then(Json::as_str) == Some("Nf3")));
+
+        let response = post("/legal-moves", r#"{"moves": ["f3", "e5", "g4", "Qh4#"]}"#);
+        assert_eq!(response.get("moves").and_then(Json::as_array).map(<[Json]>::len), Some(0));
+
+        let response = post("/legal-moves", r#"{"variant": "antichess", "fen": "8/8/8/8/8/8/p7/1R6 b - - 0 1"}"#);
+        // capturing is forced, axb1 promoting to any piece including a king
+        assert_eq!(response.get("moves").and_then(Json::as_array).map(<[Json]>::len), Some(5));
+    }
+
+    #[test]
+    fn make_move() {
+        let response = post("/move", r#"{"moves": ["e4"], "move": "e7e5"}"#);
+        let r#move = response.get("move").unwrap();
+        assert_eq!(r#move.get("uci").and_then(Json::as_str), Some("e7e5"));
+        assert_eq!(r#move.get("san").and_then(Json::as_str), Some("e5"));
+        assert_eq!(response.get("fen").and_then(Json::as_str), Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"));
+        assert_eq!(response.get("status").and_then(|status| status.get("turn")).and_then(Json::as_str), Some("white"));
+
+        assert_eq!(get_error("POST", "/move", r#"{"move": "e5"}"#), (400, "Invalid move".to_string()));
+        assert_eq!(get_error("POST", "/move", r#"{"move": "e2e5"}"#), (400, "Illegal move".to_string()));
+        assert_eq!(get_error("POST", "/move", "{}"), (400, "Missing move".to_string()));
+    }
+
+    #[test]
+    fn status() {
+        let response = post("/status", "{}");
+        assert_eq!(response.get("variant").and_then(Json::as_str), Some("chess"));
+        assert_eq!(response.get("turn").and_then(Json::as_str), Some("white"));
+        assert_eq!(response.get("over").and_then(Json::as_bool), Some(false));
+        assert_eq!(response.get("result").and_then(Json::as_str), Some("*"));
+        assert_eq!(response.get("fullmove").and_then(Json::as_u64), Some(1));
+
+        let response = post("/status", r#"{"moves": ["f2f3", "e5", "g4", "Qh4#"]}"#);
+        assert_eq!(response.get("check").and_then(Json::as_bool), Some(true));
+        assert_eq!(response.get("over").and_then(Json::as_bool), Some(true));
+        assert_eq!(response.get("result").and_then(Json::as_str), Some("0-1"));
+        assert_eq!(response.get("termination").and_then(Json::as_str), Some("checkmate"));
+        assert_eq!(response.get("halfmove").and_then(Json::as_u64), Some(1));
+
+        assert_eq!(get_error("POST", "/status", r#"{"variant": "checkers"}"#), (400, "Unknown variant".to_string()));
+    }
+
+    #[test]
+    fn analyse() {
+        let response = post("/analyse", r#"{"fen": "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "depth": 3}"#);
+        let best_move = response.get("best_move").unwrap();
+        assert_eq!(best_move.get("uci").and_then(Json::as_str), Some("d1d8"));
+        assert_eq!(best_move.get("san").and_then(Json::as_str), Some("Rd8#"));
+        assert_eq!(response.get("mate").and_then(Json::as_f64), Some(1.0));
+        assert_eq!(response.get("pv").and_then(Json::as_array).and_then(<[Json]>::first), Some(best_move));
+
+        // 1. Nf6+ gxf6 2. Bxf7#
+        let response = post("/analyse", r#"{"fen": "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1", "depth": 5}"#);
+        assert_eq!(response.get("mate").and_then(Json::as_f64), Some(2.0));
+        let response = post("/analyse", r#"{"fen": "7k/R7/1R6/8/8/8/8/6K1 b - - 0 1", "depth": 4}"#);
+        assert_eq!(response.get("mate").and_then(Json::as_f64), Some(-1.0));
+
+        assert_eq!(get_error("POST", "/analyse", r#"{"depth": 0}"#), (400, "Invalid depth".to_string()));
+        assert_eq!(get_error("POST", "/analyse", r#"{"moves": ["f3", "e5", "g4", "Qh4#"]}"#), (400, "Game is over".to_string()));
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert_eq!(get_error("GET", "/nope", ""), (404, "Not found".to_string()));
+        assert_eq!(get_error("POST", "/", ""), (404, "Not found".to_string()));
+        assert_eq!(get_error("GET", "/status", ""), (405, "Use POST".to_string()));
+        assert_eq!(get_error("PUT", "/move", "{}"), (405, "Use POST".to_string()));
+        for body in ["{", "[1]", "\"e4\"", "{\"moves\": }"] {
+            assert_eq!(get_error("POST", "/status", body), (400, "Expected a JSON object".to_string()), "{body}");
+        }
+        // nested deep enough to overflow the stack if the parser didn't stop it
+        assert_eq!(get_error("POST", "/status", &"[".repeat(100_000)), (400, "Expected a JSON object".to_string()));
+    }
+
+    #[test]
+    fn limits_headers() {
+        let read = |request: String| read_request(&mut request.as_bytes()).map(|request| request.path);
+        assert_eq!(read("GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n".to_string()), Ok("/status".to_string()));
+        assert_eq!(read("GET /status HTTP/1.1\r\nHost: local".to_string()).err(), Some("Incomplete request"));
+        assert_eq!(read(format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEADER as usize))).err(), Some("Request headers too large"));
+        let headers = "X-Padding: 0123456789\r\n".repeat(MAX_HEADER as usize / 20);
+        assert_eq!(read(format!("GET / HTTP/1.1\r\n{headers}\r\n")).err(), Some("Request headers too large"));
+    }
+
+    #[test]
+    fn hashes() {
+        let hex = |digest: [u8; 20]| digest.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // more than one block
+        assert_eq!(hex(sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+
+        for (data, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")] {
+            assert_eq!(base64(data.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&sha1(format!("{SAMPLE_KEY}{WEBSOCKET_GUID}").as_bytes())), SAMPLE_ACCEPT);
+    }
+
+    #[test]
+    fn frames() {
+        for length in [0, 125, 126, 0xffff, 0x10000] {
+            let payload = vec![b'x'; length];
+            let mut frame = Vec::new();
+            write_frame(&mut frame, OPCODE_TEXT, &payload).unwrap();
+            let header = match length {
+                0..=125 => vec![0x81, length as u8],
+                126..=0xffff => [vec![0x81, 126], (length as u16).to_be_bytes().to_vec()].concat(),
+                _ => [vec![0x81, 127], (length as u64).to_be_bytes().to_vec()].concat(),
+            };
+            assert_eq!(frame[..header.len()], header[..], "{length}");
+            assert_eq!(frame.len(), header.len() + length);
+        }
+
+        let mut frame = Vec::new();
+        write_client_frame(&mut frame, false, OPCODE_TEXT, b"list");
+        assert_eq!(read_frame(&mut frame.as_slice()).unwrap(), (false, OPCODE_TEXT, b"list".to_vec()));
+
+        // the server's own frames aren't masked, so a client can't send them
+        let mut frame = Vec::new();
+        write_frame(&mut frame, OPCODE_TEXT, b"list").unwrap();
+        assert_eq!(read_frame(&mut frame.as_slice()).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn serves_over_http() {
+        let mut stream = connect();
+        let body = r#"{"moves": ["e4"]}"#;
+        write!(stream, "POST /status HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(Json::parse(body).unwrap().get("turn").and_then(Json::as_str), Some("black"));
+    }
+
+    #[test]
+    fn plays_over_a_websocket() {
+        let mut stream = connect();
+        write!(stream, "GET /live HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {SAMPLE_KEY}\r\nSec-WebSocket-Version: 13\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            headers.push(line.trim_end().to_string());
+        }
+        assert_eq!(headers[0], "HTTP/1.1 101 Switching Protocols");
+        assert!(headers.contains(&format!("Sec-WebSocket-Accept: {SAMPLE_ACCEPT}")));
+
+        // nothing else is queued yet, so the pong comes straight back
+        write_client_frame(&mut stream, true, OPCODE_PING, b"hello");
+        assert_eq!(read_server_frame(&mut reader), (OPCODE_PONG, b"hello".to_vec()));
+
+        write_client_frame(&mut stream, true, OPCODE_TEXT, b"create white");
+        read_message(&mut reader, "created 1 White");
+
+        // a message split over several frames
+        write_client_frame(&mut stream, false, OPCODE_TEXT, b"bog");
+        write_client_frame(&mut stream, true, OPCODE_CONTINUATION, b"us");
+        read_message(&mut reader, "error Unknown command");
+
+        // longer than 125 bytes, with the 16-bit length
+        let long = format!("name {}", "x".repeat(200));
+        write_client_frame(&mut stream, true, OPCODE_TEXT, long.as_bytes());
+
+        write_client_frame(&mut stream, true, OPCODE_CLOSE, &1000u16.to_be_bytes());
+        loop {
+            let (opcode, payload) = read_server_frame(&mut reader);
+            if opcode == OPCODE_CLOSE {
+                assert_eq!(payload, 1000u16.to_be_bytes());
+                break;
+            }
+            assert_eq!(opcode, OPCODE_TEXT);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+pub mod pieces;
+pub mod board;
+pub mod uci;
+pub mod search;
+pub mod eval;
+pub mod zobrist;
+pub mod tt;
+pub mod see;
+pub mod timeman;
+pub mod movepick;
+pub mod san;
+pub mod pgn;
+pub mod polyglot;
+pub mod syzygy;
+pub mod nnue;
+pub mod variant;
+pub mod clock;
+pub mod game;
+pub mod cecp;
+pub mod server;
+pub mod json;
+pub mod http;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod render;
+pub mod svg;
+pub mod gif;
+pub mod epd;
+#[cfg(feature = "serde")]
+pub mod serialization;
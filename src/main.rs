@@ -1,16 +1,14 @@
-mod pieces;
-mod board;
-
-use board::*;
+use rschess::board::*;
 
 fn main() -> Result<(), &'static str> {
     let mut board = Board::new();
-    board.init("");
-    board.r#move("A2", "A3")?;
-    board.r#move("A3", "A4")?;
-    board.r#move("A4", "A5")?;
-    board.r#move("A5", "A6")?;
-    board.r#move("A6", "B7")?;
+    board.init("")?;
+    board.r#move("E2", "E4")?;
+    board.r#move("E7", "E5")?;
+    board.r#move("G1", "F3")?;
+    board.r#move("B8", "C6")?;
+    board.r#move("F1", "B5")?;
     println!("{board}");
+    println!("{}", board.get_fen());
     Ok(())
 }
@@ -0,0 +1,365 @@
+use crate::{board::{Board, Color, Move, Square}, pieces::Pieces::*, search::MAX_PLY};
+
+// move ordering for the search, the sooner the best move is tried the sooner the rest can be cut off
+
+// history scores are kept within +-HISTORY_MAX so old results fade as new ones come in
+const HISTORY_MAX: i32 = 16384;
+
+fn get_index(coord: u8) -> usize {
+    let (x, y) = Square::u8_to_tuple(coord);
+    x as usize * 8 + y as usize
+}
+
+// what the search has learned about quiet moves so far
+pub struct Heuristics {
+    killers: Vec<[Option<Move>; 2]>, // quiet moves that caused a cutoff at the same ply, by ply
+    counter_moves: Vec<Option<Move>>, // quiet reply that refuted the previous move, by its from and to squares
+    history: Vec<i32>, // cutoff statistics, by color, from and to squares
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heuristics {
+    pub fn new() -> Self {
+        Heuristics {
+            killers: vec![[None; 2]; MAX_PLY as usize + 1],
+            counter_moves: vec![None; 64 * 64],
+            history: vec![0; 2 * 64 * 64],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn get_killers(&self, ply: u8) -> [Option<Move>; 2] {
+        self.killers[(ply as usize).min(MAX_PLY as usize)]
+    }
+
+    pub fn get_counter_move(&self, previous: Option<Move>) -> Option<Move> {
+        previous.and_then(|previous| self.counter_moves[get_index(previous.from) * 64 + get_index(previous.to)])
+    }
+
+    pub fn get_history(&self, color: Color, r#move: Move) -> i32 {
+        self.history[Self::history_index(color, r#move)]
+    }
+
+    fn history_index(color: Color, r#move: Move) -> usize {
+        color as usize * 64 * 64 + get_index(r#move.from) * 64 + get_index(r#move.to)
+    }
+
+    // gravity keeps the score bounded, big bonuses move it less the closer it already is to the limit
+    fn update_history(&mut self, color: Color, r#move: Move, bonus: i32) {
+        let entry = &mut self.history[Self::history_index(color, r#move)];
+        *entry += bonus - *entry * bonus.abs() / HISTORY_MAX;
+    }
+
+    // a quiet move caused a beta cutoff, every quiet tried before it gets a penalty
+    pub fn update_cutoff(&mut self, color: Color, ply: u8, depth: u8, previous: Option<Move>, r#move: Move, tried: &[Move]) {
+        let killers = &mut self.killers[(ply as usize).min(MAX_PLY as usize)];
+        if killers[0] != Some(r#move) {
+            killers[1] = killers[0];
+            killers[0] = Some(r#move);
+        }
+
+        if let Some(previous) = previous {
+            self.counter_moves[get_index(previous.from) * 64 + get_index(previous.to)] = Some(r#move);
+        }
+
+        let bonus = (depth as i32 * depth as i32).min(400) * 4;
+        self.update_history(color, r#move, bonus);
+        for &quiet in tried {
+            self.update_history(color, quiet, -bonus);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// hands out moves one at a time in order of how promising they are, quiet moves are only
+// generated once the hash move, captures, killers and counter move failed to cause a cutoff
+pub struct MovePicker {
+    stage: Stage,
+    captures_only: bool,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    counter_move: Option<Move>,
+    killer_index: usize,
+    captures: Vec<(Move, i32)>,
+    bad_captures: Vec<(Move, i32)>,
+    quiets: Vec<(Move, i32)>,
+}
+
+impl MovePicker {
+    pub fn new(board: &Board, hash_move: Option<Move>, heuristics: &Heuristics, ply: u8, previous: Option<Move>) -> Self {
+        MovePicker {
+            stage: Stage::HashMove,
+            captures_only: false,
+            // the table can hold moves from a different position with the same hash
+            hash_move: hash_move.filter(|&r#move| board.is_legal(r#move)),
+            killers: heuristics.get_killers(ply),
+            counter_move: heuristics.get_counter_move(previous),
+            killer_index: 0,
+            captures: Vec::new(),
+            bad_captures: Vec::new(),
+            quiets: Vec::new(),
+        }
+    }
+
+    // for quiescence search, only captures and promotions that don't lose material
+    pub fn new_captures() -> Self {
+        MovePicker {
+            stage: Stage::GenerateCaptures,
+            captures_only: true,
+            hash_move: None,
+            killers: [None; 2],
+            counter_move: None,
+            killer_index: 0,
+            captures: Vec::new(),
+            bad_captures: Vec::new(),
+            quiets: Vec::new(),
+        }
+    }
+
+    // most valuable victim first, least valuable attacker breaks ties
+    pub fn get_mvv_lva(board: &Board, r#move: Move) -> i32 {
+        let (x, y) = Square::u8_to_tuple(r#move.to);
+        let (start_x, start_y) = Square::u8_to_tuple(r#move.from);
+        let victim = board.get_index(x, y).get_piece().map_or(
+            if board.is_capture(r#move) { Pawn.get_value() } else { 0 }, // en passant
+            |piece| piece.r#type.get_value(),
+        );
+        let attacker = board.get_index(start_x, start_y).get_piece().map_or(0, |piece| piece.r#type.get_value());
+        let promotion = r#move.promotion.map_or(0, |r#type| r#type.get_value());
+
+        (victim + promotion) * 10 - attacker
+    }
+
+    fn is_quiet_candidate(&self, board: &Board, r#move: Option<Move>) -> Option<Move> {
+        r#move.filter(|&r#move|
+            Some(r#move) != self.hash_move
+                && !board.is_capture(r#move)
+                && !board.is_promotion(r#move)
+                && board.is_legal(r#move)
+        )
+    }
+
+    fn already_tried(&self, r#move: Move) -> bool {
+        Some(r#move) == self.hash_move
+            || self.killers.contains(&Some(r#move))
+            || Some(r#move) == self.counter_move
+    }
+
+    // selection sort, usually only the first few moves get looked at
+    fn pop_best(moves: &mut Vec<(Move, i32)>) -> Option<Move> {
+        let best = moves.iter().enumerate().max_by_key(|(_, (_, score))| *score)?.0;
+        Some(moves.swap_remove(best).0)
+    }
+
+    pub fn next(&mut self, board: &Board, heuristics: &Heuristics) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if self.hash_move.is_some() {
+                        return self.hash_move;
+                    }
+                },
+                Stage::GenerateCaptures => {
+                    for r#move in board.get_legal_captures() {
+                        if Some(r#move) == self.hash_move {
+                            continue;
+                        }
+
+                        let score = Self::get_mvv_lva(board, r#move);
+                        let underpromotion = r#move.promotion.is_some_and(|r#type| r#type != Queen);
+                        if underpromotion || board.see(r#move) < 0 {
+                            self.bad_captures.push((r#move, score));
+                        } else {
+                            self.captures.push((r#move, score));
+                        }
+                    }
+                    self.stage = Stage::GoodCaptures;
+                },
+                Stage::GoodCaptures => {
+                    if let Some(r#move) = Self::pop_best(&mut self.captures) {
+                        return Some(r#move);
+                    }
+                    self.stage = if self.captures_only { Stage::Done } else { Stage::Killers };
+                },
+                Stage::Killers => {
+                    while self.killer_index < 2 {
+                        let killer = self.killers[self.killer_index];
+                        self.killer_index += 1;
+                        if let Some(r#move) = self.is_quiet_candidate(board, killer) {
+                            return Some(r#move);
+                        }
+                    }
+                    self.stage = Stage::CounterMove;
+                },
+                Stage::CounterMove => {
+                    self.stage = Stage::GenerateQuiets;
+                    if !self.killers.contains(&self.counter_move) {
+                        if let Some(r#move) = self.is_quiet_candidate(board, self.counter_move) {
+                            return Some(r#move);
+                        }
+                    }
+                },
+                Stage::GenerateQuiets => {
+                    let color = board.get_turn();
+                    for r#move in board.get_legal_quiets() {
+                        if !self.already_tried(r#move) {
+                            self.quiets.push((r#move, heuristics.get_history(color, r#move)));
+                        }
+                    }
+                    self.stage = Stage::Quiets;
+                },
+                Stage::Quiets => {
+                    if let Some(r#move) = Self::pop_best(&mut self.quiets) {
+                        return Some(r#move);
+                    }
+                    self.stage = Stage::BadCaptures;
+                },
+                Stage::BadCaptures => {
+                    if let Some(r#move) = Self::pop_best(&mut self.bad_captures) {
+                        return Some(r#move);
+                    }
+                    self.stage = Stage::Done;
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::Color::*, variant::Variant};
+
+    fn get_board(variant: Variant, fen: &str) -> Board {
+        let mut board = Board::new();
+        board.set_variant(variant);
+        board.init(fen).unwrap();
+        board
+    }
+
+    fn get_moves(board: &Board, mut picker: MovePicker, heuristics: &Heuristics) -> Vec<String> {
+        let mut moves = Vec::new();
+        while let Some(r#move) = picker.next(board, heuristics) {
+            moves.push(r#move.to_string());
+        }
+        moves
+    }
+
+    fn get_uci(uci: &str) -> Move {
+        Move::from_uci(uci).unwrap()
+    }
+
+    #[test]
+    fn every_move_once() {
+        let positions = [
+            (Variant::Standard, ""),
+            (Variant::Standard, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+            (Variant::Standard, "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"),
+            (Variant::Standard, "4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1"),
+            (Variant::Crazyhouse, "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R[Pn] w KQkq - 0 1"),
+            (Variant::Antichess, "rnbqkbnr/pppp1ppp/8/4p3/3P4/8/PPP1PPPP/RNBQKBNR w - - 0 1"),
+        ];
+        // the same heuristics are shared between positions, so killers and counter moves can be
+        // captures, illegal or missing in the current one
+        let mut heuristics = Heuristics::new();
+        for (i, (variant, fen)) in positions.into_iter().enumerate() {
+            let board = get_board(variant, fen);
+            let mut legal: Vec<String> = board.get_legal_moves().iter().map(ToString::to_string).collect();
+            legal.sort();
+
+            for (j, &r#move) in board.get_legal_moves().iter().enumerate().step_by(3) {
+                heuristics.update_cutoff(board.get_turn(), 2, j as u8 % 8, Some(get_uci("e7e5")), r#move, &[]);
+                for hash_move in [None, Some(r#move), Some(get_uci("a1a8"))] {
+                    let mut moves = get_moves(&board, MovePicker::new(&board, hash_move, &heuristics, 2, Some(get_uci("e7e5"))), &heuristics);
+                    moves.sort();
+                    assert_eq!(moves, legal, "position {i}, hash move {hash_move:?}");
+                }
+            }
+
+            // quiescence gets the captures and promotions that don't lose material
+            let captures = get_moves(&board, MovePicker::new_captures(), &heuristics);
+            for r#move in &captures {
+                let r#move = get_uci(r#move);
+                assert!(board.is_capture(r#move) || board.is_promotion(r#move));
+                assert!(board.see(r#move) >= 0 && r#move.promotion.is_none_or(|r#type| r#type == Queen));
+            }
+        }
+    }
+
+    #[test]
+    fn hash_move_first_and_bad_captures_last() {
+        // Qxd5 loses the queen to the pawn, Nxe4 trades knights
+        let board = get_board(Variant::Standard, "4k3/8/2p5/3p4/4n3/2N5/8/3QK3 w - - 0 1");
+        let heuristics = Heuristics::new();
+        let moves = get_moves(&board, MovePicker::new(&board, Some(get_uci("e1e2")), &heuristics, 0, None), &heuristics);
+        assert_eq!(moves[0], "e1e2");
+        assert_eq!(moves[1], "c3e4");
+        assert_eq!(moves[moves.len() - 1], "d1d5");
+        assert_eq!(moves.iter().filter(|r#move| *r#move == "e1e2").count(), 1);
+
+        // a hash move that isn't legal here is ignored
+        let moves = get_moves(&board, MovePicker::new(&board, Some(get_uci("a2a4")), &heuristics, 0, None), &heuristics);
+        assert_eq!(moves[0], "c3e4");
+
+        // promoting on a8 loses the queen to the rook, and underpromotions are treated as bad captures
+        let board = get_board(Variant::Standard, "1r1qk3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        let moves = get_moves(&board, MovePicker::new(&board, None, &heuristics, 0, None), &heuristics);
+        assert_eq!(moves[0], "a7b8q");
+        assert!(moves[1..moves.len() - 7].iter().all(|r#move| r#move.len() == 4));
+        assert!(moves[moves.len() - 7..].iter().all(|r#move| r#move.len() == 5));
+        assert_eq!(get_moves(&board, MovePicker::new_captures(), &heuristics), ["a7b8q"]);
+    }
+
+    #[test]
+    fn killers_and_history() {
+        let board = get_board(Variant::Standard, "");
+        let mut heuristics = Heuristics::new();
+        let (killer, counter, good, bad) = (get_uci("g1f3"), get_uci("b1c3"), get_uci("h2h3"), get_uci("a2a3"));
+
+        heuristics.update_cutoff(White, 3, 6, None, get_uci("d2d4"), &[bad]);
+        heuristics.update_cutoff(White, 3, 6, Some(get_uci("e7e5")), killer, &[bad]);
+        heuristics.update_cutoff(White, 5, 2, Some(get_uci("d7d5")), counter, &[]);
+        heuristics.update_cutoff(White, 7, 4, None, good, &[]);
+        assert_eq!(heuristics.get_killers(3), [Some(killer), Some(get_uci("d2d4"))]);
+        assert_eq!(heuristics.get_counter_move(Some(get_uci("d7d5"))), Some(counter));
+        assert!(heuristics.get_history(White, good) > 0 && heuristics.get_history(White, bad) < 0);
+        assert_eq!(heuristics.get_history(Black, good), 0);
+
+        let moves = get_moves(&board, MovePicker::new(&board, None, &heuristics, 3, Some(get_uci("d7d5"))), &heuristics);
+        assert_eq!(moves[..4], ["g1f3", "d2d4", "b1c3", "h2h3"]);
+        assert_eq!(moves[moves.len() - 1], "a2a3");
+        assert_eq!(moves.len(), 20);
+
+        // history stays bounded however often a move cuts off
+        for _ in 0..1000 {
+            heuristics.update_cutoff(White, 0, 40, None, good, &[bad]);
+        }
+        assert!(heuristics.get_history(White, good) <= HISTORY_MAX && heuristics.get_history(White, bad) >= -HISTORY_MAX);
+
+        heuristics.clear();
+        assert_eq!(heuristics.get_killers(3), [None; 2]);
+        assert_eq!(heuristics.get_history(White, good), 0);
+    }
+}
@@ -0,0 +1,330 @@
+use std::{fmt, fs, path::Path, sync::Arc};
+use crate::{board::{Board, Color::{self, *}}, pieces::{Piece, Pieces::*}};
+
+// efficiently updatable neural network evaluation
+//
+// the network is (768 -> HIDDEN) x 2 -> 1: every piece on a square is an input feature, each side has its
+// own accumulator of the hidden layer seen from its side of the board, and the output layer looks at the
+// side to move's accumulator first. Weights are stored quantised as little endian i16, in the same layout
+// as the simple example of the bullet trainer writes them, so nets trained with it can be loaded directly:
+//
+//     feature weights [768][HIDDEN], feature biases [HIDDEN], output weights [2 * HIDDEN], output bias
+//
+// and the file is padded with zeroes to a multiple of 64 bytes
+
+const FEATURES: usize = 768;
+
+// quantisation of the hidden and output layers, and the scale from the net's output to centipawns
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+// inputs are numbered pawn, knight, bishop, rook, queen, king, as trainers usually do
+fn get_feature(piece: Piece, square: usize, perspective: Color) -> usize {
+    let r#type = match piece.r#type {
+        Pawn => 0,
+        Knight => 1,
+        Bishop => 2,
+        Rook => 3,
+        Queen => 4,
+        King => 5,
+    };
+    // black sees the board upside down, with its own pieces first like white
+    let (side, square) = match perspective {
+        White => (piece.color != White, square),
+        Black => (piece.color != Black, square ^ 56),
+    };
+    (side as usize * 6 + r#type) * 64 + square
+}
+
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+    vectorised: bool, // the output layer can use avx2
+}
+
+// the weights are far too many to print
+impl fmt::Debug for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Network {{ hidden: {}, vectorised: {} }}", self.hidden, self.vectorised)
+    }
+}
+
+impl Network {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, &'static str> {
+        let bytes = fs::read(path).map_err(|_| "Could not read network file")?;
+        Self::from_bytes(&bytes)
+    }
+
+    // the hidden layer size isn't stored, it follows from the file size
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        let values_per_neuron = FEATURES + 1 + 2;
+        let hidden = bytes.len().saturating_sub(2) / 2 / values_per_neuron;
+        let size = (hidden * values_per_neuron + 1) * 2;
+        if hidden == 0 || bytes.len() - size >= 64 {
+            return Err("Network file size doesn't match any hidden layer size");
+        }
+
+        let mut values = bytes.chunks_exact(2).map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut take = |count: usize| values.by_ref().take(count).collect::<Vec<_>>();
+        let (feature_weights, feature_biases, output_weights, output_bias) = (take(FEATURES * hidden), take(hidden), take(2 * hidden), take(1)[0]);
+
+        // the vector version multiplies the clamped value by the weight in 16 bits first, which only
+        // fits for the weights trainers produce, anything else takes the slow path
+        let vectorised = has_avx2() && output_weights.iter().all(|&weight| (weight as i32 * QA).abs() <= i16::MAX as i32);
+
+        Ok(Network { hidden, feature_weights, feature_biases, output_weights, output_bias, vectorised })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = [&self.feature_weights, &self.feature_biases, &self.output_weights]
+            .into_iter()
+            .flat_map(|values| values.iter())
+            .chain([&self.output_bias])
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        bytes.resize(bytes.len().next_multiple_of(64), 0);
+        bytes
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), &'static str> {
+        fs::write(path, self.to_bytes()).map_err(|_| "Could not write network file")
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    fn get_column(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+
+    // centipawns from the side to move's point of view, computed from scratch,
+    // see Evaluator for keeping the accumulators up to date during a search instead
+    pub fn evaluate(&self, board: &Board) -> i32 {
+        self.get_output(&Accumulator::new(self, board), board.get_turn())
+    }
+
+    fn get_output(&self, accumulator: &Accumulator, turn: Color) -> i32 {
+        let (us, them) = (&accumulator.values[turn as usize], &accumulator.values[(!turn) as usize]);
+        let sum = self.screlu_dot(us, &self.output_weights[..self.hidden]) + self.screlu_dot(them, &self.output_weights[self.hidden..]);
+        (sum / QA + self.output_bias as i32) * SCALE / (QA * QB)
+    }
+
+    // sum of clamp(x, 0, QA)^2 * weight over the hidden layer
+    fn screlu_dot(&self, values: &[i16], weights: &[i16]) -> i32 {
+        #[cfg(target_arch = "x86_64")]
+        if self.vectorised {
+            // SAFETY: avx2 support was checked when loading
+            return unsafe { screlu_dot_avx2(values, weights) };
+        }
+
+        screlu_dot_scalar(values, weights)
+    }
+}
+
+fn has_avx2() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    return is_x86_feature_detected!("avx2");
+    #[cfg(not(target_arch = "x86_64"))]
+    return false;
+}
+
+// wraps around on overflow like the vector version does
+fn screlu_dot_scalar(values: &[i16], weights: &[i16]) -> i32 {
+    values.iter().zip(weights).fold(0i32, |sum, (&value, &weight)| {
+        let value = (value as i32).clamp(0, QA);
+        sum.wrapping_add(value * value * weight as i32)
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn screlu_dot_avx2(values: &[i16], weights: &[i16]) -> i32 {
+    use std::arch::x86_64::*;
+
+    let chunks = values.len() / 16 * 16;
+    let zero = _mm256_setzero_si256();
+    let max = _mm256_set1_epi16(QA as i16);
+    let mut sum = _mm256_setzero_si256();
+    // the clamped value times the weight still fits in 16 bits, times the value again goes into 32 bits
+    for i in (0..chunks).step_by(16) {
+        let value = _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i);
+        let weight = _mm256_loadu_si256(weights.as_ptr().add(i) as *const __m256i);
+        let value = _mm256_min_epi16(_mm256_max_epi16(value, zero), max);
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(_mm256_mullo_epi16(value, weight), value));
+    }
+
+    let mut lanes = [0i32; 8];
+    _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+    lanes.iter().fold(0i32, |sum, &lane| sum.wrapping_add(lane)).wrapping_add(screlu_dot_scalar(&values[chunks..], &weights[chunks..]))
+}
+
+// the hidden layer before activation, for both perspectives
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Accumulator {
+    values: [Vec<i16>; 2], // by Color
+}
+
+impl Accumulator {
+    pub fn new(network: &Network, board: &Board) -> Self {
+        let mut accumulator = Accumulator { values: [network.feature_biases.clone(), network.feature_biases.clone()] };
+        for (square, piece) in board.get_state().iter().enumerate().filter_map(|(i, square)| Some((i, square.get_piece()?))) {
+            accumulator.add(network, piece, square);
+        }
+        accumulator
+    }
+
+    fn add(&mut self, network: &Network, piece: Piece, square: usize) {
+        for perspective in [White, Black] {
+            let column = network.get_column(get_feature(piece, square, perspective));
+            for (value, weight) in self.values[perspective as usize].iter_mut().zip(column) {
+                *value = value.wrapping_add(*weight);
+            }
+        }
+    }
+
+    fn remove(&mut self, network: &Network, piece: Piece, square: usize) {
+        for perspective in [White, Black] {
+            let column = network.get_column(get_feature(piece, square, perspective));
+            for (value, weight) in self.values[perspective as usize].iter_mut().zip(column) {
+                *value = value.wrapping_sub(*weight);
+            }
+        }
+    }
+
+    // only the squares that changed between the two positions are looked at, so this works the same
+    // for captures, castling, en passant and promotions
+    pub fn update(&mut self, network: &Network, before: &Board, after: &Board) {
+        let (before, after) = (before.get_state(), after.get_state());
+        for square in 0..64 {
+            let (old, new) = (before[square].get_piece(), after[square].get_piece());
+            if old != new {
+                if let Some(piece) = old {
+                    self.remove(network, piece, square);
+                }
+                if let Some(piece) = new {
+                    self.add(network, piece, square);
+                }
+            }
+        }
+    }
+}
+
+// a stack of accumulators following the search, push when making a move and pop when taking it back
+pub struct Evaluator {
+    network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    ply: usize,
+}
+
+impl Evaluator {
+    pub fn new(network: Arc<Network>, board: &Board) -> Self {
+        let stack = vec![Accumulator::new(&network, board)];
+        Evaluator { network, stack, ply: 0 }
+    }
+
+    pub fn push(&mut self, before: &Board, after: &Board) {
+        // the accumulators of earlier branches are overwritten instead of allocating new ones
+        if self.stack.len() == self.ply + 1 {
+            self.stack.push(self.stack[self.ply].clone());
+        } else {
+            let (done, rest) = self.stack.split_at_mut(self.ply + 1);
+            rest[0].clone_from(&done[self.ply]);
+        }
+        self.ply += 1;
+        self.stack[self.ply].update(&self.network, before, after);
+    }
+
+    pub fn pop(&mut self) {
+        self.ply = self.ply.saturating_sub(1);
+    }
+
+    pub fn evaluate(&self, board: &Board) -> i32 {
+        self.network.get_output(&self.stack[self.ply], board.get_turn())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Move;
+
+    // a network of small random weights, with a hidden layer that isn't a multiple of the vector width
+    fn get_network(hidden: usize, weight: i16) -> Vec<u8> {
+        let mut state = 0x9e3779b97f4a7c15u64;
+        let mut bytes: Vec<u8> = (0..(FEATURES + 3) * hidden + 1)
+            .flat_map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                ((state % (2 * weight as u64 + 1)) as i16 - weight).to_le_bytes()
+            })
+            .collect();
+        bytes.resize(bytes.len().next_multiple_of(64), 0);
+        bytes
+    }
+
+    #[test]
+    fn reads_networks() {
+        let bytes = get_network(40, 100);
+        let network = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(network.hidden_size(), 40);
+        assert_eq!(network.to_bytes(), bytes);
+
+        for size in [0, 1, 64, bytes.len() - 64, bytes.len() + 64, bytes.len() + 1000] {
+            let mut wrong = bytes.clone();
+            wrong.resize(size, 0);
+            assert!(Network::from_bytes(&wrong).is_err(), "{size} bytes");
+        }
+    }
+
+    #[test]
+    fn updates_match_a_refresh() {
+        let network = Network::from_bytes(&get_network(40, 100)).unwrap();
+        let mut board = Board::new();
+        board.set_fen("r3k2r/1P3ppp/8/3pP3/8/8/5PPP/R3K2R w KQkq d6 0 1").unwrap();
+        let mut accumulator = Accumulator::new(&network, &board);
+
+        // en passant, castling both ways, a capture promotion and a capture
+        for uci in ["e5d6", "e8g8", "b7a8n", "g8h8", "e1c1", "f8a8", "d6d7", "h8g8", "d7d8q", "a8d8"] {
+            let before = board;
+            board.play(Move::from_uci(uci).unwrap()).expect(uci);
+            accumulator.update(&network, &before, &board);
+            assert_eq!(accumulator, Accumulator::new(&network, &board), "after {uci}");
+        }
+
+        let mut evaluator = Evaluator::new(Arc::new(network), &board);
+        let start = board;
+        board.play(Move::from_uci("d1d8").unwrap()).unwrap();
+        evaluator.push(&start, &board);
+        assert_eq!(evaluator.evaluate(&board), evaluator.network.evaluate(&board));
+        evaluator.pop();
+        assert_eq!(evaluator.evaluate(&start), evaluator.network.evaluate(&start));
+    }
+
+    #[test]
+    fn vector_dot_matches_scalar() {
+        let network = Network::from_bytes(&get_network(40, 100)).unwrap();
+        assert_eq!(network.vectorised, has_avx2());
+        // values below zero and above QA get clamped
+        let values: Vec<i16> = (0..40).map(|i| (i * 37 % 600) as i16 - 150).collect();
+        let weights = &network.output_weights[..40];
+        let expected = values.iter().zip(weights).map(|(&value, &weight)| (value as i32).clamp(0, QA).pow(2) * weight as i32).sum::<i32>();
+        assert_eq!(screlu_dot_scalar(&values, weights), expected);
+        assert_eq!(network.screlu_dot(&values, weights), expected);
+
+        #[cfg(target_arch = "x86_64")]
+        if has_avx2() {
+            // SAFETY: avx2 support was just checked
+            assert_eq!(unsafe { screlu_dot_avx2(&values, weights) }, expected);
+        }
+
+        // weights too large for 16 bits once multiplied don't use the vector version
+        let network = Network::from_bytes(&get_network(40, 200)).unwrap();
+        assert!(!network.vectorised);
+    }
+}
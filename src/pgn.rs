@@ -0,0 +1,210 @@
+use crate::{board::{Board, Move}, variant::Variant};
+
+// reading games in portable game notation, variations, comments and annotations are skipped
+
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>, // in file order, Event, Site, White, ...
+    pub moves: Vec<Move>,
+    pub result: String, // 1-0, 0-1, 1/2-1/2 or *
+}
+
+impl PgnGame {
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    // games can start from a custom position given by the FEN tag, and be of a variant given by the Variant tag
+    pub fn get_start(&self) -> Result<Board, &'static str> {
+        let mut board = Board::new();
+        match self.get_tag("Variant") {
+            Some(name) if name.eq_ignore_ascii_case("chess960") => board.set_chess960(true),
+            // lichess marks standard games that start from a FEN this way
+            Some(name) if name.eq_ignore_ascii_case("from position") => (),
+            Some(name) => board.set_variant(Variant::from_name(name).ok_or("Unknown variant in PGN")?),
+            None => (),
+        }
+        board.init(self.get_tag("FEN").unwrap_or(""))?;
+        Ok(board)
+    }
+}
+
+fn is_result(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+// splits the movetext into SAN tokens, dropping move numbers, NAGs, results, comments and variations
+fn get_tokens(movetext: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut chars = movetext.chars();
+    let mut depth = 0; // variation nesting
+
+    let mut flush = |token: &mut String, depth: i32| {
+        let trimmed = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if depth == 0 && !trimmed.is_empty() && !token.starts_with('$') && !is_result(token) {
+            tokens.push(trimmed.to_string());
+        }
+        token.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                flush(&mut token, depth);
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            },
+            ';' => {
+                flush(&mut token, depth);
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            },
+            '(' => {
+                flush(&mut token, depth);
+                depth += 1;
+            },
+            ')' => {
+                flush(&mut token, depth);
+                depth = (depth - 1).max(0);
+            },
+            c if c.is_whitespace() => flush(&mut token, depth),
+            c => token.push(c),
+        }
+    }
+    flush(&mut token, depth);
+
+    tokens
+}
+
+// parse a single game, the tag pairs followed by the movetext
+pub fn parse_game(text: &str) -> Result<PgnGame, &'static str> {
+    let mut game = PgnGame { result: "*".into(), ..Default::default() };
+    let mut movetext = String::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            let inner = &line[1..line.len() - 1];
+            let (name, value) = inner.split_once(char::is_whitespace).ok_or("Invalid PGN tag")?;
+            let value = value.trim();
+            let value = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value);
+            let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+            game.tags.push((name.to_string(), value));
+        } else if !line.starts_with('%') {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    // the movetext ends with the result, the tag is only a fallback
+    if let Some(result) = movetext.split_whitespace().last().filter(|token| is_result(token)).or(game.get_tag("Result")) {
+        if is_result(result) {
+            game.result = result.into();
+        }
+    }
+
+    let mut board = game.get_start()?;
+    for token in get_tokens(&movetext) {
+        let r#move = board.parse_san(&token)?;
+        board.apply_move(r#move);
+        game.moves.push(r#move);
+    }
+
+    Ok(game)
+}
+
+// a file usually holds many games, each one is a block of tags followed by its movetext
+pub fn split_games(text: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let mut start = 0;
+    let mut in_movetext = false;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            if in_movetext {
+                games.push(&text[start..offset]);
+                start = offset;
+                in_movetext = false;
+            }
+        } else if !trimmed.is_empty() {
+            in_movetext = true;
+        }
+        offset += line.len();
+    }
+
+    if !text[start..].trim().is_empty() {
+        games.push(&text[start..]);
+    }
+    games
+}
+
+// every game in the text that could be read, along with the errors for the ones that couldn't
+pub fn parse_games(text: &str) -> Vec<Result<PgnGame, &'static str>> {
+    split_games(text).into_iter().map(parse_game).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = r#"[Event "Rated Blitz game"]
+[White "Alice \"the rook\" Smith"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 $1
+3. Bb5!? a6 ; the Morphy defence
+4. Ba4 Nf6 5. O-O 1-0
+
+[Event "Second game"]
+[Result "*"]
+
+1. d4 d5 2. c4 dxc4 {accepted} 3. e3 b5?! 4. a4 c6 5. axb5 cxb5 6. Qf3 1/2-1/2
+
+[Event "Broken"]
+
+1. e4 e5 2. Ke3 *
+"#;
+
+    #[test]
+    fn parses_several_games() {
+        let games = parse_games(GAMES);
+        assert_eq!(games.len(), 3);
+
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.get_tag("White"), Some("Alice \"the rook\" Smith"));
+        assert_eq!(first.get_tag("Black"), Some("Bob"));
+        assert_eq!(first.result, "1-0");
+        let moves: Vec<String> = first.moves.iter().map(ToString::to_string).collect();
+        assert_eq!(moves, ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5a4", "g8f6", "e1g1"]);
+
+        // the movetext wins over the tag
+        let second = games[1].as_ref().unwrap();
+        assert_eq!(second.result, "1/2-1/2");
+        assert_eq!(second.moves.len(), 11);
+
+        assert_eq!(games[2].as_ref().err(), Some(&"Illegal move"));
+    }
+
+    #[test]
+    fn from_position() {
+        let game = parse_game(r#"[Event "Casual game"]
+[Variant "From Position"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+[SetUp "1"]
+
+1. e4 Kd7 2. e5 1/2-1/2"#).unwrap();
+        assert_eq!(game.moves.len(), 3);
+        assert_eq!(game.get_start().unwrap().get_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(game.result, "1/2-1/2");
+    }
+}
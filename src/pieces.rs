@@ -1,8 +1,9 @@
-use crate::board::{Color::{self, *}, Board, Square};
+use crate::{board::{Color::{self, *}, Board, Move, Square}, variant::Variant};
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Pieces {
     Pawn,
     Rook,
@@ -12,6 +13,19 @@ pub enum Pieces {
     King,
 }
 
+impl Pieces {
+    // material value in centipawns, the king is priceless so it isn't counted
+    pub fn get_value(&self) -> i32 {
+        match self {
+            Pawn => 100,
+            Knight => 320,
+            Bishop => 330,
+            Rook => 500,
+            Queen => 900,
+            King => 0,
+        }
+    }
+}
 
 use Pieces::*;
 // use phf::phf_map;
@@ -21,7 +35,8 @@ use Pieces::*;
 //     Pawn => [(0, 1)]
 // };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
     pub r#type: Pieces,
     pub color: Color,
@@ -49,314 +64,125 @@ impl Piece {
     }
 
     pub fn get_moves(&self, board: &mut Board, coord: &Square) -> Vec<u8> {
+        let mut moves = self.get_pseudo_moves(board, coord);
+
+        // check if any move results in check
+        moves.retain(|&to| board.is_safe_move(Move::new(coord.get_coords_u8(), to)));
+        moves
+    }
+
+    // moves that follow the piece's movement rules but may leave the king in check
+    pub fn get_pseudo_moves(&self, board: &Board, coord: &Square) -> Vec<u8> {
         let mut offsets = self.get_offsets();
         let (px, py) = Square::u8_to_tuple(coord.get_coords_u8());
-        
+
         // filter offsets that would move off of the board
-        offsets.retain(|(x, y)| 
+        offsets.retain(|(x, y)|
             px as i8 + x >= 0 && px as i8 + x <= 7 &&
-            py as i8 + y >= 0 && py as i8 + y <= 7        
+            py as i8 + y >= 0 && py as i8 + y <= 7
         );
 
         // filter offsets where a piece of the same color already exists
-        offsets.retain(|(x, y)| { // problem 
-            return !board.get_index((px as i8 + x) as u8, (py as i8 + y) as u8).get_piece().is_some_and(|piece| piece.color == self.color);
+        offsets.retain(|(x, y)| {
+            !board.get_index((px as i8 + x) as u8, (py as i8 + y) as u8).get_piece().is_some_and(|piece| piece.color == self.color)
         });
 
+        // atomic kings can't capture, they would blow themselves up
+        if self.r#type == King && board.get_variant() == Variant::Atomic {
+            offsets.retain(|(x, y)| board.get_index((px as i8 + x) as u8, (py as i8 + y) as u8).get_piece().is_none());
+        }
+
         // instead of a has_moved property, just check if the pawn is on that colors starting square
-        // remove pawn captures if there's no piece 
         if self.r#type == Pawn {
-            // check if move is an en passant
-            offsets.retain(|(x, y)| {
-                if *x == 0 { return true; }
-                return board.get_index((px as i8 + x) as u8, (py as i8 + y) as u8).get_piece().is_some_and(|p| p.color != self.color);
-            });
-
-            match board.get_enpassantable(!self.color) {
-                x if px < 7 && x.contains(&(px + 1)) => offsets.push((1, 1)),
-                x if px > 0 && x.contains(&(px - 1)) => offsets.push((1, -1)),
-                _ => ()
+            let (home, enpassant_rank) = match self.color {
+                White => (1, 4),
+                Black => (6, 3),
             };
-        }
+            // horde pawns on the first rank can step up two squares as well
+            let home = if board.get_variant() == Variant::Horde && self.color == White && py == 0 { 0 } else { home };
 
-        // remove invalid vertical and horizontal offsets
-        if matches!(self.r#type, Pawn | Rook | King | Queen) {
-            // horizontal
             offsets.retain(|(x, y)| {
-                if *y != 0 { return true }
-
-                for i in x.signum()..*x {
-                    if board.get_index((px as i8 + i) as u8, py).get_piece().is_some() {
-                        return false;
-                    }
+                let target = board.get_index((px as i8 + x) as u8, (py as i8 + y) as u8).get_piece();
+                if *x == 0 {
+                    // pushes can't capture, and can only be doubled from the starting square
+                    return target.is_none() && (y.abs() == 1 || py == home);
                 }
 
-                return true;
-            });
-
-            // vertical
-            offsets.retain(|(x, y)| {
-                if *x != 0 { return true } // is this necessary?
-
-                for i in y.signum()..*y {
-                    if board.get_index(px, (py as i8 + i) as u8).get_piece().is_some() {
-                        return false;
-                    }
-                }
-
-                return true;
+                // captures need a piece to capture, unless the pawn beside us can be taken en passant
+                target.is_some() || (py == enpassant_rank && board.get_enpassantable(!self.color).contains(&((px as i8 + x) as u8)))
             });
         }
 
-
-
-        // remove invalid diagonal offsets
-        if matches!(self.r#type, King | Queen | Bishop) {
-            offsets.retain(|(mut x, mut y)| {
-                if x.abs() != y.abs() { return true }
-
-                // x and y minus respective signums to 0
-                while x != x.signum() {                
-                    if board.get_index((px as i8 + (x - x.signum())) as u8 , (py as i8 + (y - y.signum())) as u8).get_piece().is_some() {
-                        return false;
-                    }
-
-                    x -= x.signum();
-                    y -= y.signum();
-                }
-
-                return true;
+        // remove offsets that would pass through another piece
+        if self.r#type != Knight {
+            offsets.retain(|(x, y)| {
+                let (dx, dy) = (x.signum(), y.signum());
+                (1..x.abs().max(y.abs())).all(|i|
+                    board.get_index((px as i8 + dx * i) as u8, (py as i8 + dy * i) as u8).get_piece().is_none()
+                )
             });
         }
 
-        if self.r#type == Pawn {
-            offsets.retain(|(x, y)| *x != 0 || !board.get_index((px as i8 + x) as u8, (py as i8 + y) as u8).get_piece().is_some());
+        if self.r#type == King {
+            offsets.extend(self.get_castling_offsets(board, px, py));
         }
 
-        // check if any move results in check
-        offsets.retain(|(x, y)| {
-            let mut ephemeral_board = board.clone();
-            // generate move from offset
-            let start_piece = ephemeral_board.get_index(px, py).get_piece().unwrap();
-            ephemeral_board.get_index_mut((px as i8 + x) as u8, (py as i8 + y) as u8).set_piece(start_piece);
-            ephemeral_board.get_index_mut(px, py).remove_piece();
-
-            let king = ephemeral_board.get_state().into_iter().find(|square| 
-                square.get_piece().is_some_and(|piece| piece.r#type == King && piece.color == self.color)
-            ).expect("Unable to find king");
-
-            let (kx, ky) = Square::u8_to_tuple(king.get_coords_u8());
-
-            // search down 
-            let mut offset: i8 = 1;
-            // check for king
-            if ky > 0 && ephemeral_board.get_index(kx, (ky as i8 - offset) as u8).get_piece().is_some_and(|piece| piece.r#type == King) {
-                return false;
-            }
-
-            while ky as i8 - offset >= 0 {
-                let piece = ephemeral_board.get_index(kx, (ky as i8 - offset) as u8).get_piece();
-
-                if piece.is_some_and(|piece| piece.color == self.color) {
-                    break;
-                }
-
-                if piece.is_some_and(|piece| matches!(piece.r#type, Rook | Queen)) {
-                    return false;
-                }
-
-                offset += 1;
-            }
-
-            // search up
-            let mut offset: i8 = 1;
-            // check for king
-            if ky < 7 && ephemeral_board.get_index(kx, (ky as i8 + offset) as u8).get_piece() .is_some_and(|piece| piece.r#type == King) {
-                return false;
-            }
-
-            while ky as i8 + offset <= 7 {
-                let piece = ephemeral_board.get_index(kx, (ky as i8 + offset) as u8).get_piece();
-
-                // if a piece of the same color is hit before one of the other color, the king is covered in this direction
-                if piece.is_some_and(|piece| piece.color == self.color) {
-                    break;
-                }
-
-                if piece.is_some_and(|piece| matches!(piece.r#type, Rook | Queen)) {
-                    return false;
-                }
-
-                offset += 1;
-            }
-
-            // should probably flip these as "left" is increasing index value from the perspective of the matrix
-            // search left
-            let mut offset: i8 = 1;
-            // check for king
-            if kx as i8 - offset >= 0 && ephemeral_board.get_index((kx as i8 - offset) as u8, ky).get_piece().is_some_and(|piece| piece.r#type == King) {
-                return false;
-            }
-
-            while kx as i8 - offset >= 0 {
-                let piece = ephemeral_board.get_index((kx as i8 - offset) as u8, ky).get_piece();
-
-                if piece.is_some_and(|piece| piece.color == self.color) {
-                    break;
-                }
-
-                if piece.is_some_and(|piece| matches!(piece.r#type, Rook | Queen)) {
-                    return false;
-                }
-
-                offset += 1;
-            }
-
-            
-            // search right
-            let mut offset: i8 = 1;
-            // check for king
-            if kx as i8 + offset <= 7 && ephemeral_board.get_index((kx as i8 + offset) as u8, ky).get_piece().is_some_and(|piece| piece.r#type == King) {
-                return false;
-            }
-
-            while kx as i8 + offset <= 7 {
-                let piece = ephemeral_board.get_index((kx as i8 + offset) as u8, ky).get_piece();
-
-                if piece.is_some_and(|piece| piece.color == self.color) {
-                    break;
-                }
-
-                if piece.is_some_and(|piece| matches!(piece.r#type, Rook | Queen)) {
-                    return false;
-                }
-
-                offset += 1;
-            }
-
-            // search down/left
-            let mut offset: i8 = 1;
-            // check for king or pawn
-            if  kx as i8 - offset >= 0 && ky as i8 - offset >= 0 &&
-                ephemeral_board
-                    .get_index((kx as i8 - offset) as u8, (ky as i8 - offset) as u8)
-                    .get_piece()
-                    .is_some_and(|piece| 
-                        piece.r#type == King || (piece.r#type == Pawn && piece.color == White && self.color == Black) // check if there's a pawn in an attacking position
-                    ) // this is almost certainly not the best way to do this
-            {
-                return false;
-            }
-
-
-            while kx as i8 - offset >= 0 && ky as i8 - offset >= 0 {
-                let piece = ephemeral_board.get_index((kx as i8 - offset) as u8, (ky as i8 - offset) as u8).get_piece();
-
-                if piece.is_some_and(|piece| piece.color == self.color) {
-                    break;
-                }
-
-                if piece.is_some_and(|piece| matches!(piece.r#type, Bishop | Queen)) {
-                    return false;
-                }
-
-                offset += 1;
-            }
-            // move all king/pawn checks to a single area
-            // search down/right
-            let mut offset: i8 = 1;
-            // check for king or pawn
-            if kx as i8 + offset <= 7 && ky as i8 - offset >= 0 &&
-                ephemeral_board
-                    .get_index((kx as i8 + offset) as u8, (ky as i8 - offset) as u8)
-                    .get_piece()
-                    .is_some_and(|piece| 
-                        piece.r#type == King || (piece.r#type == Pawn && piece.color == White && self.color == Black)
-                    )
-            {
-                return false;
-            }
-            
-            while kx as i8 + offset <= 7 && ky as i8 - offset >= 0 {
-                let piece = ephemeral_board.get_index((kx as i8 + offset) as u8, (ky as i8 - offset) as u8).get_piece();
+        offsets.into_iter().map(|(x, y)| (((px as i8 + x) as u8) << 4) + ((py as i8 + y) as u8)).collect()
+    }
 
-                if piece.is_some_and(|piece| piece.color == self.color) {
-                    break;
-                }
+    // castling puts the king and rook on the same squares whichever files they started on, and is only
+    // allowed if the king isn't in check, nothing stands in their way and the king doesn't pass through
+    // an attacked square, where it lands is checked like for any other move
+    fn get_castling_offsets(&self, board: &Board, px: u8, py: u8) -> Vec<(i8, i8)> {
+        let home = match self.color {
+            White => 0,
+            Black => 7,
+        };
 
-                if piece.is_some_and(|piece| matches!(piece.r#type, Bishop | Queen)) {
-                    return false;
-                }
+        let mut offsets = Vec::new();
+        if py != home || board.in_check(self.color) {
+            return offsets;
+        }
 
-                offset += 1;
+        let span = |a: u8, b: u8| a.min(b)..=a.max(b);
+        for king_side in [true, false] {
+            let Some(rook_x) = board.get_castling_rook(self.color, king_side) else { continue };
+            if board.get_index(rook_x, home).get_piece() != Some(Piece::from(Rook, self.color)) {
+                continue;
             }
 
-
-            // search up/left
-            let mut offset: i8 = 1;
-            // check for king or pawn
-            if kx as i8 - offset >= 0 && ky as i8 + offset <= 7 &&
-                ephemeral_board
-                    .get_index((kx as i8 - offset) as u8, (ky as i8 + offset) as u8)
-                    .get_piece()
-                    .is_some_and(|piece| 
-                        piece.r#type == King || (piece.r#type == Pawn && piece.color == White && self.color == Black)
-                    )
-            {
-                return false;
+            let (king_x, rook_to_x) = Board::get_castling_targets(king_side);
+            let blocked = span(px, king_x).chain(span(rook_x, rook_to_x))
+                .any(|x| x != px && x != rook_x && board.get_index(x, home).get_piece().is_some());
+            if blocked {
+                continue;
             }
-            
-            while kx as i8 - offset >= 0 && ky as i8 + offset <= 7 {
-                let piece = ephemeral_board.get_index((kx as i8 - offset) as u8, (ky as i8 + offset) as u8).get_piece();
-
-                if piece.is_some_and(|piece| piece.color == self.color) {
-                    break;
-                }
 
-                if piece.is_some_and(|piece| matches!(piece.r#type, Bishop | Queen)) {
-                    return false;
-                }
-
-                offset += 1;
+            if span(px, king_x).any(|x| x != px && x != king_x && board.is_attacked(x << 4 | home, !self.color)) {
+                continue;
             }
 
-            // search up/right
-            let mut offset: i8 = 1;
-            // check for king or pawn
-            if kx as i8 + offset <= 7 && ky as i8 + offset <= 7 &&
-                ephemeral_board
-                    .get_index((kx as i8 + offset) as u8, (ky as i8 + offset) as u8)
-                    .get_piece()
-                    .is_some_and(|piece| 
-                        piece.r#type == King || (piece.r#type == Pawn && piece.color == White && self.color == Black)
-                    )
-            {
-                return false;
-            }
-            
-            while kx as i8 + offset <= 7 && ky as i8 + offset <= 7 {
-                let piece = ephemeral_board.get_index((kx as i8 + offset) as u8, (ky as i8 + offset) as u8).get_piece();
-                if piece.is_some_and(|piece| piece.color == self.color) { break }
-                if piece.is_some_and(|piece| matches!(piece.r#type, Bishop | Queen)) { return false }
+            // chess960 writes castling as the king taking its own rook
+            let to_x = if board.is_chess960() { rook_x } else { king_x };
+            offsets.push((to_x as i8 - px as i8, 0));
+        }
 
-                offset += 1;
-            }
+        offsets
+    }
 
-            // search for knights
-            for (x, y) in Piece::OFFSETS_KNIGHT.iter() {
-                if kx as i8 + x > 7 || kx as i8 + x < 0 || ky as i8 + y > 7 || ky as i8 + y < 0 { continue; }
-                if ephemeral_board.get_index((kx as i8 + x) as u8, (ky as i8 + y) as u8)
-                    .get_piece()
-                    .is_some_and(|piece| piece.r#type == Knight && piece.color != self.color)
-                {
-                    return false;    
-                }
-            }
-            
-            return true;
-        });
+    // inverse of get_char: uppercase is white, lowercase is black
+    pub fn from_char(c: char) -> Option<Self> {
+        let r#type = match c.to_ascii_lowercase() {
+            'p' => Pawn,
+            'r' => Rook,
+            'n' => Knight,
+            'b' => Bishop,
+            'q' => Queen,
+            'k' => King,
+            _ => return None,
+        };
 
-        return offsets.into_iter().map(|(x, y)| (((px as i8 + x) as u8) << 4) + ((py as i8 + y) as u8)).collect();
+        Some(Piece::from(r#type, if c.is_ascii_uppercase() { White } else { Black }))
     }
 
     pub fn get_char(&self) -> char {
@@ -374,6 +200,24 @@ impl Piece {
             _ => letter,
         }
     }
+
+    // the Unicode chess symbol, outlined for white and filled for black
+    pub fn get_symbol(&self) -> char {
+        match (self.color, self.r#type) {
+            (White, King) => '♔',
+            (White, Queen) => '♕',
+            (White, Rook) => '♖',
+            (White, Bishop) => '♗',
+            (White, Knight) => '♘',
+            (White, Pawn) => '♙',
+            (Black, King) => '♚',
+            (Black, Queen) => '♛',
+            (Black, Rook) => '♜',
+            (Black, Bishop) => '♝',
+            (Black, Knight) => '♞',
+            (Black, Pawn) => '♟',
+        }
+    }
 }
 
 impl Piece {
@@ -427,10 +271,3 @@ impl Piece {
         (-1, -1), (0, -1), (1, -1),
     ];
 }
-
-
-/*
-    to calc if a move results in self check, simply search in the straight and diagonal directions and stop when you hit a piece or an edge
-    if the piece intersected is the same color, that direction does not check.
-    else if the piece intersected is a bishop (for diagonal) or a rook (for straight) or a queen (for both) of the opposite color, it is a check
-*/
\ No newline at end of file
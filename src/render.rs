@@ -0,0 +1,192 @@
+use crate::board::{Board, Color::{self, *}, Square};
+
+// text diagrams of a board, the defaults draw the same thing as Board's Display
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Renderer {
+    pub orientation: Color, // the side at the bottom
+    pub unicode: bool, // chess symbols instead of letters
+    pub light_square: char, // drawn on empty squares
+    pub dark_square: char,
+    pub coordinates: bool, // rank numbers and file letters around the board
+    pub highlights: Vec<u8>, // squares to put in brackets, like [e4]
+    pub panel: bool, // side to move, castling rights and FEN next to the board
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer {
+            orientation: White,
+            unicode: false,
+            light_square: ' ',
+            dark_square: '#',
+            coordinates: true,
+            highlights: Vec::new(),
+            panel: false,
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // from black's side, with the board turned around
+    pub fn flipped() -> Self {
+        Renderer { orientation: Black, ..Self::default() }
+    }
+
+    pub fn render(&self, board: &Board) -> String {
+        let margin = if self.coordinates { "  " } else { "" };
+        let panel = if self.panel { Self::get_panel(board) } else { Vec::new() };
+        let (files, ranks): (Vec<u8>, Vec<u8>) = match self.orientation {
+            White => ((0..8).collect(), (0..8).rev().collect()),
+            Black => ((0..8).rev().collect(), (0..8).collect()),
+        };
+
+        let mut lines = vec![format!("{margin}╔═════════════════╗{margin}")];
+        for (row, &y) in ranks.iter().enumerate() {
+            let mut line = if self.coordinates { format!("{} ║", y + 1) } else { "║".to_string() };
+            let mut previous = false; // whether the square to the left is highlighted
+            for &x in &files {
+                let coord = (x << 4) + y;
+                let highlighted = self.highlights.contains(&coord);
+                line.push(match (previous, highlighted) {
+                    (_, true) => '[',
+                    (true, false) => ']',
+                    _ => ' ',
+                });
+                line.push(self.get_char(board, coord));
+                previous = highlighted;
+            }
+            line.push(if previous { ']' } else { ' ' });
+            line.push('║');
+            if let Some(info) = panel.get(row).filter(|info| !info.is_empty()) {
+                line.push_str("   ");
+                line.push_str(info);
+            }
+            lines.push(line);
+        }
+        lines.push(format!("{margin}╚═════════════════╝"));
+        if self.coordinates {
+            let letters: Vec<String> = files.iter().map(|x| ((b'A' + x) as char).to_string()).collect();
+            lines.push(format!("    {}", letters.join(" ")));
+        }
+
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    fn get_char(&self, board: &Board, coord: u8) -> char {
+        let (x, y) = Square::u8_to_tuple(coord);
+        let square = board.get_state()[y as usize * 8 + x as usize];
+        match square.get_piece() {
+            Some(piece) if self.unicode => piece.get_symbol(),
+            Some(piece) => piece.get_char(),
+            None if square.get_color() == White => self.light_square,
+            None => self.dark_square,
+        }
+    }
+
+    // one line per rank, starting at the top
+    fn get_panel(board: &Board) -> Vec<String> {
+        let fen = board.get_fen();
+        let fields: Vec<&str> = fen.split(' ').collect();
+        vec![
+            format!("{} to move", board.get_turn()),
+            format!("Castling: {}", fields.get(2).unwrap_or(&"-")),
+            format!("En passant: {}", fields.get(3).unwrap_or(&"-")),
+            format!("Halfmove clock: {}", board.get_halfmove()),
+            format!("Move: {}", board.get_fullmove()),
+            String::new(),
+            fen,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEN: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+
+    fn get_board() -> Board {
+        let mut board = Board::new();
+        board.init(FEN).unwrap();
+        board
+    }
+
+    fn get_lines(lines: &[&str]) -> String {
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    #[test]
+    fn default() {
+        // the same diagram Board's Display has always drawn
+        let expected = get_lines(&[
+            "  ╔═════════════════╗  ",
+            "8 ║ r # b q k b n r ║",
+            "7 ║ p p p p # p p p ║",
+            "6 ║   # n #   #   # ║",
+            "5 ║ #   #   p   #   ║",
+            "4 ║   #   # P #   # ║",
+            "3 ║ #   #   # N #   ║",
+            "2 ║ P P P P   P P P ║",
+            "1 ║ R N B Q K B # R ║",
+            "  ╚═════════════════╝",
+            "    A B C D E F G H",
+        ]);
+        let board = get_board();
+        assert_eq!(Renderer::new().render(&board), expected);
+        assert_eq!(board.to_string(), expected);
+    }
+
+    #[test]
+    fn flipped() {
+        let board = get_board();
+        assert_eq!(Renderer::flipped().render(&board), get_lines(&[
+            "  ╔═════════════════╗  ",
+            "1 ║ R # B K Q B N R ║",
+            "2 ║ P P P   P P P P ║",
+            "3 ║   # N #   #   # ║",
+            "4 ║ #   # P #   #   ║",
+            "5 ║   #   p   #   # ║",
+            "6 ║ #   #   # n #   ║",
+            "7 ║ p p p # p p p p ║",
+            "8 ║ r n b k q b # r ║",
+            "  ╚═════════════════╝",
+            "    H G F E D C B A",
+        ]));
+
+        // highlights at the edges of the board, and next to each other
+        let renderer = Renderer { highlights: vec![0x00, 0x10, 0x77], ..Renderer::flipped() };
+        let lines: Vec<String> = renderer.render(&board).lines().map(String::from).collect();
+        assert_eq!((lines[1].as_str(), lines[8].as_str()), ("1 ║ R # B K Q B[N[R]║", "8 ║[r]n b k q b # r ║"));
+    }
+
+    #[test]
+    fn options() {
+        let board = get_board();
+        let renderer = Renderer {
+            unicode: true,
+            coordinates: false,
+            highlights: vec![0x41, 0x43],
+            panel: true,
+            light_square: '.',
+            dark_square: ':',
+            ..Renderer::new()
+        };
+        assert_eq!(renderer.render(&board), get_lines(&[
+            "╔═════════════════╗",
+            "║ ♜ : ♝ ♛ ♚ ♝ ♞ ♜ ║   White to move",
+            "║ ♟ ♟ ♟ ♟ : ♟ ♟ ♟ ║   Castling: KQkq",
+            "║ . : ♞ : . : . : ║   En passant: -",
+            "║ : . : . ♟ . : . ║   Halfmove clock: 2",
+            "║ . : . :[♙]: . : ║   Move: 3",
+            "║ : . : . : ♘ : . ║",
+            &format!("║ ♙ ♙ ♙ ♙[.]♙ ♙ ♙ ║   {FEN}"),
+            "║ ♖ ♘ ♗ ♕ ♔ ♗ : ♖ ║",
+            "╚═════════════════╝",
+        ]));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{board::{Board, Color, Move, Square}, pieces::{Piece, Pieces::{self, *}}, variant::Variant};
+
+// standard algebraic notation as used in PGN: e4, Nbd7, exd5, O-O, e8=Q+
+
+fn get_letter(r#type: Pieces) -> char {
+    Piece::from(r#type, Color::White).get_char()
+}
+
+impl Board {
+    pub fn get_san(&self, r#move: Move) -> String {
+        let (start_x, start_y) = Square::u8_to_tuple(r#move.from);
+        let (x, y) = Square::u8_to_tuple(r#move.to);
+        // crazyhouse drops, pawns go without a letter: N@f3, @e4
+        if let Some(r#type) = r#move.drop {
+            let letter = if r#type == Pawn { String::new() } else { get_letter(r#type).to_string() };
+            return format!("{letter}@{}{}", Square::u8_to_string(r#move.to), self.get_check_suffix(r#move));
+        }
+
+        let Some(piece) = self.get_index(start_x, start_y).get_piece() else { return r#move.to_string() };
+
+        let mut san = String::new();
+        if self.is_castling(r#move) {
+            san.push_str(if x > start_x { "O-O" } else { "O-O-O" });
+        } else {
+            let capture = self.is_capture(r#move);
+            if piece.r#type == Pawn {
+                if capture {
+                    san.push((b'a' + start_x) as char);
+                }
+            } else {
+                san.push(get_letter(piece.r#type));
+
+                // only name the start file or rank when another piece of the same type could go there too
+                let others: Vec<Move> = self.get_legal_moves().into_iter()
+                    .filter(|other| other.to == r#move.to && other.from != r#move.from)
+                    .filter(|other| {
+                        let (other_x, other_y) = Square::u8_to_tuple(other.from);
+                        self.get_index(other_x, other_y).get_piece() == Some(piece)
+                    })
+                    .collect();
+
+                if !others.is_empty() {
+                    let same_file = others.iter().any(|other| Square::u8_to_tuple(other.from).0 == start_x);
+                    let same_rank = others.iter().any(|other| Square::u8_to_tuple(other.from).1 == start_y);
+                    if !same_file {
+                        san.push((b'a' + start_x) as char);
+                    } else if !same_rank {
+                        san.push((b'1' + start_y) as char);
+                    } else {
+                        san.push_str(&Square::u8_to_string(r#move.from));
+                    }
+                }
+            }
+
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&Square::u8_to_string(r#move.to));
+
+            if piece.r#type == Pawn && (y == 0 || y == 7) {
+                san.push('=');
+                san.push(get_letter(r#move.promotion.unwrap_or(Queen)));
+            }
+        }
+
+        san.push_str(self.get_check_suffix(r#move));
+        san
+    }
+
+    fn get_check_suffix(&self, r#move: Move) -> &'static str {
+        let mut board = *self;
+        board.apply_move(r#move);
+        match board.in_check(board.get_turn()) {
+            // three-check ends on the third check, that isn't mate
+            true if board.get_variant_outcome().is_none() && board.get_legal_moves().is_empty() => "#",
+            true => "+",
+            false => "",
+        }
+    }
+
+    // accepts some sloppiness that shows up in real PGN files: missing x or =, check marks,
+    // annotations like ! and ?, 0-0 for castling and more disambiguation than needed
+    pub fn parse_san(&self, san: &str) -> Result<Move, &'static str> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let legal = self.get_legal_moves();
+
+        if matches!(san, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+            let r#move = self.get_castling_move(self.get_turn(), san.len() == 3).ok_or("Illegal castling")?;
+            return if legal.contains(&r#move) { Ok(r#move) } else { Err("Illegal castling") };
+        }
+
+        if !san.is_ascii() || san.len() < 2 {
+            return Err("Invalid SAN move");
+        }
+
+        if let Some((letter, square)) = san.split_once('@') {
+            let r#type = match letter {
+                "" | "P" => Pawn,
+                _ => Piece::from_char(letter.chars().next().unwrap()).filter(|_| letter.len() == 1).ok_or("Invalid drop piece")?.r#type,
+            };
+            let r#move = Move::new_drop(r#type, Square::parse_u8(square).ok_or("Invalid target square")?);
+            return if legal.contains(&r#move) { Ok(r#move) } else { Err("Illegal drop") };
+        }
+
+        let (r#type, rest) = match san.chars().next() {
+            Some(c @ ('N' | 'B' | 'R' | 'Q' | 'K')) => (Piece::from_char(c).unwrap().r#type, &san[1..]),
+            _ => (Pawn, san),
+        };
+
+        // promotion at the end: e8=Q or e8Q, antichess pawns can become kings too
+        let (rest, promotion) = match rest.chars().last() {
+            Some(c @ ('N' | 'B' | 'R' | 'Q' | 'K')) if r#type == Pawn && (c != 'K' || self.get_variant() == Variant::Antichess) => {
+                (rest[..rest.len() - 1].trim_end_matches('='), Some(Piece::from_char(c).unwrap().r#type))
+            },
+            _ => (rest, None),
+        };
+
+        let rest = rest.replace(['x', '-', ':'], "");
+        if rest.len() < 2 {
+            return Err("Invalid SAN move");
+        }
+        let to = Square::parse_u8(&rest[rest.len() - 2..]).ok_or("Invalid target square")?;
+
+        // whatever is left before the target square narrows down the start square
+        let mut from_x = None;
+        let mut from_y = None;
+        for c in rest[..rest.len() - 2].chars() {
+            match c {
+                'a'..='h' => from_x = Some(c as u8 - b'a'),
+                '1'..='8' => from_y = Some(c as u8 - b'1'),
+                _ => return Err("Invalid SAN move"),
+            }
+        }
+
+        let mut candidates = legal.into_iter().filter(|r#move| {
+            let (x, y) = Square::u8_to_tuple(r#move.from);
+            r#move.to == to
+                && from_x.is_none_or(|from_x| from_x == x)
+                && from_y.is_none_or(|from_y| from_y == y)
+                && self.get_index(x, y).get_piece().is_some_and(|piece| piece.r#type == r#type)
+                && (r#move.promotion.is_none() || r#move.promotion == Some(promotion.unwrap_or(Queen)))
+        });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(r#move), None) => Ok(r#move),
+            (None, _) => Err("Illegal move"),
+            (Some(_), Some(_)) => Err("Ambiguous move"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_board(variant: Variant, fen: &str) -> Board {
+        let mut board = Board::new();
+        board.set_variant(variant);
+        board.init(fen).unwrap();
+        board
+    }
+
+    // every legal move reads back from its own SAN
+    fn assert_round_trip(board: &Board) {
+        for r#move in board.get_legal_moves() {
+            let san = board.get_san(r#move);
+            assert_eq!(board.parse_san(&san), Ok(r#move), "{san}");
+        }
+    }
+
+    #[test]
+    fn disambiguation() {
+        let cases = [
+            ("rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R b KQkq - 1 3", "b8d7", "Nbd7"),
+            ("rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R b KQkq - 1 3", "f6d7", "Nfd7"),
+            ("7k/8/8/8/8/4R3/8/K3R3 w - - 0 1", "e1e2", "R1e2"),
+            ("7k/8/8/8/8/4R3/8/K3R3 w - - 0 1", "e3e2", "R3e2"),
+            ("1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1", "h4e1", "Qh4e1"),
+            ("1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1", "h1e1", "Q1e1"),
+            ("1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1", "e4e1", "Qee1"),
+            // a pinned knight doesn't count as another one that could go there
+            ("4k3/8/8/8/1b6/8/3N4/4K1N1 w - - 0 1", "g1f3", "Nf3"),
+        ];
+        for (fen, uci, san) in cases {
+            let board = get_board(Variant::Standard, fen);
+            let r#move = Move::from_uci(uci).unwrap();
+            assert_eq!(board.get_san(r#move), san);
+            assert_eq!(board.parse_san(san), Ok(r#move));
+            assert_round_trip(&board);
+        }
+
+        let board = get_board(Variant::Standard, "1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1");
+        assert_eq!(board.parse_san("Qe1"), Err("Ambiguous move"));
+        assert_eq!(board.parse_san("Qh1e1"), Ok(Move::from_uci("h1e1").unwrap()));
+    }
+
+    #[test]
+    fn notation() {
+        let board = get_board(Variant::Standard, "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        for (uci, san) in [("e1g1", "O-O"), ("e1c1", "O-O-O"), ("e5d6", "exd6"), ("b7a8n", "bxa8=N"), ("b7b8q", "b8=Q+"), ("a1a8", "Rxa8+")] {
+            let r#move = Move::from_uci(uci).unwrap();
+            assert_eq!(board.get_san(r#move), san);
+            assert_eq!(board.parse_san(san), Ok(r#move));
+        }
+        assert_round_trip(&board);
+
+        // the sloppy forms seen in real files
+        for (san, uci) in [("0-0", "e1g1"), ("ed6", "e5d6"), ("bxa8N", "b7a8n"), ("b8Q", "b7b8q"), ("Ra1xa8!?", "a1a8"), ("Ra1-a8", "a1a8")] {
+            assert_eq!(board.parse_san(san), Ok(Move::from_uci(uci).unwrap()), "{san}");
+        }
+        assert_eq!(board.parse_san("Rb1b8"), Err("Illegal move"));
+        assert!(board.parse_san("Zz9").is_err());
+
+        let board = get_board(Variant::Standard, "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+        assert_eq!(board.get_san(Move::from_uci("d1d8").unwrap()), "Rd8#");
+    }
+
+    #[test]
+    fn antichess_king_promotions() {
+        let board = get_board(Variant::Antichess, "8/8/8/8/8/8/p7/1R6 b - - 0 1");
+        assert_eq!(board.parse_san("axb1=K"), Ok(Move::from_uci("a2b1k").unwrap()));
+        assert_round_trip(&board);
+
+        let board = get_board(Variant::Standard, "6k1/8/8/8/8/8/p7/1R4K1 b - - 0 1");
+        assert!(board.parse_san("axb1=K").is_err());
+        assert_round_trip(&board);
+    }
+}
@@ -0,0 +1,567 @@
+use std::{
+    sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+use crate::{
+    board::{Board, Move},
+    eval::{self, Weights},
+    movepick::{Heuristics, MovePicker},
+    nnue::{Evaluator, Network},
+    pieces::Pieces::*,
+    syzygy::{Tablebase, Wdl},
+    timeman::{Signals, TimeManager},
+    tt::{Bound, TranspositionTable},
+    variant::Outcome,
+};
+
+// negamax alpha-beta search with iterative deepening
+
+pub const INFINITY: i32 = 32000;
+pub const MATE: i32 = 31000; // mate in n plies scores MATE - n
+pub const MAX_PLY: u8 = 64;
+pub const DEFAULT_HASH_MB: usize = 16;
+const ONE_SHOT_HASH_MB: usize = 1; // for search, which throws its table away afterwards
+
+// tablebase wins rank below any mate the search finds itself
+pub const TB_WIN: i32 = MATE - 2 * MAX_PLY as i32;
+
+// captures that can't bring the score back up to alpha even if they win the piece outright are skipped
+const DELTA_MARGIN: i32 = 200;
+
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub depth: Option<u8>, // defaults to MAX_PLY
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>, // remaining clock time, see TimeManager for how it is spent
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>, // moves until the next time control
+    pub infinite: bool, // search until stopped through signals
+    pub ponder: bool, // search infinitely until ponderhit, then use the clock
+    pub signals: Option<Signals>,
+    pub searchmoves: Vec<Move>, // only search these at the root, all legal moves when empty
+    pub tablebase: Option<Arc<Tablebase>>, // probed once few enough pieces are left
+    pub threads: usize, // the Threads option, helper threads share the table with the calling thread
+    pub network: Option<Arc<Network>>, // evaluate with this instead of the handcrafted evaluation
+}
+
+impl Limits {
+    pub fn depth(depth: u8) -> Self {
+        Limits { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        Limits { movetime: Some(movetime), ..Default::default() }
+    }
+
+    // remaining time and increment for both sides
+    pub fn clock(time: Duration, increment: Duration) -> Self {
+        Limits { wtime: Some(time), btime: Some(time), winc: Some(increment), binc: Some(increment), ..Default::default() }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>, // None if there are no legal moves
+    pub score: i32, // centipawns from the side to move's point of view
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    pub depth: u8, // deepest completed iteration
+    pub nps: u64, // nodes per second over all threads
+}
+
+impl SearchResult {
+    // plies until mate if the score is a forced mate, negative if the side to move is getting mated
+    pub fn mate_in_plies(&self) -> Option<i32> {
+        match self.score {
+            score if score >= MATE - MAX_PLY as i32 => Some(MATE - score),
+            score if score <= -MATE + MAX_PLY as i32 => Some(-MATE - score),
+            _ => None,
+        }
+    }
+}
+
+// a one-off search with a small table of its own, use search_with_table to keep a bigger one between calls
+pub fn search(board: &Board, limits: &Limits) -> SearchResult {
+    search_with_table(board, limits, &mut TranspositionTable::new(ONE_SHOT_HASH_MB))
+}
+
+// reuse a table across searches, so analysis of related positions can build on earlier results
+pub fn search_with_table(board: &Board, limits: &Limits, tt: &mut TranspositionTable) -> SearchResult {
+    tt.new_search();
+    let tt = &*tt;
+    let shared = Shared::default();
+    let start = Instant::now();
+
+    // lazy smp, the helpers search the same root and only help by filling the shared table,
+    // the calling thread decides when to stop and its result is the one that counts
+    let mut result = thread::scope(|scope| {
+        let helpers: Vec<_> = (1..limits.threads.max(1)).map(|id| {
+            let limits = Limits {
+                depth: limits.depth,
+                searchmoves: limits.searchmoves.clone(),
+                tablebase: limits.tablebase.clone(),
+                network: limits.network.clone(),
+                ..Default::default()
+            };
+            let shared = &shared;
+            scope.spawn(move || Searcher::new(limits, tt, board, shared, id).run(board).nodes)
+        }).collect();
+
+        let result = Searcher::new(limits.clone(), tt, board, &shared, 0).run(board);
+        shared.stop.store(true, Ordering::Relaxed);
+        let helper_nodes: u64 = helpers.into_iter().map(|helper| helper.join().unwrap_or(0)).sum();
+        SearchResult { nodes: result.nodes + helper_nodes, ..result }
+    });
+
+    result.nps = (result.nodes as u128 * 1_000_000 / start.elapsed().as_micros().max(1)) as u64;
+    result
+}
+
+// between the threads of a parallel search
+#[derive(Default)]
+struct Shared {
+    stop: AtomicBool, // the main thread is done, so are the helpers
+    helper_nodes: AtomicU64, // added to every so often, for the node limit
+}
+
+struct Searcher<'a> {
+    id: usize, // 0 for the main thread
+    limits: Limits,
+    time: TimeManager,
+    tt: &'a TranspositionTable,
+    shared: &'a Shared,
+    history: Vec<u64>, // hashes of the positions leading to the current node, for repetitions
+    path: Vec<Move>, // moves leading to the current node, for counter moves
+    heuristics: Heuristics,
+    nodes: u64,
+    stopped: bool,
+    previous_pv: Vec<Move>, // from the last completed iteration
+    weights: Weights,
+    nnue: Option<Evaluator>,
+}
+
+impl<'a> Searcher<'a> {
+    fn new(limits: Limits, tt: &'a TranspositionTable, board: &Board, shared: &'a Shared, id: usize) -> Self {
+        Searcher {
+            id,
+            time: TimeManager::new(&limits, board.get_turn()),
+            nnue: limits.network.clone().map(|network| Evaluator::new(network, board)),
+            limits,
+            tt,
+            shared,
+            history: Vec::new(),
+            path: Vec::new(),
+            heuristics: Heuristics::new(),
+            nodes: 0,
+            stopped: false,
+            previous_pv: Vec::new(),
+            weights: Weights::default(),
+        }
+    }
+
+    fn run(&mut self, board: &Board) -> SearchResult {
+        let mut result = SearchResult::default();
+        let max_depth = self.limits.depth.unwrap_or(MAX_PLY).clamp(1, MAX_PLY);
+
+        // the tablebase already knows which moves keep the best result, the search only has to pick one
+        if self.limits.searchmoves.is_empty() {
+            if let Some(tablebase) = self.limits.tablebase.as_ref().filter(|tablebase| tablebase.can_probe(board)) {
+                self.limits.searchmoves = tablebase.filter_root_moves(board).unwrap_or_default();
+            }
+        }
+
+        // odd helpers stay a ply ahead of the others, so the threads don't all search the same tree
+        let first_depth = (1 + (self.id % 2) as u8).min(max_depth);
+        for depth in first_depth..=max_depth {
+            let mut pv = Vec::new();
+            let score = self.negamax(board, depth, 0, -INFINITY, INFINITY, &mut pv);
+
+            // a partial iteration can't be trusted, keep the last complete one
+            if self.stopped {
+                break;
+            }
+
+            result = SearchResult {
+                best_move: pv.first().copied(),
+                score,
+                pv: pv.clone(),
+                nodes: self.nodes,
+                depth,
+                nps: 0,
+            };
+            self.previous_pv = pv;
+
+            // no point searching deeper without moves, or once a forced mate has been found within the horizon
+            if result.best_move.is_none() || result.mate_in_plies().is_some_and(|plies| plies.unsigned_abs() <= depth as u32) {
+                break;
+            }
+
+            if !self.time.should_start_iteration() {
+                break;
+            }
+        }
+
+        self.time.wait_for_stop();
+        result.nodes = self.nodes;
+        result
+    }
+
+    fn should_stop(&mut self) -> bool {
+        // helpers run until the main thread is done
+        if self.id > 0 {
+            if self.nodes.is_multiple_of(1024) {
+                self.shared.helper_nodes.fetch_add(1024, Ordering::Relaxed);
+            }
+            self.stopped = self.shared.stop.load(Ordering::Relaxed);
+            return self.stopped;
+        }
+
+        // never stop before the first iteration has found a move to play
+        if self.previous_pv.is_empty() {
+            return false;
+        }
+
+        if self.limits.nodes.is_some_and(|nodes| self.nodes + self.shared.helper_nodes.load(Ordering::Relaxed) >= nodes) {
+            self.stopped = true;
+        }
+
+        // checking the clock is comparatively slow, so only do it every so often
+        if self.nodes.is_multiple_of(1024) && self.time.should_abort() {
+            self.stopped = true;
+        }
+
+        self.stopped
+    }
+
+    fn negamax(&mut self, board: &Board, depth: u8, ply: u8, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        let hash = board.get_hash();
+        if ply > 0 && (board.get_halfmove() >= 100 || self.is_repetition(board, hash)) {
+            return 0;
+        }
+
+        if let Some(outcome) = board.get_variant_outcome() {
+            return get_outcome_score(outcome, board, ply);
+        }
+
+        // right after a capture or pawn move the result can be looked up, the 50 move rule can't get in the way
+        if ply > 0 && board.get_halfmove() == 0 {
+            if let Some(wdl) = self.probe_wdl(board) {
+                return match wdl {
+                    Wdl::Win => TB_WIN - ply as i32,
+                    Wdl::Loss => -TB_WIN + ply as i32,
+                    _ => 0,
+                };
+            }
+        }
+
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let entry = self.tt.probe(hash, ply);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            match entry.bound {
+                Bound::Exact => {
+                    // the rest of the line was searched before, possibly by another thread
+                    *pv = self.get_table_pv(board, entry.depth);
+                    return entry.score;
+                },
+                Bound::Lower if entry.score >= beta => return entry.score,
+                Bound::Upper if entry.score <= alpha => return entry.score,
+                _ => (),
+            }
+        }
+
+        if ply >= MAX_PLY {
+            return self.evaluate(board);
+        }
+
+        let previous = self.path.last().copied();
+        let mut picker = MovePicker::new(board, entry.and_then(|entry| entry.best_move), &self.heuristics, ply, previous);
+
+        let original_alpha = alpha;
+        let mut best_move = None;
+        let mut searched = 0;
+        let mut quiets_tried = Vec::new();
+        self.history.push(hash);
+        while let Some(r#move) = picker.next(board, &self.heuristics) {
+            if ply == 0 && !self.limits.searchmoves.is_empty() && !self.limits.searchmoves.contains(&r#move) {
+                continue;
+            }
+            searched += 1;
+            let quiet = !board.is_capture(r#move) && !board.is_promotion(r#move);
+
+            let mut child = *board;
+            child.apply_move(r#move);
+
+            let mut child_pv = Vec::new();
+            self.path.push(r#move);
+            self.make_move(board, &child);
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            self.unmake_move();
+            self.path.pop();
+            if self.stopped {
+                self.history.pop();
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                best_move = Some(r#move);
+                pv.clear();
+                pv.push(r#move);
+                pv.extend(child_pv);
+
+                if alpha >= beta {
+                    if quiet {
+                        self.heuristics.update_cutoff(board.get_turn(), ply, depth, previous, r#move, &quiets_tried);
+                    }
+                    break;
+                }
+            }
+
+            if quiet {
+                quiets_tried.push(r#move);
+            }
+        }
+        self.history.pop();
+
+        if searched == 0 {
+            return get_outcome_score(board.get_outcome_without_moves(), board, ply);
+        }
+
+        let bound = match alpha {
+            alpha if alpha >= beta => Bound::Lower,
+            alpha if alpha > original_alpha => Bound::Exact,
+            _ => Bound::Upper,
+        };
+        self.tt.store(hash, best_move, alpha, depth, bound, ply);
+
+        alpha
+    }
+
+    // follows the best moves stored in the table, for lines that were cut short by a table hit
+    fn get_table_pv(&self, board: &Board, depth: u8) -> Vec<Move> {
+        let mut board = *board;
+        let mut pv = Vec::new();
+        for _ in 0..depth {
+            let Some(r#move) = self.tt.probe(board.get_hash(), 0).and_then(|entry| entry.best_move).filter(|&r#move| board.is_legal(r#move)) else { break };
+            board.apply_move(r#move);
+            pv.push(r#move);
+        }
+        pv
+    }
+
+    // only search captures at the horizon, so the score isn't taken in the middle of an exchange
+    fn quiescence(&mut self, board: &Board, ply: u8, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        if let Some(outcome) = board.get_variant_outcome() {
+            return get_outcome_score(outcome, board, ply);
+        }
+
+        // in check every evasion has to be looked at, standing pat isn't an option
+        let in_check = board.in_check(board.get_turn());
+        let stand_pat = self.evaluate(board);
+        if ply >= MAX_PLY {
+            return stand_pat;
+        }
+
+        if !in_check {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+
+            // even winning a queen wouldn't be enough
+            if stand_pat + Queen.get_value() + DELTA_MARGIN < alpha {
+                return alpha;
+            }
+
+            alpha = alpha.max(stand_pat);
+        }
+
+        let mut picker = if in_check {
+            MovePicker::new(board, None, &self.heuristics, ply, None)
+        } else {
+            MovePicker::new_captures()
+        };
+
+        let mut searched = 0;
+        while let Some(r#move) = picker.next(board, &self.heuristics) {
+            searched += 1;
+            if !in_check && r#move.promotion.is_none() {
+                let (x, y) = (r#move.to >> 4, r#move.to & 0x0F);
+                let victim = board.get_index(x, y).get_piece().map_or(Pawn.get_value(), |piece| piece.r#type.get_value());
+                if stand_pat + victim + DELTA_MARGIN < alpha {
+                    continue;
+                }
+            }
+
+            let mut child = *board;
+            child.apply_move(r#move);
+            self.make_move(board, &child);
+            let score = -self.quiescence(&child, ply + 1, -beta, -alpha);
+            self.unmake_move();
+            if self.stopped {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        if in_check && searched == 0 {
+            return -MATE + ply as i32;
+        }
+
+        alpha
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        match &self.nnue {
+            Some(nnue) => nnue.evaluate(board),
+            None => eval::evaluate(board, &self.weights),
+        }
+    }
+
+    // keeps the network's accumulators in step with the search
+    fn make_move(&mut self, board: &Board, child: &Board) {
+        if let Some(nnue) = &mut self.nnue {
+            nnue.push(board, child);
+        }
+    }
+
+    fn unmake_move(&mut self) {
+        if let Some(nnue) = &mut self.nnue {
+            nnue.pop();
+        }
+    }
+
+    fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        let tablebase = self.limits.tablebase.as_ref().filter(|tablebase| tablebase.can_probe(board))?;
+        tablebase.probe_wdl(board).ok()
+    }
+
+    // a position seen before since the last capture or pawn move is scored as a draw,
+    // only positions with the same side to move can match so step back two plies at a time
+    fn is_repetition(&self, board: &Board, hash: u64) -> bool {
+        self.history.iter().rev()
+            .take(board.get_halfmove() as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|&previous| previous == hash)
+    }
+}
+
+// finished games from the side to move's point of view, wins that come sooner score higher
+fn get_outcome_score(outcome: Outcome, board: &Board, ply: u8) -> i32 {
+    match outcome {
+        Outcome::Win(color) if color == board.get_turn() => MATE - ply as i32,
+        Outcome::Win(_) => -MATE + ply as i32,
+        Outcome::Draw => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::Color::*, variant::Outcome};
+
+    fn get_board(fen: &str) -> Board {
+        let mut board = Board::new();
+        board.init(fen).unwrap();
+        board
+    }
+
+    // plays the principal variation and checks that it ends in mate for the side that started
+    fn assert_mates(board: &Board, result: &SearchResult) {
+        let mut board = *board;
+        let winner = board.get_turn();
+        for &r#move in &result.pv {
+            board.play(r#move).unwrap();
+        }
+        assert_eq!(board.get_outcome(), Some(Outcome::Win(winner)));
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let board = get_board("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+        let result = search(&board, &Limits::depth(3));
+        assert_eq!(result.best_move, Move::from_uci("d1d8").ok());
+        assert_eq!(result.mate_in_plies(), Some(1));
+        assert_mates(&board, &result);
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        // 1. Nf6+ gxf6 2. Bxf7#
+        let board = get_board("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1");
+        let result = search(&board, &Limits::depth(5));
+        assert_eq!(result.best_move, Move::from_uci("d5f6").ok());
+        assert_eq!(result.mate_in_plies(), Some(3));
+        assert_mates(&board, &result);
+    }
+
+    #[test]
+    fn sees_mate_coming() {
+        // Kg8 is the only move, then Rb8#
+        let board = get_board("7k/R7/1R6/8/8/8/8/6K1 b - - 0 1");
+        let result = search(&board, &Limits::depth(4));
+        assert_eq!(result.best_move, Move::from_uci("h8g8").ok());
+        assert_eq!(result.mate_in_plies(), Some(-2));
+
+        let board = get_board("3R2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1");
+        let result = search(&board, &Limits::depth(3));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -MATE);
+        assert!(board.in_check(Black));
+    }
+
+    #[test]
+    fn shares_table_between_searches() {
+        let board = get_board("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1");
+        let mut tt = TranspositionTable::new(1);
+        let first = search_with_table(&board, &Limits::depth(5), &mut tt);
+        assert!(tt.hashfull() > 0);
+        let second = search_with_table(&board, &Limits::depth(5), &mut tt);
+        assert_eq!(second.best_move, first.best_move);
+        assert!(second.nodes < first.nodes);
+    }
+
+    #[test]
+    fn lazy_smp() {
+        let board = get_board("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1");
+        for _ in 0..3 {
+            let result = search(&board, &Limits { threads: 4, ..Limits::depth(5) });
+            assert_eq!(result.best_move, Move::from_uci("d5f6").ok());
+            assert_eq!(result.mate_in_plies(), Some(3));
+            assert_mates(&board, &result);
+        }
+
+        let board = get_board("7k/R7/1R6/8/8/8/8/6K1 b - - 0 1");
+        let result = search(&board, &Limits { threads: 4, ..Limits::depth(4) });
+        assert_eq!(result.best_move, Move::from_uci("h8g8").ok());
+        assert_eq!(result.mate_in_plies(), Some(-2));
+
+        // the helpers have no limits of their own and stop with the main thread
+        let start = Instant::now();
+        let result = search(&get_board(""), &Limits { threads: 4, ..Limits::movetime(Duration::from_millis(100)) });
+        assert!(result.best_move.is_some() && start.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{board::{Board, Color, Move, Square}, pieces::{Piece, Pieces::{self, *}}};
+
+// static exchange evaluation, plays out every capture on the target square with the least
+// valuable attacker first and returns the material won or lost by the side making the move
+
+// the king has no material value, but should only ever be the last piece to capture
+const KING_VALUE: i32 = 20000;
+
+fn get_see_value(piece: Piece) -> i32 {
+    if piece.r#type == King { KING_VALUE } else { piece.r#type.get_value() }
+}
+
+impl Board {
+    // centipawns won by playing the move if both sides keep recapturing on its square,
+    // negative if the move hangs material
+    pub fn see(&self, r#move: Move) -> i32 {
+        let (start_x, start_y) = Square::u8_to_tuple(r#move.from);
+        let (x, y) = Square::u8_to_tuple(r#move.to);
+        let Some(mut piece) = self.get_index(start_x, start_y).get_piece() else { return 0 };
+
+        let mut board = *self;
+        let mut gain = vec![board.get_index(x, y).get_piece().map_or(0, get_see_value)];
+
+        if piece.r#type == Pawn {
+            // en passant, the captured pawn isn't on the target square
+            if start_x != x && board.get_index(x, y).get_piece().is_none() {
+                board.get_index_mut(x, start_y).remove_piece();
+                gain[0] = Pawn.get_value();
+            }
+
+            if let Some(promotion) = r#move.promotion.or(if y == 0 || y == 7 { Some(Queen) } else { None }) {
+                piece.r#type = promotion;
+                gain[0] += promotion.get_value() - Pawn.get_value();
+            }
+        }
+
+        board.get_index_mut(start_x, start_y).remove_piece();
+        board.get_index_mut(x, y).set_piece(piece);
+
+        let mut side = !piece.color;
+        let mut on_square = get_see_value(piece);
+
+        // removing each capturing piece from the board uncovers any slider behind it
+        while let Some((attacker_x, attacker_y, attacker)) = board.get_least_valuable_attacker(r#move.to, side) {
+            gain.push(on_square - gain[gain.len() - 1]);
+
+            board.get_index_mut(attacker_x, attacker_y).remove_piece();
+            board.get_index_mut(x, y).set_piece(attacker);
+            on_square = get_see_value(attacker);
+            side = !side;
+        }
+
+        // each side can choose to stop capturing, so fold back from the end of the sequence
+        while gain.len() > 1 {
+            let last = gain.pop().unwrap();
+            let previous = gain.len() - 1;
+            gain[previous] = -(-gain[previous]).max(last);
+        }
+
+        gain[0]
+    }
+
+    // every square holding a piece of the given color that attacks coord, same search as is_attacked
+    pub fn get_attackers(&self, coord: u8, by: Color) -> Vec<u8> {
+        let (x, y) = Square::u8_to_tuple(coord);
+        let mut attackers = Vec::new();
+        let mut check = |dx: i8, dy: i8, types: &[Pieces]| -> bool { // returns whether the square was empty
+            let (tx, ty) = (x as i8 + dx, y as i8 + dy);
+            if !(0..8).contains(&tx) || !(0..8).contains(&ty) {
+                return false;
+            }
+
+            match self.get_index(tx as u8, ty as u8).get_piece() {
+                Some(piece) => {
+                    if piece.color == by && types.contains(&piece.r#type) {
+                        attackers.push(((tx as u8) << 4) + ty as u8);
+                    }
+                    false
+                },
+                None => true,
+            }
+        };
+
+        for (dx, dy) in Piece::OFFSETS_KNIGHT {
+            check(dx, dy, &[Knight]);
+        }
+
+        // pawns attack forwards, so look backwards from the attacking side's point of view
+        let behind = if by == Color::White { -1 } else { 1 };
+        for dx in [-1, 1] {
+            check(dx, behind, &[Pawn]);
+        }
+
+        for (dx, dy) in Piece::OFFSETS_KING {
+            let sliders: &[Pieces] = if dx != 0 && dy != 0 { &[Bishop, Queen] } else { &[Rook, Queen] };
+            if !check(dx, dy, &[King, sliders[0], sliders[1]]) {
+                continue;
+            }
+
+            let mut i = 2;
+            while check(dx * i, dy * i, sliders) {
+                i += 1;
+            }
+        }
+
+        attackers
+    }
+
+    fn get_least_valuable_attacker(&self, coord: u8, by: Color) -> Option<(u8, u8, Piece)> {
+        self.get_attackers(coord, by).into_iter()
+            .map(|square| {
+                let (x, y) = Square::u8_to_tuple(square);
+                (x, y, self.get_index(x, y).get_piece().unwrap())
+            })
+            .min_by_key(|(_, _, piece)| get_see_value(*piece))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see(fen: &str, uci: &str) -> i32 {
+        let mut board = Board::new();
+        board.set_fen(fen).unwrap();
+        board.see(Move::from_uci(uci).unwrap())
+    }
+
+    #[test]
+    fn losing_captures() {
+        // the pawn is defended by a pawn
+        assert_eq!(see("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), 100 - 900);
+        // the king can only take back if nothing else defends the square
+        assert_eq!(see("8/8/4k3/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), 100 - 900);
+        assert_eq!(see("8/8/4k3/3p4/8/8/3R4/3QK3 w - - 0 1", "d2d5"), 100);
+        // moving to an attacked square without capturing
+        assert_eq!(see("4k3/8/2p5/8/8/8/8/3QK3 w - - 0 1", "d1d5"), -900);
+    }
+
+    #[test]
+    fn winning_exchanges() {
+        assert_eq!(see("4k3/8/2p5/3r4/8/4N3/8/4K3 w - - 0 1", "e3d5"), 500 - 320);
+        assert_eq!(see("4k3/8/8/3r4/8/4N3/8/4K3 w - - 0 1", "e3d5"), 500);
+        // knight takes a knight defended once and attacked twice
+        assert_eq!(see("4k3/4r3/8/3n4/8/2N1N3/8/4K3 w - - 0 1", "c3d5"), 320);
+    }
+
+    #[test]
+    fn x_rays() {
+        // the rook behind the first one takes back
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", "d2d5"), 100 - 500);
+        // and so does the queen behind the defending rook
+        assert_eq!(see("3qk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100 - 500 + 500 - 500);
+        // a bishop behind a pawn
+        assert_eq!(see("4k3/8/4r3/8/2P5/1B6/8/4K3 w - - 0 1", "c4c5"), 0);
+    }
+
+    #[test]
+    fn en_passant() {
+        assert_eq!(see("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1", "d5e6"), 100);
+        assert_eq!(see("4k3/5p2/8/3Pp3/8/8/8/4K3 w - e6 0 1", "d5e6"), 0);
+    }
+
+    #[test]
+    fn promotions() {
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"), 500 + 800);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), 800 - 900);
+    }
+}
@@ -0,0 +1,321 @@
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+use crate::{board::{Board, Move, Square}, pieces::Piece, variant::Variant};
+
+// serde support, behind the serde feature. Color, Pieces and Piece derive it, boards are written
+// as their FEN and moves in UCI notation, use the structured module for a board spelled out field
+// by field
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.get_fen())
+    }
+}
+
+// a FEN only tells crazyhouse (the pocket) and three-check (the checks field) apart from standard
+// chess, other variants and the chess960 flag only survive in the structured form
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        if fen.trim().is_empty() {
+            return Err(de::Error::custom("FEN is empty"));
+        }
+
+        let mut board = Board::new();
+        let placement = fen.split_whitespace().next().unwrap_or("");
+        if placement.contains('[') || placement.split('/').count() == 9 {
+            board.set_variant(Variant::Crazyhouse);
+        } else if fen.split_whitespace().skip(1).any(|field| field.contains('+')) {
+            board.set_variant(Variant::ThreeCheck);
+        }
+        board.init(&fen).map_err(de::Error::custom)?;
+        Ok(board)
+    }
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let notation = String::deserialize(deserializer)?;
+        Move::from_uci(&notation).map_err(de::Error::custom)
+    }
+}
+
+// as {"square": "e4", "piece": {"type": "Pawn", "color": "White"}}, piece is null when empty
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Square", 2)?;
+        state.serialize_field("square", &Square::u8_to_string(self.get_coords_u8()))?;
+        state.serialize_field("piece", &self.get_piece())?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Square")]
+        struct Fields {
+            square: String,
+            piece: Option<Piece>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        let (x, y) = Square::u8_to_tuple(Square::parse_u8(&fields.square).ok_or_else(|| de::Error::custom("Invalid square"))?);
+        let mut square = Square::from(x, y);
+        if let Some(piece) = fields.piece {
+            square.set_piece(piece);
+        }
+        Ok(square)
+    }
+}
+
+// a board as its separate fields instead of a FEN, for #[serde(with = "rschess::serialization::structured")]
+pub mod structured {
+    use std::collections::BTreeMap;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use crate::{board::{Board, Color::{self, *}, Square}, pieces::{Piece, Pieces::{self, *}}, variant::Variant};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Board")]
+    struct Fields {
+        variant: String, // as Variant::get_name
+        chess960: bool,
+        pieces: BTreeMap<String, Piece>, // by square name, a1 to h8
+        turn: Color,
+        castling: String, // as in the FEN, KQkq or the rook files
+        en_passant: Option<String>, // the square behind the pawn that can be taken
+        halfmove: u16,
+        fullmove: u16,
+        pockets: PerColor<Vec<Pieces>>, // crazyhouse, pieces in hand
+        promoted: Vec<String>, // crazyhouse, squares of pieces that were pawns
+        checks: PerColor<u8>, // three-check, checks given
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct PerColor<T> {
+        white: T,
+        black: T,
+    }
+
+    pub fn serialize<S: Serializer>(board: &Board, serializer: S) -> Result<S::Ok, S::Error> {
+        let fen = board.get_fen();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let pieces = board.get_state().iter()
+            .filter_map(|square| Some((Square::u8_to_string(square.get_coords_u8()), square.get_piece()?)))
+            .collect();
+
+        // the promoted pieces are only marked in the FEN, with a ~ after the piece
+        let mut promoted = Vec::new();
+        for (i, rank) in fields[0].split('[').next().unwrap_or("").split('/').enumerate() {
+            let mut x = 0;
+            for c in rank.chars() {
+                match c {
+                    '~' => promoted.push(Square::u8_to_string(((x - 1) << 4) + 7 - i as u8)),
+                    '1'..='8' => x += c as u8 - b'0',
+                    _ => x += 1,
+                }
+            }
+        }
+
+        let pocket = |color: Color| {
+            let counts = board.get_pocket(color);
+            [Pawn, Knight, Bishop, Rook, Queen].into_iter().flat_map(|r#type| std::iter::repeat_n(r#type, counts[r#type as usize] as usize)).collect()
+        };
+        Fields {
+            variant: board.get_variant().get_name().to_string(),
+            chess960: board.is_chess960(),
+            pieces,
+            turn: board.get_turn(),
+            castling: fields[2].to_string(),
+            en_passant: (fields[3] != "-").then(|| fields[3].to_string()),
+            halfmove: board.get_halfmove(),
+            fullmove: board.get_fullmove(),
+            pockets: PerColor { white: pocket(White), black: pocket(Black) },
+            promoted,
+            checks: PerColor { white: board.get_checks(White), black: board.get_checks(Black) },
+        }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        let fields = Fields::deserialize(deserializer)?;
+        let variant = Variant::from_name(&fields.variant).ok_or_else(|| de::Error::custom("Unknown variant"))?;
+
+        if fields.pieces.keys().chain(&fields.promoted).any(|name| Square::parse_u8(name).map(Square::u8_to_string).as_ref() != Some(name)) {
+            return Err(de::Error::custom("Invalid square"));
+        }
+
+        // back to a FEN, so the board checks it the same way
+        let mut placement = String::new();
+        for y in (0..8).rev() {
+            let mut empty = 0;
+            for x in 0..8 {
+                let name = Square::u8_to_string((x << 4) + y);
+                let Some(piece) = fields.pieces.get(&name) else {
+                    empty += 1;
+                    continue;
+                };
+                if empty > 0 {
+                    placement.push((b'0' + empty) as char);
+                    empty = 0;
+                }
+                placement.push(piece.get_char());
+                if fields.promoted.contains(&name) {
+                    placement.push('~');
+                }
+            }
+            if empty > 0 {
+                placement.push((b'0' + empty) as char);
+            }
+            if y > 0 {
+                placement.push('/');
+            }
+        }
+
+        if variant == Variant::Crazyhouse {
+            placement.push('[');
+            for (color, pocket) in [(White, &fields.pockets.white), (Black, &fields.pockets.black)] {
+                placement.extend(pocket.iter().map(|&r#type| Piece::from(r#type, color).get_char()));
+            }
+            placement.push(']');
+        }
+
+        let turn = if fields.turn == White { "w" } else { "b" };
+        let castling = if fields.castling.is_empty() { "-" } else { &fields.castling };
+        let en_passant = fields.en_passant.as_deref().unwrap_or("-");
+        let fen = format!(
+            "{placement} {turn} {castling} {en_passant} {} {} +{}+{}",
+            fields.halfmove, fields.fullmove, fields.checks.white, fields.checks.black,
+        );
+
+        let mut board = Board::new();
+        board.set_variant(variant);
+        board.set_chess960(fields.chess960);
+        board.init(&fen).map_err(de::Error::custom)?;
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use crate::{board::{Board, Color::*, Move, Square}, pieces::{Piece, Pieces::*}, variant::Variant};
+
+    #[derive(Serialize, Deserialize)]
+    struct Structured(#[serde(with = "super::structured")] Board);
+
+    fn get_board(variant: Variant, fen: &str) -> Board {
+        let mut board = Board::new();
+        board.set_variant(variant);
+        board.init(fen).unwrap();
+        board
+    }
+
+    fn assert_same(board: &Board, other: &Board) {
+        assert_eq!(
+            (other.get_fen(), other.get_variant(), other.is_chess960(), other.get_hash()),
+            (board.get_fen(), board.get_variant(), board.is_chess960(), board.get_hash()),
+        );
+    }
+
+    #[test]
+    fn boards_as_fen() {
+        for (variant, fen) in [
+            (Variant::Standard, "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"),
+            (Variant::Crazyhouse, "r1bqk2r/pppp1ppp/2n5/4p3/1b2P3/2N5/PPPP1PPP/R1BQKB~1R[NPnp] w KQkq - 0 6"),
+            (Variant::ThreeCheck, "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 2+3 0 2"),
+        ] {
+            let board = get_board(variant, fen);
+            let json = serde_json::to_string(&board).unwrap();
+            assert_eq!(json, format!("\"{fen}\""));
+            assert_same(&board, &serde_json::from_str(&json).unwrap());
+        }
+
+        // the pocket as a ninth rank is crazyhouse too
+        let board: Board = serde_json::from_str("\"4k3/8/8/8/8/8/8/4K3/Qp w - - 0 1\"").unwrap();
+        assert_eq!((board.get_variant(), board.get_fen()), (Variant::Crazyhouse, "4k3/8/8/8/8/8/8/4K3[Qp] w - - 0 1".to_string()));
+
+        for json in ["\"\"", "\"  \"", "\"4k3/8/8 w - - 0 1\"", "42"] {
+            assert!(serde_json::from_str::<Board>(json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn structured_boards() {
+        let mut chess960 = Board::new();
+        chess960.init_960(0).unwrap();
+        chess960.play(Move::from_uci("e2e4").unwrap()).unwrap();
+        for board in [
+            get_board(Variant::Standard, "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"),
+            get_board(Variant::Crazyhouse, "r1bqk2r/pppp1ppp/2n5/4p3/1b2P3/2N5/PPPP1PPP/R1BQKB~1R[NPnp] w KQkq - 0 6"),
+            get_board(Variant::ThreeCheck, "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 2+1 0 2"),
+            get_board(Variant::Atomic, ""),
+            get_board(Variant::Antichess, "8/8/8/8/8/8/6k1/4K3 b - - 3 40"),
+            get_board(Variant::Horde, ""),
+            chess960,
+        ] {
+            let json = serde_json::to_string(&Structured(board)).unwrap();
+            let Structured(other) = serde_json::from_str(&json).unwrap();
+            assert_same(&board, &other);
+        }
+
+        let board = get_board(Variant::Crazyhouse, "4k3/8/8/8/8/8/8/Q~3K3[Pn] b - - 1 30");
+        let json: serde_json::Value = serde_json::to_value(Structured(board)).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "variant": "crazyhouse",
+            "chess960": false,
+            "pieces": {
+                "a1": {"type": "Queen", "color": "White"},
+                "e1": {"type": "King", "color": "White"},
+                "e8": {"type": "King", "color": "Black"},
+            },
+            "turn": "Black",
+            "castling": "-",
+            "en_passant": null,
+            "halfmove": 1,
+            "fullmove": 30,
+            "pockets": {"white": ["Pawn"], "black": ["Knight"]},
+            "promoted": ["a1"],
+            "checks": {"white": 0, "black": 0},
+        }));
+
+        // squares have to be named the way the board names them
+        let mut bad = json.clone();
+        bad["pieces"]["A1"] = bad["pieces"]["a1"].take();
+        assert!(serde_json::from_value::<Structured>(bad).is_err());
+        let mut bad = json.clone();
+        bad["variant"] = "shogi".into();
+        assert!(serde_json::from_value::<Structured>(bad).is_err());
+        let mut bad = json;
+        bad["pieces"]["e1"] = serde_json::Value::Null;
+        assert!(serde_json::from_value::<Structured>(bad).is_err());
+    }
+
+    #[test]
+    fn moves_squares_and_pieces() {
+        for uci in ["e2e4", "e7e8q", "a2a1n", "e1g1", "N@f3", "P@e5"] {
+            let r#move = Move::from_uci(uci).unwrap();
+            assert_eq!(serde_json::to_string(&r#move).unwrap(), format!("\"{uci}\""));
+            assert_eq!(serde_json::from_str::<Move>(&format!("\"{uci}\"")).unwrap(), r#move);
+        }
+        assert!(serde_json::from_str::<Move>("\"e2e9\"").is_err());
+
+        let mut square = Square::from(4, 3);
+        assert_eq!(serde_json::to_string(&square).unwrap(), r#"{"square":"e4","piece":null}"#);
+        square.set_piece(Piece::from(Pawn, White));
+        let json = serde_json::to_string(&square).unwrap();
+        assert_eq!(json, r#"{"square":"e4","piece":{"type":"Pawn","color":"White"}}"#);
+        let read: Square = serde_json::from_str(&json).unwrap();
+        assert_eq!((read.get_coords_u8(), read.get_piece()), (0x43, Some(Piece::from(Pawn, White))));
+        assert!(serde_json::from_str::<Square>(r#"{"square":"z9","piece":null}"#).is_err());
+
+        for piece in [Piece::from(Knight, Black), Piece::from(King, White)] {
+            assert_eq!(serde_json::from_str::<Piece>(&serde_json::to_string(&piece).unwrap()).unwrap(), piece);
+        }
+        assert!(serde_json::from_str::<Piece>(r#"{"type":"Archbishop","color":"White"}"#).is_err());
+    }
+}
@@ -0,0 +1,646 @@
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashMap},
+    hash::{BuildHasher, Hasher},
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{mpsc::{self, Sender}, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use crate::{
+    board::{Board, Color::{self, *}, Move},
+    clock::{Clock, TimeControl},
+    game::{Game, Player},
+    variant::Variant,
+};
+
+// hosts games over TCP for clients on the same machine or network, one line of text per message.
+// The server holds the only real board, so whatever clients send goes through the same legality
+// checks as Board::r#move and a client can't get an illegal move into the game.
+//
+// client to server:
+//     name <name>                                 what other players see
+//     list                                        one game line per game
+//     create [white|black|random] [300+2] [variant]
+//     join <id>                                   take the free seat
+//     watch <id>                                  spectate
+//     move <uci>                                  e2e4, e7e8q, N@f3
+//     resign | draw | decline | abort | leave
+//
+// server to client:
+//     created <id> <color>, joined <id> <color>, watching <id>
+//     game <id> <white> <black> <waiting|playing|over> <variant> <time control>
+//     board <id> <fen>                            after every change
+//     moved <id> <uci> <san>
+//     clock <id> <white ms> <black ms>
+//     draw <id> <color>                           offered by color
+//     result <id> <1-0|0-1|1/2-1/2|*> <termination>
+//     error <message>
+
+type ClientId = u64;
+
+const MAX_LINE: u64 = 4096; // longer lines end the connection
+
+struct Room {
+    game: Game,
+    control: Option<TimeControl>, // the clock only starts once both seats are taken
+    seats: [Option<ClientId>; 2], // by Color
+    spectators: Vec<ClientId>,
+}
+
+impl Room {
+    fn get_status(&self) -> &'static str {
+        if self.game.is_over() {
+            "over"
+        } else if self.game.get_clock().is_some() || self.seats.iter().all(Option::is_some) {
+            "playing"
+        } else {
+            "waiting"
+        }
+    }
+
+    fn get_members(&self) -> impl Iterator<Item = ClientId> + '_ {
+        self.seats.iter().flatten().chain(&self.spectators).copied()
+    }
+
+    // nobody is left and nobody can pick it up where it was, the game is over or never started
+    fn is_abandoned(&self) -> bool {
+        self.get_members().next().is_none() && (self.game.is_over() || self.game.get_moves().is_empty())
+    }
+}
+
+#[derive(Default)]
+struct Client {
+    sender: Option<Sender<String>>,
+    name: String,
+    room: Option<u32>,
+}
+
+// every game and client, behind one lock
+#[derive(Default)]
+pub struct Lobby {
+    rooms: BTreeMap<u32, Room>,
+    clients: HashMap<ClientId, Client>,
+    next_room: u32,
+    next_client: ClientId,
+}
+
+impl Lobby {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // a lobby for several connection threads to share, flags are checked on a thread of their own
+    pub fn shared() -> Arc<Mutex<Self>> {
+        let lobby = Arc::new(Mutex::new(Self::new()));
+        let ticker = Arc::clone(&lobby);
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(100));
+            ticker.lock().unwrap().check_flags();
+        });
+        lobby
+    }
+
+    // messages for the client go to the sender, returns the id to handle its lines with
+    pub fn connect(&mut self, sender: Sender<String>) -> ClientId {
+        self.next_client += 1;
+        let id = self.next_client;
+        self.clients.insert(id, Client { sender: Some(sender), name: format!("guest{id}"), room: None });
+        id
+    }
+
+    pub fn disconnect(&mut self, client: ClientId) {
+        self.leave(client);
+        self.clients.remove(&client);
+    }
+
+    fn send(&self, client: ClientId, message: String) {
+        if let Some(sender) = self.clients.get(&client).and_then(|client| client.sender.as_ref()) {
+            let _ = sender.send(message);
+        }
+    }
+
+    fn broadcast(&self, room: u32, message: String) {
+        let Some(members) = self.rooms.get(&room).map(|room| room.get_members().collect::<Vec<_>>()) else { return };
+        for member in members {
+            self.send(member, message.clone());
+        }
+    }
+
+    pub fn handle(&mut self, client: ClientId, line: &str) {
+        let line = line.trim();
+        let (command, args) = line.split_once(char::is_whitespace).map_or((line, ""), |(command, args)| (command, args.trim()));
+        let result = match command {
+            "" => Ok(()),
+            "name" => self.set_name(client, args),
+            "list" => {
+                for id in self.rooms.keys() {
+                    self.send(client, self.get_game_line(*id));
+                }
+                Ok(())
+            },
+            "create" => self.create(client, args),
+            "join" => args.parse().map_err(|_| "Invalid game id").and_then(|id| self.join(client, id)),
+            "watch" => args.parse().map_err(|_| "Invalid game id").and_then(|id| self.watch(client, id)),
+            "move" => self.play(client, args),
+            "resign" | "draw" | "decline" | "abort" => self.act(client, command),
+            "leave" => {
+                self.leave(client);
+                Ok(())
+            },
+            _ => Err("Unknown command"),
+        };
+
+        if let Err(message) = result {
+            self.send(client, format!("error {message}"));
+        }
+    }
+
+    fn set_name(&mut self, client: ClientId, name: &str) -> Result<(), &'static str> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err("Names must be a single word");
+        }
+        self.clients.get_mut(&client).ok_or("Unknown client")?.name = name.into();
+        Ok(())
+    }
+
+    fn get_game_line(&self, id: u32) -> String {
+        let Some(room) = self.rooms.get(&id) else { return format!("error No game {id}") };
+        let name = |player: &Player| if player.name.is_empty() { "-".to_string() } else { player.name.clone() };
+        let control = room.control.as_ref().map_or("-".into(), ToString::to_string);
+        let variant = room.game.get_board().get_variant().get_name();
+        format!("game {id} {} {} {} {variant} {control}", name(&room.game.white), name(&room.game.black), room.get_status())
+    }
+
+    // the options come in any order: a color, a PGN time control and a variant name
+    fn create(&mut self, client: ClientId, args: &str) -> Result<(), &'static str> {
+        let mut color = White;
+        let mut control = None;
+        let mut variant = Variant::Standard;
+        for arg in args.split_whitespace() {
+            match arg {
+                "white" => color = White,
+                "black" => color = Black,
+                "random" => color = if RandomState::new().build_hasher().finish() & 1 == 0 { White } else { Black },
+                _ => match Variant::from_name(arg) {
+                    Some(found) => variant = found,
+                    None => control = Some(TimeControl::parse(arg).map_err(|_| "Invalid option, expected a color, time control or variant")?),
+                },
+            }
+        }
+
+        self.leave(client);
+        let mut board = Board::new();
+        board.set_variant(variant);
+        board.init("")?;
+        self.next_room += 1;
+        let id = self.next_room;
+        self.rooms.insert(id, Room { game: Game::from_board(board), control, seats: [None, None], spectators: Vec::new() });
+        self.send(client, format!("created {id} {color}"));
+        self.seat(client, id, color);
+        Ok(())
+    }
+
+    fn join(&mut self, client: ClientId, id: u32) -> Result<(), &'static str> {
+        let room = self.rooms.get(&id).ok_or("No such game")?;
+        if room.game.is_over() {
+            return Err("Game is over");
+        }
+        let color = [White, Black].into_iter().find(|&color| room.seats[color as usize].is_none()).ok_or("Game is full")?;
+
+        self.leave(client);
+        self.send(client, format!("joined {id} {color}"));
+        self.seat(client, id, color);
+        Ok(())
+    }
+
+    fn seat(&mut self, client: ClientId, id: u32, color: Color) {
+        let name = self.clients.get(&client).map_or(String::new(), |client| client.name.clone());
+        if let Some(client) = self.clients.get_mut(&client) {
+            client.room = Some(id);
+        }
+        let Some(room) = self.rooms.get_mut(&id) else { return };
+        room.seats[color as usize] = Some(client);
+        *match color {
+            White => &mut room.game.white,
+            Black => &mut room.game.black,
+        } = Player::new(&name);
+
+        // the clock starts with the game, when the second player sits down
+        if room.seats.iter().all(Option::is_some) && room.game.get_clock().is_none() {
+            if let Some(control) = room.control.clone() {
+                room.game.set_clock(Clock::new(control));
+            }
+        }
+
+        self.broadcast(id, self.get_game_line(id));
+        self.send_state(id);
+    }
+
+    fn watch(&mut self, client: ClientId, id: u32) -> Result<(), &'static str> {
+        if !self.rooms.contains_key(&id) {
+            return Err("No such game");
+        }
+        self.leave(client);
+        self.rooms.get_mut(&id).unwrap().spectators.push(client);
+        if let Some(client) = self.clients.get_mut(&client) {
+            client.room = Some(id);
+        }
+        self.send(client, format!("watching {id}"));
+        self.send(client, self.get_game_line(id));
+        for line in self.get_state(id) {
+            self.send(client, line);
+        }
+        Ok(())
+    }
+
+    // a player's seat is freed so someone else can take it, the game itself carries on
+    fn leave(&mut self, client: ClientId) {
+        let Some(id) = self.clients.get_mut(&client).and_then(|client| client.room.take()) else { return };
+        let Some(room) = self.rooms.get_mut(&id) else { return };
+        room.spectators.retain(|&spectator| spectator != client);
+        for seat in &mut room.seats {
+            if *seat == Some(client) {
+                *seat = None;
+            }
+        }
+        self.broadcast(id, self.get_game_line(id));
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        self.rooms.retain(|_, room| !room.is_abandoned());
+    }
+
+    // the room the client plays in, and which side it plays
+    fn get_seat(&self, client: ClientId) -> Result<(u32, Color), &'static str> {
+        let id = self.clients.get(&client).and_then(|client| client.room).ok_or("Not in a game")?;
+        let room = self.rooms.get(&id).ok_or("Not in a game")?;
+        let color = [White, Black].into_iter().find(|&color| room.seats[color as usize] == Some(client)).ok_or("Spectators can't play")?;
+        Ok((id, color))
+    }
+
+    fn play(&mut self, client: ClientId, notation: &str) -> Result<(), &'static str> {
+        let (id, color) = self.get_seat(client)?;
+        let room = self.rooms.get_mut(&id).unwrap();
+        if room.seats.iter().any(Option::is_none) {
+            return Err("Waiting for an opponent");
+        }
+        if room.game.get_board().get_turn() != color {
+            return Err("Not your turn");
+        }
+
+        let r#move = Move::from_uci(notation)?;
+        let before = *room.game.get_board();
+        let was_over = room.game.is_over();
+        let played = room.game.play(r#move);
+        // a move made after the flag fell still ends the game
+        let over = !was_over && room.game.is_over();
+        if played.is_ok() {
+            self.broadcast(id, format!("moved {id} {move} {}", before.get_san(r#move)));
+        }
+        if played.is_ok() || over {
+            self.send_state(id);
+        }
+        played
+    }
+
+    fn act(&mut self, client: ClientId, action: &str) -> Result<(), &'static str> {
+        let (id, color) = self.get_seat(client)?;
+        let game = &mut self.rooms.get_mut(&id).unwrap().game;
+        match action {
+            "resign" => game.resign(color)?,
+            "draw" if game.get_claimable_draw().is_some() => game.claim_draw()?,
+            "draw" => game.offer_draw(color)?,
+            "decline" => game.decline_draw(color)?,
+            _ => game.abort()?,
+        }
+
+        if let Some(offer) = game.get_draw_offer() {
+            self.broadcast(id, format!("draw {id} {offer}"));
+        }
+        self.send_state(id);
+        Ok(())
+    }
+
+    // the position and clocks, and the result once there is one
+    fn get_state(&self, id: u32) -> Vec<String> {
+        let Some(room) = self.rooms.get(&id) else { return Vec::new() };
+        let mut state = vec![format!("board {id} {}", room.game.get_board().get_fen())];
+        if let Some(clock) = room.game.get_clock() {
+            let millis = |color: Color| clock.get_remaining(color).as_millis();
+            state.push(format!("clock {id} {} {}", millis(White), millis(Black)));
+        }
+        if let Some(termination) = room.game.get_termination() {
+            state.push(format!("result {id} {} {termination}", room.game.get_result_token()));
+        }
+        state
+    }
+
+    fn send_state(&self, id: u32) {
+        for line in self.get_state(id) {
+            self.broadcast(id, line);
+        }
+    }
+
+    // flags fall whether or not anyone moves
+    pub fn check_flags(&mut self) {
+        let flagged: Vec<u32> = self.rooms.iter_mut()
+            .filter(|(_, room)| !room.game.is_over())
+            .filter_map(|(&id, room)| room.game.check_flag().then_some(id))
+            .collect();
+        for id in flagged {
+            self.send_state(id);
+        }
+        self.prune();
+    }
+}
+
+// accept clients until the listener fails, every client gets a thread for reading and one for writing
+pub fn serve(address: impl ToSocketAddrs) -> Result<(), &'static str> {
+    let listener = TcpListener::bind(address).map_err(|_| "Could not listen on that address")?;
+    let lobby = Lobby::shared();
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let lobby = Arc::clone(&lobby);
+        thread::spawn(move || handle_connection(stream, lobby));
+    }
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, lobby: Arc<Mutex<Lobby>>) {
+    let Ok(mut writer) = stream.try_clone() else { return };
+    let (sender, receiver) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for message in receiver {
+            if writeln!(writer, "{message}").is_err() {
+                break;
+            }
+        }
+    });
+
+    let client = lobby.lock().unwrap().connect(sender);
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match Read::take(&mut reader, MAX_LINE).read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(length) if length as u64 == MAX_LINE && !line.ends_with('\n') => {
+                lobby.lock().unwrap().send(client, "error Line too long".into());
+                break;
+            },
+            Ok(_) => (),
+        }
+        if line.trim() == "quit" {
+            break;
+        }
+        lobby.lock().unwrap().handle(client, &line);
+    }
+    lobby.lock().unwrap().disconnect(client);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+    use crate::clock::ManualTime;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn connect(lobby: &mut Lobby) -> (ClientId, Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (lobby.connect(sender), receiver)
+    }
+
+    // everything sent to the client since the last call
+    fn get_messages(receiver: &Receiver<String>) -> Vec<String> {
+        receiver.try_iter().collect()
+    }
+
+    // alice plays White and bob Black in game 1, with the messages so far thrown away
+    fn start_game(lobby: &mut Lobby) -> [(ClientId, Receiver<String>); 2] {
+        let (alice, alice_messages) = connect(lobby);
+        let (bob, bob_messages) = connect(lobby);
+        lobby.handle(alice, "name alice");
+        lobby.handle(bob, "name bob");
+        lobby.handle(alice, "create white");
+        lobby.handle(bob, "join 1");
+        get_messages(&alice_messages);
+        get_messages(&bob_messages);
+        [(alice, alice_messages), (bob, bob_messages)]
+    }
+
+    #[test]
+    fn create_and_join() {
+        let mut lobby = Lobby::new();
+        let (alice, alice_messages) = connect(&mut lobby);
+        let (bob, bob_messages) = connect(&mut lobby);
+        lobby.handle(alice, "name alice");
+        lobby.handle(alice, "create black 300+2");
+        assert_eq!(get_messages(&alice_messages), [
+            "created 1 Black".to_string(),
+            "game 1 - alice waiting chess 300+2".to_string(),
+            format!("board 1 {START}"),
+        ]);
+
+        lobby.handle(bob, "list");
+        assert_eq!(get_messages(&bob_messages), ["game 1 - alice waiting chess 300+2"]);
+        lobby.handle(bob, "join 1");
+        let messages = get_messages(&bob_messages);
+        assert_eq!(messages[..3], [
+            "joined 1 White".to_string(),
+            "game 1 guest2 alice playing chess 300+2".to_string(),
+            format!("board 1 {START}"),
+        ]);
+        assert!(messages[3].starts_with("clock 1 "));
+        assert_eq!(get_messages(&alice_messages)[0], "game 1 guest2 alice playing chess 300+2");
+
+        let (carol, carol_messages) = connect(&mut lobby);
+        lobby.handle(carol, "join 1");
+        lobby.handle(carol, "join 2");
+        lobby.handle(carol, "create purple");
+        lobby.handle(carol, "fly");
+        assert_eq!(get_messages(&carol_messages), [
+            "error Game is full",
+            "error No such game",
+            "error Invalid option, expected a color, time control or variant",
+            "error Unknown command",
+        ]);
+    }
+
+    #[test]
+    fn plays_in_turn() {
+        let mut lobby = Lobby::new();
+        let [(alice, alice_messages), (bob, bob_messages)] = start_game(&mut lobby);
+
+        lobby.handle(bob, "move e7e5");
+        assert_eq!(get_messages(&bob_messages), ["error Not your turn"]);
+        lobby.handle(alice, "move e2e5");
+        lobby.handle(alice, "move e2");
+        assert_eq!(get_messages(&alice_messages).len(), 2);
+        assert!(get_messages(&bob_messages).is_empty());
+        assert_eq!(lobby.rooms[&1].game.get_board().get_fen(), START);
+
+        lobby.handle(alice, "move e2e4");
+        let expected = ["moved 1 e2e4 e4", "board 1 rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"];
+        assert_eq!(get_messages(&alice_messages), expected);
+        assert_eq!(get_messages(&bob_messages), expected);
+        lobby.handle(alice, "move d2d4");
+        assert_eq!(get_messages(&alice_messages), ["error Not your turn"]);
+        lobby.handle(bob, "move e7e5");
+        assert_eq!(get_messages(&alice_messages)[0], "moved 1 e7e5 e5");
+    }
+
+    #[test]
+    fn waits_for_an_opponent() {
+        let mut lobby = Lobby::new();
+        let (alice, alice_messages) = connect(&mut lobby);
+        lobby.handle(alice, "move e2e4");
+        lobby.handle(alice, "create white");
+        lobby.handle(alice, "move e2e4");
+        assert_eq!(get_messages(&alice_messages)[0], "error Not in a game");
+        lobby.handle(alice, "move e2e4");
+        assert_eq!(get_messages(&alice_messages), ["error Waiting for an opponent"]);
+    }
+
+    #[test]
+    fn spectators() {
+        let mut lobby = Lobby::new();
+        let [(alice, _), (bob, _)] = start_game(&mut lobby);
+        lobby.handle(alice, "move e2e4");
+
+        let (carol, carol_messages) = connect(&mut lobby);
+        lobby.handle(carol, "watch 1");
+        assert_eq!(get_messages(&carol_messages), [
+            "watching 1",
+            "game 1 alice bob playing chess -",
+            "board 1 rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        ]);
+        lobby.handle(carol, "move e7e5");
+        lobby.handle(carol, "resign");
+        assert_eq!(get_messages(&carol_messages), ["error Spectators can't play", "error Spectators can't play"]);
+
+        lobby.handle(bob, "move e7e5");
+        assert_eq!(get_messages(&carol_messages)[0], "moved 1 e7e5 e5");
+        lobby.handle(carol, "leave");
+        lobby.handle(alice, "move g1f3");
+        assert!(get_messages(&carol_messages).is_empty());
+    }
+
+    #[test]
+    fn resign() {
+        let mut lobby = Lobby::new();
+        let [(alice, alice_messages), (bob, bob_messages)] = start_game(&mut lobby);
+        lobby.handle(bob, "resign");
+        assert_eq!(get_messages(&alice_messages), [format!("board 1 {START}"), "result 1 1-0 resignation".to_string()]);
+        assert_eq!(get_messages(&bob_messages).len(), 2);
+        lobby.handle(alice, "move e2e4");
+        lobby.handle(alice, "resign");
+        assert_eq!(get_messages(&alice_messages), ["error Game is over", "error Game is over"]);
+    }
+
+    #[test]
+    fn draw() {
+        let mut lobby = Lobby::new();
+        let [(alice, alice_messages), (bob, bob_messages)] = start_game(&mut lobby);
+        lobby.handle(alice, "draw");
+        assert_eq!(get_messages(&bob_messages)[0], "draw 1 White");
+        lobby.handle(bob, "decline");
+        lobby.handle(bob, "decline");
+        assert_eq!(get_messages(&bob_messages).last().unwrap(), "error No draw offer to decline");
+
+        lobby.handle(bob, "draw");
+        get_messages(&alice_messages);
+        lobby.handle(alice, "draw");
+        assert_eq!(get_messages(&alice_messages).last().unwrap(), "result 1 1/2-1/2 agreement");
+    }
+
+    #[test]
+    fn abort() {
+        let mut lobby = Lobby::new();
+        let [(alice, alice_messages), (bob, _)] = start_game(&mut lobby);
+        lobby.handle(alice, "move e2e4");
+        lobby.handle(bob, "abort");
+        assert_eq!(get_messages(&alice_messages).last().unwrap(), "result 1 * aborted");
+
+        let mut lobby = Lobby::new();
+        let [(alice, alice_messages), (bob, _)] = start_game(&mut lobby);
+        lobby.handle(alice, "move e2e4");
+        lobby.handle(bob, "move e7e5");
+        get_messages(&alice_messages);
+        lobby.handle(alice, "abort");
+        assert_eq!(get_messages(&alice_messages), ["error Game can only be aborted before both sides have moved"]);
+    }
+
+    #[test]
+    fn flag_falls() {
+        let mut lobby = Lobby::new();
+        let [(alice, alice_messages), (_, bob_messages)] = start_game(&mut lobby);
+        let time = Arc::new(ManualTime::new());
+        let control = TimeControl::parse("60").unwrap();
+        lobby.rooms.get_mut(&1).unwrap().game.set_clock(Clock::with_source(control, time.clone()));
+
+        time.advance(Duration::from_secs(59));
+        lobby.check_flags();
+        assert!(get_messages(&bob_messages).is_empty());
+        time.advance(Duration::from_secs(2));
+        lobby.check_flags();
+        let expected = [format!("board 1 {START}"), "clock 1 0 60000".to_string(), "result 1 0-1 timeout".to_string()];
+        assert_eq!(get_messages(&alice_messages), expected);
+        assert_eq!(get_messages(&bob_messages), expected);
+
+        // only once
+        lobby.check_flags();
+        lobby.handle(alice, "move e2e4");
+        assert_eq!(get_messages(&alice_messages), ["error Game is over"]);
+    }
+
+    #[test]
+    fn prunes_abandoned_rooms() {
+        let mut lobby = Lobby::new();
+        let (alice, _alice) = connect(&mut lobby);
+        let (bob, _bob) = connect(&mut lobby);
+
+        // a game nobody joined goes when its creator leaves
+        lobby.handle(alice, "create white");
+        lobby.handle(alice, "leave");
+        assert!(lobby.rooms.is_empty());
+
+        // a running game stays for whoever comes back, a finished one goes with its last player
+        lobby.handle(alice, "create white");
+        lobby.handle(bob, "join 2");
+        lobby.handle(alice, "move e2e4");
+        lobby.handle(alice, "leave");
+        lobby.handle(bob, "leave");
+        assert!(lobby.rooms.contains_key(&2));
+        lobby.handle(alice, "join 2");
+        lobby.handle(alice, "resign");
+        assert!(lobby.rooms.contains_key(&2));
+        lobby.disconnect(alice);
+        assert!(lobby.rooms.is_empty());
+    }
+
+    #[test]
+    fn limits_line_length() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || handle_connection(listener.accept().unwrap().0, Arc::new(Mutex::new(Lobby::new()))));
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+
+        writeln!(stream, "nonsense").unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "error Unknown command\n");
+
+        // without a newline the connection is dropped once the limit is reached
+        let _ = stream.write_all(&[b'x'; MAX_LINE as usize]);
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "error Line too long\n");
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+    }
+}
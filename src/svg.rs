@@ -0,0 +1,374 @@
+use crate::{
+    board::{Board, Color::{self, *}, Square},
+    pieces::{Piece, Pieces::{self, *}},
+};
+
+// board diagrams as SVG, with a piece set of its own so nothing depends on the fonts installed
+
+// the pieces are drawn in a 45 by 45 box
+pub(crate) const PIECE_SIZE: f32 = 45.0;
+pub(crate) const PIECE_STROKE: f32 = 1.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Shape {
+    Polygon(Vec<(f32, f32)>), // filled and outlined
+    Circle(f32, f32, f32), // center and radius, filled and outlined
+    Line((f32, f32), (f32, f32)), // a detail, in the opposite color of the piece
+}
+
+// in drawing order, later shapes go on top
+pub(crate) fn get_shapes(r#type: Pieces) -> Vec<Shape> {
+    use Shape::*;
+    let base = Polygon(vec![(9.0, 39.0), (36.0, 39.0), (36.0, 36.0), (9.0, 36.0)]);
+    match r#type {
+        Pawn => vec![
+            base,
+            Polygon(vec![(14.0, 36.0), (15.0, 31.0), (18.0, 26.0), (20.0, 22.0), (25.0, 22.0), (27.0, 26.0), (30.0, 31.0), (31.0, 36.0)]),
+            Circle(22.5, 15.0, 5.5),
+        ],
+        Rook => vec![
+            base,
+            Polygon(vec![(12.0, 36.0), (33.0, 36.0), (31.0, 32.0), (14.0, 32.0)]),
+            Polygon(vec![(15.0, 32.0), (30.0, 32.0), (30.0, 17.0), (15.0, 17.0)]),
+            Polygon(vec![
+                (12.0, 17.0), (33.0, 17.0), (33.0, 9.0), (29.0, 9.0), (29.0, 12.0), (25.0, 12.0),
+                (25.0, 9.0), (20.0, 9.0), (20.0, 12.0), (16.0, 12.0), (16.0, 9.0), (12.0, 9.0),
+            ]),
+        ],
+        Knight => vec![
+            base,
+            Polygon(vec![
+                (13.0, 36.0), (32.0, 36.0), (32.0, 27.0), (31.0, 20.0), (28.0, 14.0), (24.0, 10.0), (21.0, 9.0),
+                (20.0, 6.0), (18.0, 10.0), (15.0, 13.0), (11.0, 19.0), (10.0, 23.0), (12.0, 25.0), (15.0, 23.0),
+                (18.0, 22.0), (20.0, 21.0), (16.0, 27.0), (13.0, 31.0),
+            ]),
+            Line((17.0, 15.0), (18.5, 15.0)),
+            Line((27.0, 16.0), (29.0, 28.0)),
+        ],
+        Bishop => vec![
+            base,
+            Polygon(vec![(14.0, 36.0), (31.0, 36.0), (29.0, 30.0), (16.0, 30.0)]),
+            Polygon(vec![(16.0, 30.0), (29.0, 30.0), (27.5, 27.0), (17.5, 27.0)]),
+            Polygon(vec![(22.5, 8.0), (27.0, 12.0), (30.0, 17.0), (30.0, 22.0), (27.5, 27.0), (17.5, 27.0), (15.0, 22.0), (15.0, 17.0), (18.0, 12.0)]),
+            Circle(22.5, 6.0, 2.5),
+            Line((20.0, 15.0), (25.0, 20.0)),
+        ],
+        Queen => vec![
+            base,
+            Polygon(vec![
+                (12.0, 36.0), (9.0, 15.0), (15.0, 25.0), (16.0, 11.0), (20.0, 24.0), (22.5, 9.0),
+                (25.0, 24.0), (29.0, 11.0), (30.0, 25.0), (36.0, 15.0), (33.0, 36.0),
+            ]),
+            Circle(9.0, 15.0, 2.0),
+            Circle(16.0, 11.0, 2.0),
+            Circle(22.5, 9.0, 2.0),
+            Circle(29.0, 11.0, 2.0),
+            Circle(36.0, 15.0, 2.0),
+            Line((12.5, 31.0), (32.5, 31.0)),
+        ],
+        King => vec![
+            base,
+            Polygon(vec![
+                (12.0, 36.0), (10.0, 27.0), (12.0, 22.0), (16.0, 20.0), (20.0, 21.0), (22.5, 24.0),
+                (25.0, 21.0), (29.0, 20.0), (33.0, 22.0), (35.0, 27.0), (33.0, 36.0),
+            ]),
+            Polygon(vec![
+                (21.5, 5.0), (23.5, 5.0), (23.5, 8.0), (26.5, 8.0), (26.5, 10.0), (23.5, 10.0),
+                (23.5, 20.0), (21.5, 20.0), (21.5, 10.0), (18.5, 10.0), (18.5, 8.0), (21.5, 8.0),
+            ]),
+            Line((12.0, 31.0), (33.0, 31.0)),
+        ],
+    }
+}
+
+// fill and details, the outline is always black
+pub(crate) fn get_piece_colors(color: Color) -> (&'static str, &'static str) {
+    match color {
+        White => ("#ffffff", "#000000"),
+        Black => ("#000000", "#ffffff"),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Arrow {
+    pub from: u8,
+    pub to: u8,
+    pub color: String,
+}
+
+impl Arrow {
+    pub fn new(from: u8, to: u8, color: &str) -> Self {
+        Arrow { from, to, color: color.into() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SvgRenderer {
+    pub size: u32, // width and height in pixels
+    pub light: String, // any SVG color
+    pub dark: String,
+    pub orientation: Color, // the side at the bottom
+    pub coordinates: bool, // file letters along the bottom and rank numbers along the left, inside the squares
+    pub highlights: Vec<(u8, String)>, // squares with a color laid over them half transparent
+    pub arrows: Vec<Arrow>,
+}
+
+impl Default for SvgRenderer {
+    fn default() -> Self {
+        SvgRenderer {
+            size: 400,
+            light: "#f0d9b5".into(),
+            dark: "#b58863".into(),
+            orientation: White,
+            coordinates: true,
+            highlights: Vec::new(),
+            arrows: Vec::new(),
+        }
+    }
+}
+
+impl SvgRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn render(&self, board: &Board) -> String {
+        let square = self.size as f32 / 8.0;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" version=\"1.1\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">\n",
+            self.size
+        );
+
+        // every piece on the board is defined once and used wherever it stands
+        let mut pieces: Vec<Piece> = board.get_state().iter().filter_map(|square| square.get_piece()).collect();
+        pieces.sort_by_key(|piece| (piece.color as u8, piece.r#type));
+        pieces.dedup();
+        if !pieces.is_empty() {
+            svg.push_str("<defs>\n");
+            for piece in &pieces {
+                svg.push_str(&Self::get_piece_definition(*piece));
+            }
+            svg.push_str("</defs>\n");
+        }
+
+        for coord in (0..8).flat_map(|x| (0..8).map(move |y| (x << 4) + y)) {
+            let (x, y) = self.get_position(coord);
+            let light = Square::from(coord >> 4, coord & 0x0f).get_color() == White;
+            let fill = if light { &self.light } else { &self.dark };
+            svg.push_str(&format!("<rect x=\"{x}\" y=\"{y}\" width=\"{square}\" height=\"{square}\" fill=\"{fill}\"/>\n"));
+        }
+        for (coord, color) in &self.highlights {
+            let (x, y) = self.get_position(*coord);
+            svg.push_str(&format!("<rect x=\"{x}\" y=\"{y}\" width=\"{square}\" height=\"{square}\" fill=\"{color}\" opacity=\"0.5\"/>\n"));
+        }
+        if self.coordinates {
+            svg.push_str(&self.get_coordinates());
+        }
+
+        for (i, square_state) in board.get_state().iter().enumerate() {
+            let Some(piece) = square_state.get_piece() else { continue };
+            let (x, y) = self.get_position((((i % 8) as u8) << 4) + (i / 8) as u8);
+            svg.push_str(&format!(
+                "<use xlink:href=\"#{}\" transform=\"translate({x} {y}) scale({})\"/>\n",
+                Self::get_piece_id(piece),
+                square / PIECE_SIZE
+            ));
+        }
+
+        for arrow in &self.arrows {
+            svg.push_str(&self.get_arrow(arrow));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    // top left corner of the square
+    fn get_position(&self, coord: u8) -> (f32, f32) {
+        let square = self.size as f32 / 8.0;
+        let (x, y) = Square::u8_to_tuple(coord);
+        let (column, row) = match self.orientation {
+            White => (x, 7 - y),
+            Black => (7 - x, y),
+        };
+        (column as f32 * square, row as f32 * square)
+    }
+
+    fn get_piece_id(piece: Piece) -> String {
+        let name = match piece.r#type {
+            Pawn => "pawn",
+            Rook => "rook",
+            Knight => "knight",
+            Bishop => "bishop",
+            Queen => "queen",
+            King => "king",
+        };
+        format!("{}-{name}", if piece.color == White { "white" } else { "black" })
+    }
+
+    fn get_piece_definition(piece: Piece) -> String {
+        let (fill, detail) = get_piece_colors(piece.color);
+        let mut definition = format!(
+            "<g id=\"{}\" fill=\"{fill}\" stroke=\"#000000\" stroke-width=\"{PIECE_STROKE}\" stroke-linejoin=\"round\" stroke-linecap=\"round\">\n",
+            Self::get_piece_id(piece)
+        );
+        for shape in get_shapes(piece.r#type) {
+            definition.push_str(&match shape {
+                Shape::Polygon(points) => {
+                    let points: Vec<String> = points.iter().map(|(x, y)| format!("{x},{y}")).collect();
+                    format!("<polygon points=\"{}\"/>\n", points.join(" "))
+                },
+                Shape::Circle(x, y, r) => format!("<circle cx=\"{x}\" cy=\"{y}\" r=\"{r}\"/>\n"),
+                Shape::Line((x1, y1), (x2, y2)) => format!("<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"{detail}\"/>\n"),
+            });
+        }
+        definition.push_str("</g>\n");
+        definition
+    }
+
+    // in the corners of the squares along the edges, in the other square color so they stand out
+    fn get_coordinates(&self) -> String {
+        let square = self.size as f32 / 8.0;
+        let font = square * 0.22;
+        // the rank along the bottom and the file along the left
+        let (bottom, left) = match self.orientation {
+            White => (0, 0),
+            Black => (7, 7),
+        };
+        let mut coordinates = String::new();
+        for i in 0..8u8 {
+            let (file, rank) = match self.orientation {
+                White => (i, 7 - i),
+                Black => (7 - i, i),
+            };
+            let bottom_right = (file << 4) + bottom;
+            let top_left = (left << 4) + rank;
+            for (coord, label, anchor, dx, dy) in [
+                (bottom_right, ((b'a' + file) as char).to_string(), "end", square - font * 0.3, square - font * 0.3),
+                (top_left, (rank + 1).to_string(), "start", font * 0.3, font * 1.0),
+            ] {
+                let (x, y) = self.get_position(coord);
+                let light = Square::from(coord >> 4, coord & 0x0f).get_color() == White;
+                let fill = if light { &self.dark } else { &self.light };
+                coordinates.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"{font}\" font-family=\"sans-serif\" font-weight=\"bold\" text-anchor=\"{anchor}\" fill=\"{fill}\">{label}</text>\n",
+                    x + dx,
+                    y + dy
+                ));
+            }
+        }
+        coordinates
+    }
+
+    // from the center of one square to the other, the line stops where the head starts
+    fn get_arrow(&self, arrow: &Arrow) -> String {
+        let square = self.size as f32 / 8.0;
+        let center = |coord| {
+            let (x, y) = self.get_position(coord);
+            (x + square / 2.0, y + square / 2.0)
+        };
+        let ((x1, y1), (x2, y2)) = (center(arrow.from), center(arrow.to));
+        let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+        if length == 0.0 {
+            return String::new();
+        }
+        let (dx, dy) = ((x2 - x1) / length, (y2 - y1) / length);
+        let (width, head) = (square * 0.18, square * 0.45);
+        let (bx, by) = (x2 - dx * head, y2 - dy * head);
+        let (px, py) = (-dy * head * 0.6, dx * head * 0.6);
+        let (left, right) = ((bx + px, by + py), (bx - px, by - py));
+        format!(
+            "<g fill=\"{color}\" stroke=\"{color}\" opacity=\"0.8\"><line x1=\"{x1}\" y1=\"{y1}\" x2=\"{bx}\" y2=\"{by}\" stroke-width=\"{width}\"/><polygon points=\"{x2},{y2} {},{} {},{}\" stroke=\"none\"/></g>\n",
+            left.0,
+            left.1,
+            right.0,
+            right.1,
+            color = arrow.color,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the names of the elements, checking that every one is closed in the right order
+    fn get_elements(svg: &str) -> Vec<String> {
+        let mut elements = Vec::new();
+        let mut open = Vec::new();
+        for tag in svg.split('<').skip(1) {
+            let tag = tag.split_once('>').unwrap().0;
+            assert_eq!(tag.matches('"').count() % 2, 0, "unbalanced quotes in <{tag}>");
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name.to_string()));
+                continue;
+            }
+            let name = tag.split_whitespace().next().unwrap().trim_end_matches('/').to_string();
+            if !tag.ends_with('/') {
+                open.push(name.clone());
+            }
+            elements.push(name);
+        }
+        assert!(open.is_empty(), "unclosed {open:?}");
+        elements
+    }
+
+    fn get_board(fen: &str) -> Board {
+        let mut board = Board::new();
+        board.init(fen).unwrap();
+        board
+    }
+
+    #[test]
+    fn structure() {
+        let renderer = SvgRenderer {
+            highlights: vec![(0x43, "#ffff00".into())],
+            arrows: vec![Arrow::new(0x41, 0x43, "green"), Arrow::new(0x41, 0x41, "red")],
+            ..SvgRenderer::new()
+        };
+        let svg = renderer.render(&get_board(""));
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\"") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("width=\"400\" height=\"400\" viewBox=\"0 0 400 400\""));
+
+        let elements = get_elements(&svg);
+        let count = |name: &str| elements.iter().filter(|element| *element == name).count();
+        assert_eq!(elements[..2], ["svg", "defs"]);
+        // a definition of every kind of piece, one group per arrow going from one square to another
+        assert_eq!((count("defs"), count("g"), count("use")), (1, 12 + 1, 32));
+        assert_eq!((count("rect"), count("text")), (64 + 1, 16));
+        for piece in ["white-pawn", "white-king", "black-queen", "black-knight"] {
+            assert!(svg.contains(&format!("<g id=\"{piece}\"")) && svg.contains(&format!("xlink:href=\"#{piece}\"")));
+        }
+
+        // nothing to define on an empty board, and no coordinates when turned off
+        let svg = SvgRenderer { coordinates: false, ..SvgRenderer::new() }.render(&Board::new());
+        assert_eq!(get_elements(&svg).iter().filter(|element| *element != "rect").collect::<Vec<_>>(), ["svg"]);
+    }
+
+    #[test]
+    fn placement() {
+        let board = get_board("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+        let svg = SvgRenderer { highlights: vec![(0x43, "yellow".into())], ..SvgRenderer::new() }.render(&board);
+        // squares are 50 pixels, pieces are drawn at 45 and scaled up
+        assert!(svg.contains("<use xlink:href=\"#white-rook\" transform=\"translate(0 350) scale(1.1111112)\"/>"));
+        assert!(svg.contains("<use xlink:href=\"#white-king\" transform=\"translate(200 350)"));
+        assert!(svg.contains("<use xlink:href=\"#black-king\" transform=\"translate(200 0)"));
+        assert!(svg.contains("<rect x=\"200\" y=\"200\" width=\"50\" height=\"50\" fill=\"yellow\" opacity=\"0.5\"/>"));
+        // a1 is dark, h1 is light
+        assert!(svg.contains("<rect x=\"0\" y=\"350\" width=\"50\" height=\"50\" fill=\"#b58863\"/>"));
+        assert!(svg.contains("<rect x=\"350\" y=\"350\" width=\"50\" height=\"50\" fill=\"#f0d9b5\"/>"));
+        // the a in the corner of a1 and the 8 in the corner of a8, in the other square color
+        assert!(svg.contains("<text x=\"46.7\" y=\"396.7\" font-size=\"11\" font-family=\"sans-serif\" font-weight=\"bold\" text-anchor=\"end\" fill=\"#f0d9b5\">a</text>"));
+        assert!(svg.contains("y=\"11\" font-size=\"11\" font-family=\"sans-serif\" font-weight=\"bold\" text-anchor=\"start\" fill=\"#b58863\">8</text>"));
+
+        // turned around, a1 is in the top right
+        let svg = SvgRenderer { size: 800, orientation: Black, ..SvgRenderer::new() }.render(&board);
+        assert!(svg.contains("<use xlink:href=\"#white-rook\" transform=\"translate(700 0) scale(2.2222223)\"/>"));
+        assert!(svg.contains("<use xlink:href=\"#black-king\" transform=\"translate(300 700)"));
+        assert!(svg.contains("<text x=\"93.4\" y=\"793.4\" font-size=\"22\"") && svg.contains(">h</text>"));
+        assert!(svg.contains("y=\"22\" font-size=\"22\" font-family=\"sans-serif\" font-weight=\"bold\" text-anchor=\"start\" fill=\"#b58863\">1</text>"));
+
+        // arrows go from the center of one square towards the center of the other
+        let svg = SvgRenderer { arrows: vec![Arrow::new(0x41, 0x43, "green")], ..SvgRenderer::new() }.render(&board);
+        assert!(svg.contains("<g fill=\"green\" stroke=\"green\" opacity=\"0.8\"><line x1=\"225\" y1=\"325\" x2=\"225\" y2=\"247.5\" stroke-width=\"9\"/><polygon points=\"225,225 238.5,247.5 211.5,247.5\""));
+    }
+}
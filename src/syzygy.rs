@@ -0,0 +1,1727 @@
+use std::{collections::HashMap, fmt, fs, ops::Neg, path::{Path, PathBuf}, sync::OnceLock};
+use crate::{board::{Board, Color::*, Move, Square}, pieces::{Piece, Pieces::*}, variant::Variant};
+
+// syzygy endgame tablebases, win/draw/loss (.rtbw) and distance to zeroing move (.rtbz) files
+// laid out the same way as the original generator and the probing code in Stockfish
+
+pub const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// per table flags, all but SINGLE_VALUE only appear in dtz files
+const FLAG_STM: u8 = 1; // which side to move the dtz table is stored for
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// win or loss from the side to move's point of view, cursed wins and blessed losses
+// would be wins or losses without the 50 move rule
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Wdl::from_value(-(self as i32))
+    }
+}
+
+// the dtz of the move before a capture or pawn move, which is all the tables can tell us about those
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+// lookup tables for turning a position into an index, squares are numbered a1 = 0 to h8 = 63
+struct Indexes {
+    binomial: [[u64; 64]; MAX_PIECES], // binomial[k][n] is n choose k
+    map_pawns: [u64; 64], // a2-h7 to 0..47, the lead pawn is the one with the highest value
+    lead_pawn_idx: [[u64; 64]; MAX_PIECES], // by number of lead pawns and the square of the first one
+    lead_pawns_size: [[u64; 4]; MAX_PIECES], // by number of lead pawns and file a to d
+    map_b1h1h7: [u64; 64], // the 28 squares below the a1-h8 diagonal
+    map_a1d1d4: [u64; 64], // the a1-d1-d4 triangle, the 6 squares below the diagonal first
+    map_kk: [[u64; 64]; 10], // the 462 ways to place both kings with the first one in the triangle
+}
+
+const fn off_diagonal(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+static INDEXES: Indexes = {
+    let mut indexes = Indexes {
+        binomial: [[0; 64]; MAX_PIECES],
+        map_pawns: [0; 64],
+        lead_pawn_idx: [[0; 64]; MAX_PIECES],
+        lead_pawns_size: [[0; 4]; MAX_PIECES],
+        map_b1h1h7: [0; 64],
+        map_a1d1d4: [0; 64],
+        map_kk: [[0; 64]; 10],
+    };
+
+    let mut n = 0;
+    while n < 64 {
+        let mut k = 0;
+        while k < MAX_PIECES {
+            indexes.binomial[k][n] = if k == 0 {
+                1
+            } else if n == 0 {
+                0
+            } else {
+                indexes.binomial[k - 1][n - 1] + indexes.binomial[k][n - 1]
+            };
+            k += 1;
+        }
+        n += 1;
+    }
+
+    let mut code = 0;
+    let mut square = 0;
+    while square < 64 {
+        if off_diagonal(square) < 0 {
+            indexes.map_b1h1h7[square] = code;
+            code += 1;
+        }
+        square += 1;
+    }
+
+    // below the diagonal first, then a1, b2, c3, d4
+    let mut code = 0;
+    let mut square = 0;
+    while square < 28 {
+        if off_diagonal(square) < 0 && square % 8 <= 3 {
+            indexes.map_a1d1d4[square] = code;
+            code += 1;
+        }
+        square += 1;
+    }
+    let mut i = 0;
+    while i < 4 {
+        indexes.map_a1d1d4[i * 9] = code;
+        code += 1;
+        i += 1;
+    }
+
+    // kings can't be next to each other, and with the first king on the diagonal the second one
+    // can be mirrored below it too, positions with both kings on the diagonal come last
+    let mut code = 0;
+    let mut idx = 0;
+    while idx < 10 {
+        let mut first = 0;
+        while first < 28 {
+            let in_triangle = first % 8 <= 3 && off_diagonal(first) <= 0;
+            if in_triangle && indexes.map_a1d1d4[first] == idx as u64 && (idx > 0 || first == 1) {
+                let mut second = 0;
+                while second < 64 {
+                    let touching = (first / 8).abs_diff(second / 8) <= 1 && (first % 8).abs_diff(second % 8) <= 1;
+                    if !touching && !(off_diagonal(first) == 0 && off_diagonal(second) >= 0) {
+                        indexes.map_kk[idx][second] = code;
+                        code += 1;
+                    }
+                    second += 1;
+                }
+            }
+            first += 1;
+        }
+        idx += 1;
+    }
+    let mut idx = 0;
+    while idx < 10 {
+        let mut first = 0;
+        while first < 28 {
+            if first % 8 <= 3 && off_diagonal(first) == 0 && indexes.map_a1d1d4[first] == idx as u64 {
+                let mut second = 0;
+                while second < 64 {
+                    let touching = (first / 8).abs_diff(second / 8) <= 1 && (first % 8).abs_diff(second % 8) <= 1;
+                    if !touching && off_diagonal(second) == 0 {
+                        indexes.map_kk[idx][second] = code;
+                        code += 1;
+                    }
+                    second += 1;
+                }
+            }
+            first += 1;
+        }
+        idx += 1;
+    }
+
+    // pawns nearer the edge and further back come first, a2, h2, a3, h3 ... then b2, g2 ...
+    let mut file = 0;
+    while file < 4 {
+        let mut rank = 1;
+        while rank < 7 {
+            let square = rank * 8 + file;
+            let available = 47 - 2 * (file * 6 + rank - 1) as u64;
+            indexes.map_pawns[square] = available;
+            indexes.map_pawns[square ^ 7] = available - 1;
+            rank += 1;
+        }
+        file += 1;
+    }
+
+    let mut count = 1;
+    while count < MAX_PIECES {
+        let mut file = 0;
+        while file < 4 {
+            let mut idx = 0;
+            let mut rank = 1;
+            while rank < 7 {
+                let square = rank * 8 + file;
+                indexes.lead_pawn_idx[count][square] = idx;
+                idx += indexes.binomial[count - 1][indexes.map_pawns[square] as usize];
+                rank += 1;
+            }
+            indexes.lead_pawns_size[count][file] = idx;
+            file += 1;
+        }
+        count += 1;
+    }
+
+    indexes
+};
+
+// piece codes as stored in the files, 1 to 6 for white pawn to king and 9 to 14 for black
+fn get_code(piece: Piece) -> u8 {
+    let r#type = match piece.r#type {
+        Pawn => 1,
+        Knight => 2,
+        Bishop => 3,
+        Rook => 4,
+        Queen => 5,
+        King => 6,
+    };
+    if piece.color == White { r#type } else { r#type | 8 }
+}
+
+fn read_u16_le(bytes: &[u8], offset: usize) -> u16 {
+    bytes.get(offset..offset + 2).map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> u32 {
+    bytes.get(offset..offset + 4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> u32 {
+    bytes.get(offset..offset + 4).map_or(0, |bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+// everything needed to decompress one of the sub tables in a file, there is one per side to move
+// and per file of the lead pawn, offsets point into the file's bytes
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8, // doubles as the value for single value tables
+    num_blocks: usize,
+    block_size: usize,
+    span: u64, // every span values there is a sparse index entry
+    lowest_sym: usize,
+    btree: usize, // each symbol expands to a left and right symbol, 12 bits each
+    block_length: usize, // number of values minus one in each block
+    block_length_size: usize,
+    sparse_index: usize, // 6 bytes each, a block number and an offset into it
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>, // lowest symbol of each length, padded to 64 bits
+    symlen: Vec<u32>, // number of values minus one each symbol expands to
+    pieces: [u8; MAX_PIECES], // the order the pieces are encoded in
+    group_idx: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1], // zero terminated
+    map_idx: [u16; 4], // dtz value maps for win, loss, cursed win and blessed loss
+}
+
+impl PairsData {
+    fn get_left(bytes: &[u8], btree: usize, sym: u32) -> u32 {
+        let offset = btree + sym as usize * 3;
+        ((bytes[offset + 1] as u32 & 0x0F) << 8) | bytes[offset] as u32
+    }
+
+    fn get_right(bytes: &[u8], btree: usize, sym: u32) -> u32 {
+        let offset = btree + sym as usize * 3;
+        ((bytes[offset + 2] as u32) << 4) | (bytes[offset + 1] as u32 >> 4)
+    }
+
+    fn set_symlen(&mut self, bytes: &[u8], sym: u32, visited: &mut [bool]) -> Result<u32, &'static str> {
+        visited[sym as usize] = true;
+        let right = Self::get_right(bytes, self.btree, sym);
+        if right == 0xFFF {
+            return Ok(0);
+        }
+
+        let left = Self::get_left(bytes, self.btree, sym);
+        for child in [left, right] {
+            if child as usize >= visited.len() {
+                return Err("Corrupted tablebase symbol tree");
+            }
+            if !visited[child as usize] {
+                self.symlen[child as usize] = self.set_symlen(bytes, child, visited)?;
+            }
+        }
+
+        Ok(self.symlen[left as usize] + self.symlen[right as usize] + 1)
+    }
+
+    // reads the compression parameters and returns the offset after them
+    fn set_sizes(&mut self, bytes: &[u8], mut offset: usize) -> Result<usize, &'static str> {
+        let byte = |offset: usize| bytes.get(offset).copied().ok_or("Truncated tablebase file");
+
+        self.flags = byte(offset)?;
+        offset += 1;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            self.min_sym_len = byte(offset)?;
+            return Ok(offset + 1);
+        }
+
+        let size = self.group_idx[self.group_len.iter().position(|&len| len == 0).unwrap()];
+        self.block_size = 1 << byte(offset)?;
+        self.span = 1 << byte(offset + 1)?;
+        self.sparse_index_size = size.div_ceil(self.span) as usize;
+        let padding = byte(offset + 2)? as usize;
+        self.num_blocks = read_u32_le(bytes, offset + 3) as usize;
+        self.block_length_size = self.num_blocks + padding;
+        let max_sym_len = byte(offset + 7)?;
+        self.min_sym_len = byte(offset + 8)?;
+        offset += 9;
+        if self.min_sym_len == 0 || max_sym_len < self.min_sym_len || max_sym_len > 32 {
+            return Err("Corrupted tablebase symbol lengths");
+        }
+
+        // canonical huffman code, longer symbols have lower values
+        self.lowest_sym = offset;
+        let lengths = (max_sym_len - self.min_sym_len + 1) as usize;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16_le(bytes, self.lowest_sym + i * 2) as u64;
+            let next = read_u16_le(bytes, self.lowest_sym + (i + 1) * 2) as u64;
+            self.base64[i] = (self.base64[i + 1] + lowest - next) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - i as u32 - self.min_sym_len as u32).unwrap_or(0);
+        }
+        offset += lengths * 2;
+
+        // recursive pairing, every symbol stands for a pair of smaller symbols
+        let symbols = read_u16_le(bytes, offset) as usize;
+        offset += 2;
+        self.btree = offset;
+        if bytes.len() < self.btree + symbols * 3 {
+            return Err("Truncated tablebase file");
+        }
+
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(bytes, sym as u32, &mut visited)?;
+            }
+        }
+
+        Ok(offset + symbols * 3 + (symbols & 1))
+    }
+
+    fn decompress(&self, bytes: &[u8], idx: u64) -> Result<i32, &'static str> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Ok(self.min_sym_len as i32);
+        }
+
+        // the sparse index points at the block holding the value in the middle of each span,
+        // walk from there to the block holding ours
+        let k = (idx / self.span) as usize;
+        if k >= self.sparse_index_size {
+            return Err("Tablebase index out of range");
+        }
+        let mut block = read_u32_le(bytes, self.sparse_index + k * 6) as usize;
+        let mut offset = read_u16_le(bytes, self.sparse_index + k * 6 + 4) as i64;
+        offset += (idx % self.span) as i64 - (self.span / 2) as i64;
+
+        let block_length = |block: usize| read_u16_le(bytes, self.block_length + block * 2) as i64;
+        while offset < 0 {
+            block = block.checked_sub(1).ok_or("Corrupted tablebase sparse index")?;
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) {
+            offset -= block_length(block) + 1;
+            block += 1;
+            if block >= self.block_length_size {
+                return Err("Corrupted tablebase sparse index");
+            }
+        }
+
+        // find the symbol covering our offset in the block's huffman coded symbols
+        let mut pointer = self.data + block * self.block_size;
+        let mut buffer = ((read_u32_be(bytes, pointer) as u64) << 32) | read_u32_be(bytes, pointer + 4) as u64;
+        pointer += 8;
+        let mut buffer_size = 64;
+
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buffer < self.base64[len] {
+                len += 1;
+                if len == self.base64.len() {
+                    return Err("Corrupted tablebase data");
+                }
+            }
+
+            sym = ((buffer - self.base64[len]) >> (64 - len - self.min_sym_len as usize)) as u32;
+            sym += read_u16_le(bytes, self.lowest_sym + len * 2) as u32;
+            let expanded = *self.symlen.get(sym as usize).ok_or("Corrupted tablebase data")? as i64 + 1;
+            if offset < expanded {
+                break;
+            }
+
+            offset -= expanded;
+            let len = len + self.min_sym_len as usize;
+            buffer <<= len;
+            buffer_size -= len;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= (read_u32_be(bytes, pointer) as u64) << (64 - buffer_size);
+                pointer += 4;
+            }
+        }
+
+        // then expand it until we're down to a single value
+        while self.symlen[sym as usize] != 0 {
+            let left = Self::get_left(bytes, self.btree, sym);
+            if offset < self.symlen[left as usize] as i64 + 1 {
+                sym = left;
+            } else {
+                offset -= self.symlen[left as usize] as i64 + 1;
+                sym = Self::get_right(bytes, self.btree, sym);
+            }
+        }
+
+        Ok(Self::get_left(bytes, self.btree, sym) as i32)
+    }
+}
+
+// the contents of one .rtbw or .rtbz file
+struct TableData {
+    bytes: Vec<u8>,
+    pairs: Vec<PairsData>, // by side to move * 4 + file of the lead pawn
+    map: usize, // dtz value maps
+}
+
+impl TableData {
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        &self.pairs[stm * 4 + file]
+    }
+}
+
+// one endgame, like KRvK, with white as the stronger side
+struct Table {
+    key: String,
+    mirrored_key: String, // same material with the colors swapped, KvKR
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool, // a piece other than the kings that there is only one of
+    pawn_count: [usize; 2], // lead color, other color
+    wdl_path: PathBuf,
+    dtz_path: PathBuf,
+    wdl: OnceLock<Result<TableData, &'static str>>, // loaded on first probe
+    dtz: OnceLock<Result<TableData, &'static str>>,
+}
+
+// KRvK style keys, kings first then the other pieces from most to least valuable
+fn get_key(pieces: &[Piece]) -> String {
+    let mut key = String::new();
+    for color in [White, Black] {
+        if color == Black {
+            key.push('v');
+        }
+        for r#type in [King, Queen, Rook, Bishop, Knight, Pawn] {
+            for _ in pieces.iter().filter(|piece| piece.color == color && piece.r#type == r#type) {
+                key.push(Piece::from(r#type, White).get_char());
+            }
+        }
+    }
+    key
+}
+
+impl Table {
+    fn new(name: &str, wdl_path: PathBuf, dtz_path: PathBuf) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let mut pieces = Vec::new();
+        for (side, color) in [(white, White), (black, Black)] {
+            if !side.starts_with('K') || side.matches('K').count() != 1 {
+                return None;
+            }
+            for c in side.chars() {
+                pieces.push(Piece::from(Piece::from_char(c)?.r#type, color));
+            }
+        }
+
+        let key = get_key(&pieces);
+        if key != name || pieces.len() > MAX_PIECES {
+            return None;
+        }
+        let mirrored: Vec<Piece> = pieces.iter().map(|&piece| Piece::from(piece.r#type, !piece.color)).collect();
+
+        let count = |r#type, color| pieces.iter().filter(|piece| piece.r#type == r#type && piece.color == color).count();
+        let (white_pawns, black_pawns) = (count(Pawn, White), count(Pawn, Black));
+
+        // the side with fewer pawns leads, it compresses better
+        let lead = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+
+        Some(Table {
+            key,
+            mirrored_key: get_key(&mirrored),
+            piece_count: pieces.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: [White, Black].into_iter()
+                .any(|color| [Queen, Rook, Bishop, Knight, Pawn].into_iter().any(|r#type| count(r#type, color) == 1)),
+            pawn_count: if lead { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            wdl_path,
+            dtz_path,
+            wdl: OnceLock::new(),
+            dtz: OnceLock::new(),
+        })
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.key == self.mirrored_key
+    }
+
+    fn get_data(&self, dtz: bool) -> Result<&TableData, &'static str> {
+        let (lock, path) = if dtz { (&self.dtz, &self.dtz_path) } else { (&self.wdl, &self.wdl_path) };
+        lock.get_or_init(|| {
+            let bytes = fs::read(path).map_err(|_| "Could not read tablebase file")?;
+            self.parse(bytes, dtz)
+        }).as_ref().map_err(|&error| error)
+    }
+
+    // pieces are split into groups that are encoded together, the first three unique pieces or
+    // the two kings, or the lead pawns, then every run of identical pieces
+    fn set_groups(&self, data: &mut PairsData, order: [u8; 2], file: usize) {
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns { 0 } else if self.has_unique_pieces { 3 } else { 2 };
+        data.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || data.pieces[i] == data.pieces[i - 1] {
+                data.group_len[n] += 1;
+            } else {
+                n += 1;
+                data.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        data.group_len[n] = 0;
+
+        // the groups are encoded in the order the file asks for, not necessarily the order above
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - data.group_len[0] - if both_pawns { data.group_len[1] } else { 0 };
+        let mut idx = 1;
+
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                data.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    INDEXES.lead_pawns_size[data.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                data.group_idx[1] = idx;
+                idx *= INDEXES.binomial[data.group_len[1]][48 - data.group_len[0]];
+            } else {
+                data.group_idx[next] = idx;
+                idx *= INDEXES.binomial[data.group_len[next]][free_squares];
+                free_squares -= data.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        data.group_idx[n] = idx;
+    }
+
+    fn parse(&self, bytes: Vec<u8>, dtz: bool) -> Result<TableData, &'static str> {
+        if bytes.get(0..4) != Some(if dtz { &DTZ_MAGIC } else { &WDL_MAGIC }) {
+            return Err("Corrupted tablebase file");
+        }
+        let byte = |offset: usize| bytes.get(offset).copied().ok_or("Truncated tablebase file");
+
+        let mut offset = 4;
+        if (byte(offset)? & 2 != 0) != self.has_pawns {
+            return Err("Tablebase file doesn't match its name");
+        }
+        offset += 1;
+
+        let sides = if !dtz && !self.is_symmetric() { 2 } else { 1 };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+
+        let mut pairs = vec![PairsData::default(); 2 * 4];
+        for file in 0..files {
+            let order = [byte(offset)?, if both_pawns { byte(offset + 1)? } else { 0xFF }];
+            offset += 1 + both_pawns as usize;
+            for k in 0..self.piece_count {
+                let codes = byte(offset + k)?;
+                pairs[file].pieces[k] = codes & 0x0F;
+                pairs[4 + file].pieces[k] = codes >> 4;
+            }
+            offset += self.piece_count;
+
+            for side in 0..sides {
+                let order = [order[0] >> (side * 4) & 0x0F, order[1] >> (side * 4) & 0x0F];
+                self.set_groups(&mut pairs[side * 4 + file], order, file);
+            }
+        }
+        offset += offset & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                offset = pairs[side * 4 + file].set_sizes(&bytes, offset)?;
+            }
+        }
+
+        let map = offset;
+        if dtz {
+            for data in pairs.iter_mut().take(files) {
+                if data.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if data.flags & FLAG_WIDE != 0 {
+                    offset += offset & 1;
+                    for i in 0..4 {
+                        data.map_idx[i] = ((offset - map) / 2 + 1) as u16;
+                        offset += 2 * read_u16_le(&bytes, offset) as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        data.map_idx[i] = (offset - map + 1) as u16;
+                        offset += byte(offset)? as usize + 1;
+                    }
+                }
+            }
+            offset += offset & 1;
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let data = &mut pairs[side * 4 + file];
+                data.sparse_index = offset;
+                offset += data.sparse_index_size * 6;
+            }
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let data = &mut pairs[side * 4 + file];
+                data.block_length = offset;
+                offset += data.block_length_size * 2;
+            }
+        }
+
+        if offset > bytes.len() {
+            return Err("Truncated tablebase file");
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let data = &mut pairs[side * 4 + file];
+                offset = (offset + 0x3F) & !0x3F;
+                data.data = offset;
+                offset += data.num_blocks * data.block_size;
+                if data.num_blocks > 0 && offset > bytes.len() {
+                    return Err("Truncated tablebase file");
+                }
+            }
+        }
+
+        Ok(TableData { bytes, pairs, map })
+    }
+
+    // the side to move and lead pawn file of the sub table holding the position, and its index
+    // in there, None if a dtz table only has the other side to move
+    fn encode(&self, table: &TableData, board: &Board, dtz: bool) -> Option<(usize, usize, u64)> {
+        let pieces: Vec<(usize, Piece)> = board.get_state().iter().enumerate()
+            .filter_map(|(index, square)| square.get_piece().map(|piece| (index, piece)))
+            .collect();
+
+        // tables are stored with white as the stronger side, and symmetric ones only with white to
+        // move, anything else is looked up with the colors swapped and the board flipped
+        let key = get_key(&pieces.iter().map(|&(_, piece)| piece).collect::<Vec<_>>());
+        let flip = key != self.key || (self.is_symmetric() && board.get_turn() == Black);
+        let (flip_color, flip_squares) = if flip { (8, 56) } else { (0, 0) };
+        let stm = flip as usize ^ (board.get_turn() == Black) as usize;
+
+        let mut squares = Vec::with_capacity(pieces.len());
+        let mut codes = Vec::with_capacity(pieces.len());
+        let mut lead_pawns = 0;
+        let mut file = 0;
+
+        // pawn tables are split by the file of the lead pawn, the one nearest the edge and furthest back
+        if self.has_pawns {
+            let lead = table.get(0, 0).pieces[0] ^ flip_color;
+            for &(index, piece) in &pieces {
+                if get_code(piece) == lead {
+                    squares.push(index ^ flip_squares);
+                    codes.push(lead);
+                }
+            }
+            lead_pawns = squares.len();
+
+            let best = (0..lead_pawns).max_by_key(|&i| INDEXES.map_pawns[squares[i]]).unwrap();
+            squares.swap(0, best);
+            file = squares[0] % 8;
+            if file > 3 {
+                file = 7 - file;
+            }
+        }
+
+        if dtz {
+            let flags = table.get(0, file).flags;
+            if (flags & FLAG_STM) as usize != stm && (!self.is_symmetric() || self.has_pawns) {
+                return None;
+            }
+        }
+
+        for &(index, piece) in &pieces {
+            if self.has_pawns && get_code(piece) == table.get(0, 0).pieces[0] ^ flip_color {
+                continue;
+            }
+            squares.push(index ^ flip_squares);
+            codes.push(get_code(piece) ^ flip_color);
+        }
+
+        let side = if dtz { 0 } else { stm };
+        let data = table.get(side, file);
+
+        // put the pieces in the order the table encodes them in
+        let size = squares.len();
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if data.pieces[i] == codes[j] {
+                    codes.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // mirror so the lead piece is on files a to d
+        if squares[0] % 8 > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 7;
+            }
+        }
+
+        let mut idx;
+        if self.has_pawns {
+            idx = INDEXES.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| INDEXES.map_pawns[square]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += INDEXES.binomial[i][INDEXES.map_pawns[square] as usize];
+            }
+        } else {
+            // without pawns the board can also be flipped vertically and along the a1-h8 diagonal
+            if squares[0] / 8 > 3 {
+                for square in squares.iter_mut() {
+                    *square ^= 56;
+                }
+            }
+
+            for i in 0..data.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for square in squares.iter_mut().skip(i) {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            idx = if self.has_unique_pieces {
+                let adjust1 = (squares[1] > squares[0]) as u64;
+                let adjust2 = (squares[2] > squares[0]) as u64 + (squares[2] > squares[1]) as u64;
+                let rank = |square: usize| (square / 8) as u64;
+
+                if off_diagonal(squares[0]) != 0 {
+                    (INDEXES.map_a1d1d4[squares[0]] * 63 + (squares[1] as u64 - adjust1)) * 62 + squares[2] as u64 - adjust2
+                } else if off_diagonal(squares[1]) != 0 {
+                    (6 * 63 + rank(squares[0]) * 28 + INDEXES.map_b1h1h7[squares[1]]) * 62 + squares[2] as u64 - adjust2
+                } else if off_diagonal(squares[2]) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62
+                        + rank(squares[0]) * 7 * 28
+                        + (rank(squares[1]) - adjust1) * 28
+                        + INDEXES.map_b1h1h7[squares[2]]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28
+                        + rank(squares[0]) * 7 * 6
+                        + (rank(squares[1]) - adjust1) * 6
+                        + (rank(squares[2]) - adjust2)
+                }
+            } else {
+                INDEXES.map_kk[INDEXES.map_a1d1d4[squares[0]] as usize][squares[1]]
+            };
+        }
+
+        // the remaining groups, each one placed on the squares the earlier groups left free
+        idx *= data.group_idx[0];
+        let mut start = data.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while data.group_len[next] != 0 {
+            let len = data.group_len[next];
+            squares[start..start + len].sort_unstable();
+
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&other| square > other).count();
+                n += INDEXES.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+
+            remaining_pawns = false;
+            idx += n * data.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        Some((side, file, idx))
+    }
+
+    // the raw value stored for the position and the lead pawn file, None if a dtz table only has
+    // the other side to move
+    fn probe(&self, board: &Board, dtz: bool) -> Result<Option<(i32, usize)>, &'static str> {
+        let table = self.get_data(dtz)?;
+        let Some((side, file, idx)) = self.encode(table, board, dtz) else { return Ok(None) };
+        Ok(Some((table.get(side, file).decompress(&table.bytes, idx)?, file)))
+    }
+
+    fn probe_wdl(&self, board: &Board) -> Result<Wdl, &'static str> {
+        let (value, _) = self.probe(board, false)?.ok_or("Missing side to move in tablebase")?;
+        Ok(Wdl::from_value(value - 2))
+    }
+
+    // dtz in plies, None if the table is stored for the other side to move
+    fn probe_dtz(&self, board: &Board, wdl: Wdl) -> Result<Option<i32>, &'static str> {
+        let Some((mut value, file)) = self.probe(board, true)? else { return Ok(None) };
+
+        let table = self.get_data(true)?;
+        let data = table.get(0, file);
+        if data.flags & FLAG_MAPPED != 0 {
+            let map = data.map_idx[match wdl {
+                Wdl::Win | Wdl::Draw => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+            }] as usize;
+            value = if data.flags & FLAG_WIDE != 0 {
+                read_u16_le(&table.bytes, table.map + 2 * (map + value as usize)) as i32
+            } else {
+                table.bytes.get(table.map + map + value as usize).copied().ok_or("Corrupted tablebase map")? as i32
+            };
+        }
+
+        // some tables count moves instead of plies
+        if (wdl == Wdl::Win && data.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && data.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+
+        Ok(Some(value + 1))
+    }
+}
+
+fn count_pieces(board: &Board) -> usize {
+    board.get_state().iter().filter(|square| square.get_piece().is_some()).count()
+}
+
+fn is_zeroing(board: &Board, r#move: Move) -> bool {
+    let (x, y) = Square::u8_to_tuple(r#move.from);
+    board.is_capture(r#move) || board.get_index(x, y).get_piece().is_some_and(|piece| piece.r#type == Pawn)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RootMove {
+    pub r#move: Move,
+    pub wdl: Wdl,
+    pub dtz: i32, // plies to the next capture or pawn move counted from before the move, see probe_dtz
+    pub rank: i32, // higher is better, wins that can be converted before the 50 move rule all rank 1000
+}
+
+#[derive(Default)]
+pub struct Tablebase {
+    tables: Vec<Table>,
+    keys: HashMap<String, usize>, // both the key and the mirrored key of every table
+    max_pieces: usize,
+}
+
+impl fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tablebase {{ tables: {}, max_pieces: {} }}", self.tables.len(), self.max_pieces)
+    }
+}
+
+impl Tablebase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // adds every table in the directory, files are only read once a position needs them,
+    // returns the number of tables found
+    pub fn add_directory(&mut self, path: impl AsRef<Path>) -> Result<usize, &'static str> {
+        let entries = fs::read_dir(path.as_ref()).map_err(|_| "Could not read tablebase directory")?;
+        let mut added = 0;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "rtbw") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+            if self.keys.contains_key(name) {
+                continue;
+            }
+
+            let Some(table) = Table::new(name, path.clone(), path.with_extension("rtbz")) else { continue };
+            self.max_pieces = self.max_pieces.max(table.piece_count);
+            self.keys.insert(table.key.clone(), self.tables.len());
+            self.keys.insert(table.mirrored_key.clone(), self.tables.len());
+            self.tables.push(table);
+            added += 1;
+        }
+
+        Ok(added)
+    }
+
+    // positions with more pieces than this can't be probed
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    pub fn can_probe(&self, board: &Board) -> bool {
+        board.get_variant() == Variant::Standard && board.get_castling() == 0 && count_pieces(board) <= self.max_pieces
+    }
+
+    fn get_table(&self, board: &Board) -> Result<&Table, &'static str> {
+        let key = get_key(&board.get_state().iter().filter_map(|square| square.get_piece()).collect::<Vec<_>>());
+        self.keys.get(&key).map(|&index| &self.tables[index]).ok_or("Missing tablebase for this material")
+    }
+
+    fn probe_table_wdl(&self, board: &Board) -> Result<Wdl, &'static str> {
+        if count_pieces(board) == 2 {
+            return Ok(Wdl::Draw);
+        }
+        self.get_table(board)?.probe_wdl(board)
+    }
+
+    // the tables don't store the right value when the best move is a capture (or a pawn move in
+    // dtz tables), or when en passant is possible, so those moves are searched and the table is
+    // only trusted if none of them does better; the flag is set when such a move is the best one
+    fn search(&self, board: &Board, pawn_moves: bool) -> Result<(Wdl, bool), &'static str> {
+        let moves = board.get_legal_moves();
+        if moves.is_empty() {
+            return Ok((if board.in_check(board.get_turn()) { Wdl::Loss } else { Wdl::Draw }, false));
+        }
+
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for &r#move in &moves {
+            if !(board.is_capture(r#move) || pawn_moves && is_zeroing(board, r#move)) {
+                continue;
+            }
+
+            searched += 1;
+            let mut child = *board;
+            child.apply_move(r#move);
+            let (value, _) = self.search(&child, false)?;
+            let value = -value;
+
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+
+        let no_more_moves = searched == moves.len();
+        let value = if no_more_moves { best } else { self.probe_table_wdl(board)? };
+
+        if best >= value {
+            return Ok((best, best > Wdl::Draw || no_more_moves));
+        }
+        Ok((value, false))
+    }
+
+    fn check(&self, board: &Board) -> Result<(), &'static str> {
+        if board.get_castling() != 0 {
+            return Err("Tablebases don't cover positions with castling rights");
+        }
+        if count_pieces(board) > self.max_pieces {
+            return Err("Too many pieces for the tablebases");
+        }
+        Ok(())
+    }
+
+    // win, draw or loss for the side to move with perfect play
+    pub fn probe_wdl(&self, board: &Board) -> Result<Wdl, &'static str> {
+        self.check(board)?;
+        Ok(self.search(board, false)?.0)
+    }
+
+    // plies until the next capture or pawn move with perfect play, positive when the side to move
+    // wins, negative when it loses and 0 for draws; 100 more for cursed wins and blessed losses
+    pub fn probe_dtz(&self, board: &Board) -> Result<i32, &'static str> {
+        self.check(board)?;
+        self.probe_dtz_unchecked(board)
+    }
+
+    fn probe_dtz_unchecked(&self, board: &Board) -> Result<i32, &'static str> {
+        let (wdl, zeroing_best) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing_best {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+
+        let table = self.get_table(board)?;
+        if let Some(dtz) = table.probe_dtz(board, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Ok((dtz + if cursed { 100 } else { 0 }) * wdl.signum());
+        }
+
+        // the table only has the other side to move, so look one ply ahead for the fastest win
+        let mut min_dtz = i32::MAX;
+        let moves = board.get_legal_moves();
+        for r#move in moves {
+            let zeroing = is_zeroing(board, r#move);
+            let mut child = *board;
+            child.apply_move(r#move);
+
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&child, false)?.0)
+            } else {
+                -self.probe_dtz_unchecked(&child)?
+            };
+
+            if dtz == 1 && child.in_check(child.get_turn()) && child.get_legal_moves().is_empty() {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        Ok(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    // every legal move with its tablebase result, best first
+    pub fn rank_root_moves(&self, board: &Board) -> Result<Vec<RootMove>, &'static str> {
+        self.check(board)?;
+        let halfmove = board.get_halfmove() as i32;
+        let mut moves = Vec::new();
+
+        for r#move in board.get_legal_moves() {
+            let mut child = *board;
+            child.apply_move(r#move);
+
+            let mut dtz = if child.get_halfmove() == 0 {
+                dtz_before_zeroing(-self.search(&child, false)?.0)
+            } else {
+                let dtz = -self.probe_dtz_unchecked(&child)?;
+                dtz + dtz.signum()
+            };
+
+            // mate comes before any dtz
+            if dtz == 2 && child.in_check(child.get_turn()) && child.get_legal_moves().is_empty() {
+                dtz = 1;
+            }
+
+            // wins that still beat the 50 move rule are all as good as each other, so are losses
+            // that can't be dragged out past it
+            let rank = match dtz {
+                dtz if dtz > 0 => if dtz + halfmove <= 99 { 1000 } else { 1000 - (dtz + halfmove) },
+                dtz if dtz < 0 => if -dtz * 2 + halfmove < 100 { -1000 } else { -1000 + (-dtz + halfmove) },
+                _ => 0,
+            };
+
+            let wdl = match rank {
+                1000 => Wdl::Win,
+                rank if rank > 0 => Wdl::CursedWin,
+                -1000 => Wdl::Loss,
+                rank if rank < 0 => Wdl::BlessedLoss,
+                _ => Wdl::Draw,
+            };
+            moves.push(RootMove { r#move, wdl, dtz, rank });
+        }
+
+        // quicker wins and slower losses first among moves with the same rank
+        moves.sort_by_key(|root| (std::cmp::Reverse(root.rank), root.dtz));
+        Ok(moves)
+    }
+
+    // the moves that keep the best result and get closest to converting it, for restricting the
+    // search at the root, a shallow search could otherwise shuffle around until the 50 move rule
+    pub fn filter_root_moves(&self, board: &Board) -> Result<Vec<Move>, &'static str> {
+        let moves = self.rank_root_moves(board)?;
+        let best = moves.first().map_or((0, 0), |root| (root.rank, root.dtz));
+        Ok(moves.into_iter().filter(|root| (root.rank, root.dtz) == best).map(|root| root.r#move).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Reverse, collections::BinaryHeap};
+    use crate::{board::Color, pieces::Pieces};
+    use super::*;
+
+    // generated by generate_tables below, with the same indexing code that probes them. The
+    // official tables check that against the real format, point SYZYGY_PATH at a directory with
+    // the 3 and 4 piece ones and run cargo test --release official_tables -- --ignored
+    const TABLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/syzygy");
+
+    // a white king, one more white piece and a black king on any squares with either side to move
+    const STATES: usize = 64 * 64 * 64 * 2;
+    const ILLEGAL: i8 = i8::MIN;
+
+    fn get_tablebase() -> Tablebase {
+        let mut tablebase = Tablebase::new();
+        assert_eq!(tablebase.add_directory(TABLES), Ok(5));
+        tablebase
+    }
+
+    fn get_board(fen: &str) -> Board {
+        let mut board = Board::new();
+        board.init(fen).unwrap();
+        board
+    }
+
+    // squares are a1 = 0 to h8 = 63, flipped swaps the colors and mirrors the board top to bottom
+    fn get_position(r#type: Pieces, state: usize, flipped: bool) -> Option<Board> {
+        let (white_king, square, black_king) = (state / 2 / 64 / 64, state / 2 / 64 % 64, state / 2 % 64);
+        let turn = if state % 2 == 1 { Black } else { White };
+        if white_king == square || square == black_king || white_king == black_king {
+            return None;
+        }
+        if r#type == Pawn && !(8..56).contains(&square) {
+            return None;
+        }
+        if (white_king % 8).abs_diff(black_king % 8) <= 1 && (white_king / 8).abs_diff(black_king / 8) <= 1 {
+            return None;
+        }
+
+        let mut pieces = [None; 64];
+        let (flip_squares, turn) = if flipped { (56, !turn) } else { (0, turn) };
+        let color = |color: Color| if flipped { !color } else { color };
+        pieces[white_king ^ flip_squares] = Some(Piece::from(King, color(White)));
+        pieces[square ^ flip_squares] = Some(Piece::from(r#type, color(White)));
+        pieces[black_king ^ flip_squares] = Some(Piece::from(King, color(Black)));
+
+        let board = get_setup(&pieces, turn);
+        (!board.in_check(!turn)).then_some(board)
+    }
+
+    // the pieces by square, a1 = 0 to h8 = 63
+    fn get_setup(pieces: &[Option<Piece>; 64], turn: Color) -> Board {
+        let mut placement = String::new();
+        for y in (0..8).rev() {
+            let mut empty = 0;
+            for x in 0..8 {
+                match pieces[y * 8 + x] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            placement.push((b'0' + empty) as char);
+                            empty = 0;
+                        }
+                        placement.push(piece.get_char());
+                    },
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push((b'0' + empty) as char);
+            }
+            if y > 0 {
+                placement.push('/');
+            }
+        }
+
+        get_board(&format!("{placement} {} - - 0 1", if turn == White { "w" } else { "b" }))
+    }
+
+    // what a move leads to, a position in the same table and whether the move zeroes the 50 move
+    // counter, or the value for the side to move of a position in another table
+    #[derive(Clone, Copy)]
+    enum Child {
+        Same(usize, bool),
+        Known(i8),
+    }
+
+    // retrograde analysis with the board's own move generation, independent of the probing code
+    struct Solution {
+        wdl: Vec<i8>, // for the side to move, 1 win, 0 draw, -1 loss or ILLEGAL
+        dtz: Vec<i32>, // plies to mate or the next capture or pawn move, 0 once mated, -1 for draws
+    }
+
+    fn solve(r#type: Pieces, promotions: &[(Pieces, &Solution)]) -> Solution {
+        let mut wdl = vec![ILLEGAL; STATES];
+        let mut children = vec![Vec::new(); STATES];
+        for state in 0..STATES {
+            let Some(board) = get_position(r#type, state, false) else { continue };
+            let moves = board.get_legal_moves();
+            wdl[state] = if moves.is_empty() && board.in_check(board.get_turn()) { -1 } else { 0 };
+
+            for r#move in moves {
+                let zeroing = is_zeroing(&board, r#move);
+                let mut child = board;
+                child.apply_move(r#move);
+
+                let mut kings = [0; 2];
+                let mut other = None;
+                for (square, piece) in child.get_state().iter().enumerate().filter_map(|(i, square)| Some((i, square.get_piece()?))) {
+                    match piece.r#type {
+                        King => kings[(piece.color == Black) as usize] = square,
+                        r#type => other = Some((square, r#type)),
+                    }
+                }
+
+                let Some((square, other)) = other else {
+                    children[state].push(Child::Known(0));
+                    continue;
+                };
+                let child_state = ((kings[0] * 64 + square) * 64 + kings[1]) * 2 + (child.get_turn() == Black) as usize;
+                children[state].push(if other == r#type {
+                    Child::Same(child_state, zeroing)
+                } else {
+                    let solution = promotions.iter().find(|&&(promoted, _)| promoted == other);
+                    Child::Known(solution.map_or(0, |(_, solution)| solution.wdl[child_state]))
+                });
+            }
+        }
+
+        // a position is won if a move leads to a lost one, and lost if every move leads to a won one
+        let value = |wdl: &[i8], child: &Child| match *child {
+            Child::Same(state, _) => wdl[state],
+            Child::Known(value) => value,
+        };
+        loop {
+            let mut changed = false;
+            for state in 0..STATES {
+                if wdl[state] != 0 || children[state].is_empty() {
+                    continue;
+                }
+                if children[state].iter().any(|child| value(&wdl, child) == -1) {
+                    wdl[state] = 1;
+                    changed = true;
+                } else if children[state].iter().all(|child| value(&wdl, child) == 1) {
+                    wdl[state] = -1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        // the winner takes the quickest way to mate or a zeroing move, the loser the slowest
+        let mut dtz: Vec<i32> = wdl.iter().zip(&children).map(|(&wdl, children)| if wdl == -1 && children.is_empty() { 0 } else { -1 }).collect();
+        loop {
+            let previous = dtz.clone();
+            let plies = |child: &Child| match *child {
+                Child::Same(state, false) => previous[state],
+                _ => 0,
+            };
+            for state in 0..STATES {
+                if previous[state] >= 0 || wdl[state].abs() != 1 {
+                    continue;
+                }
+                let mut moves = children[state].iter().filter(|child| value(&wdl, child) == -wdl[state]).map(plies);
+                let best = if wdl[state] == 1 {
+                    moves.filter(|&plies| plies >= 0).min()
+                } else {
+                    moves.try_fold(0, |max, plies| (plies >= 0).then_some(max.max(plies)))
+                };
+                if let Some(best) = best {
+                    dtz[state] = best + 1;
+                }
+            }
+            if dtz == previous {
+                break;
+            }
+        }
+        assert!(wdl.iter().zip(&dtz).all(|(&wdl, &dtz)| wdl.abs() != 1 || dtz >= 0));
+
+        Solution { wdl, dtz }
+    }
+
+    // one sub table, compressed the way PairsData reads it
+    #[derive(Default)]
+    struct Compressed {
+        sizes: Vec<u8>,
+        sparse_index: Vec<u8>,
+        block_lengths: Vec<u8>,
+        data: Vec<u8>,
+    }
+
+    const BLOCK_SIZE_LOG: u8 = 10;
+    const SPAN_LOG: u8 = 10;
+    const RUNS: usize = 5; // symbols for runs of 1, 2, 4, 8 and 16 equal values
+
+    fn compress(values: &[u16], flags: u8) -> Compressed {
+        if values.iter().all(|&value| value == values[0]) {
+            return Compressed { sizes: vec![flags | FLAG_SINGLE_VALUE, values[0] as u8], ..Default::default() };
+        }
+
+        // each run is a pair of the next shorter one, cut the values into the longest runs possible
+        let mut distinct = values.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let count = RUNS * distinct.len();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < values.len() {
+            let run = (0..RUNS).rev()
+                .find(|&run| values[i..].iter().take(1 << run).filter(|&&value| value == values[i]).count() == 1 << run)
+                .unwrap();
+            tokens.push((run * distinct.len() + distinct.binary_search(&values[i]).unwrap(), 1 << run));
+            i += 1 << run;
+        }
+
+        // huffman code lengths, every symbol gets one since the longer runs are made of the shorter ones
+        let mut weights = vec![1; count];
+        for &(symbol, _) in &tokens {
+            weights[symbol] += 1;
+        }
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> = weights.iter().enumerate().map(|(symbol, &weight)| Reverse((weight, symbol))).collect();
+        let mut parents = vec![usize::MAX; count];
+        while let (Some(Reverse((a, left))), Some(Reverse((b, right)))) = (heap.pop(), heap.pop()) {
+            parents.push(usize::MAX);
+            parents[left] = parents.len() - 1;
+            parents[right] = parents.len() - 1;
+            heap.push(Reverse((a + b, parents.len() - 1)));
+        }
+        let lengths: Vec<usize> = (0..count).map(|mut node| {
+            let mut length = 0;
+            while parents[node] != usize::MAX {
+                node = parents[node];
+                length += 1;
+            }
+            length
+        }).collect();
+        let (min, max) = (*lengths.iter().min().unwrap(), *lengths.iter().max().unwrap());
+        assert!(max <= 32);
+
+        // canonical codes, the longest ones get the lowest symbol numbers and the lowest values
+        let mut order: Vec<usize> = (0..count).collect();
+        order.sort_by_key(|&symbol| (Reverse(lengths[symbol]), symbol));
+        let mut numbers = vec![0; count];
+        for (number, &symbol) in order.iter().enumerate() {
+            numbers[symbol] = number as u64;
+        }
+        let with_length = |length| lengths.iter().filter(|&&other| other == length).count() as u64;
+        let mut lowest = vec![0; max + 1];
+        let mut base = vec![0; max + 1];
+        for length in (min..max).rev() {
+            lowest[length] = lowest[length + 1] + with_length(length + 1);
+            base[length] = (base[length + 1] + with_length(length + 1)) / 2;
+        }
+
+        let mut sizes = vec![flags, BLOCK_SIZE_LOG, SPAN_LOG, 1];
+        let mut blocks = Vec::new();
+        let mut bits = Vec::new();
+        let mut block = (0, 0); // first value and number of values
+        for &(symbol, run) in &tokens {
+            // a block holds at most 2^16 values, keep it well below so the sparse index offsets fit
+            if bits.len() - blocks.len() * (8 << BLOCK_SIZE_LOG) + lengths[symbol] > (8 << BLOCK_SIZE_LOG) - 64 || block.1 + run > 1 << 15 {
+                blocks.push(block);
+                block = (block.0 + block.1, 0);
+                bits.resize(blocks.len() * (8 << BLOCK_SIZE_LOG), false);
+            }
+            let code = base[lengths[symbol]] + numbers[symbol] - lowest[lengths[symbol]];
+            bits.extend((0..lengths[symbol]).rev().map(|bit| code >> bit & 1 == 1));
+            block.1 += run;
+        }
+        blocks.push(block);
+        bits.resize(blocks.len() * (8 << BLOCK_SIZE_LOG), false);
+
+        sizes.extend((blocks.len() as u32).to_le_bytes());
+        sizes.extend([max as u8, min as u8]);
+        for lowest in &lowest[min..=max] {
+            sizes.extend((*lowest as u16).to_le_bytes());
+        }
+        sizes.extend((count as u16).to_le_bytes());
+        for &symbol in &order {
+            let (run, value) = (symbol / distinct.len(), symbol % distinct.len());
+            let (left, right) = if run == 0 {
+                (distinct[value] as u32, 0xFFF)
+            } else {
+                let shorter = numbers[symbol - distinct.len()] as u32;
+                (shorter, shorter)
+            };
+            sizes.extend([left as u8, (left >> 8 | right << 4) as u8, (right >> 4) as u8]);
+        }
+        if count % 2 == 1 {
+            sizes.push(0);
+        }
+
+        let span = 1 << SPAN_LOG;
+        let mut sparse_index = Vec::new();
+        for k in 0..values.len().div_ceil(span) {
+            let middle = k * span + span / 2;
+            let block = blocks.partition_point(|&(first, _)| first <= middle) - 1;
+            sparse_index.extend((block as u32).to_le_bytes());
+            sparse_index.extend(((middle - blocks[block].0) as u16).to_le_bytes());
+        }
+
+        let mut block_lengths: Vec<u8> = blocks.iter().flat_map(|&(_, length)| (length as u16 - 1).to_le_bytes()).collect();
+        block_lengths.extend([0, 0]);
+        let data = bits.chunks(8).map(|byte| byte.iter().fold(0, |byte, &bit| byte << 1 | bit as u8)).collect();
+
+        Compressed { sizes, sparse_index, block_lengths, data }
+    }
+
+    // the sub tables go by file of the lead pawn, then by side to move
+    fn get_file(table: &Table, dtz: bool, codes: &[u8], subtables: &[Compressed]) -> Vec<u8> {
+        let files = if table.has_pawns { 4 } else { 1 };
+        let mut bytes = if dtz { DTZ_MAGIC } else { WDL_MAGIC }.to_vec();
+        bytes.push((subtables.len() / files == 2) as u8 | (table.has_pawns as u8) << 1);
+        for _ in 0..files {
+            bytes.push(0);
+            bytes.extend(codes.iter().map(|&code| code | code << 4));
+        }
+        bytes.resize(bytes.len().next_multiple_of(2), 0);
+
+        for subtable in subtables {
+            bytes.extend(&subtable.sizes);
+        }
+        if dtz {
+            bytes.resize(bytes.len().next_multiple_of(2), 0);
+        }
+        for subtable in subtables {
+            bytes.extend(&subtable.sparse_index);
+        }
+        for subtable in subtables {
+            bytes.extend(&subtable.block_lengths);
+        }
+        for subtable in subtables {
+            bytes.resize(bytes.len().next_multiple_of(64), 0);
+            bytes.extend(&subtable.data);
+        }
+        bytes
+    }
+
+    // writes the .rtbw and .rtbz files, without a solution every position is a draw
+    fn write_tables(directory: &Path, r#type: Pieces, solution: Option<&Solution>) {
+        let pieces = [Piece::from(r#type, White), Piece::from(King, White), Piece::from(King, Black)];
+        let name = get_key(&pieces);
+        let table = Table::new(&name, PathBuf::new(), PathBuf::new()).unwrap();
+        let codes = pieces.map(get_code);
+        let files = if table.has_pawns { 4 } else { 1 };
+
+        for dtz in [false, true] {
+            // only dtz tables for white to move, in plies, black never wins in these
+            let flags = if dtz { FLAG_WIN_PLIES | FLAG_LOSS_PLIES } else { 0 };
+            let sides = if dtz { 1 } else { 2 };
+            let draw = if dtz { 0 } else { 2 };
+
+            // a file of single value tables to work out the indexes with
+            let empty: Vec<Compressed> = (0..files * sides).map(|_| compress(&[draw], flags)).collect();
+            let data = table.parse(get_file(&table, dtz, &codes, &empty), dtz).unwrap();
+            let mut values: Vec<Vec<Option<u16>>> = (0..files * sides).map(|i| {
+                let pairs = data.get(i % sides, i / sides);
+                vec![None; pairs.group_idx[pairs.group_len.iter().position(|&len| len == 0).unwrap()] as usize]
+            }).collect();
+
+            for (state, &wdl) in solution.map_or(&[][..], |solution| &solution.wdl).iter().enumerate() {
+                if wdl == ILLEGAL {
+                    continue;
+                }
+                let board = get_position(r#type, state, false).unwrap();
+                let Some((side, file, idx)) = table.encode(&data, &board, dtz) else { continue };
+                let value = match dtz {
+                    false => (wdl * 2 + 2) as u16,
+                    true if wdl == 1 => solution.unwrap().dtz[state] as u16 - 1,
+                    true => continue,
+                };
+                let stored = &mut values[file * sides + side][idx as usize];
+                assert!(stored.is_none_or(|stored| stored == value), "{} has two values", board.get_fen());
+                *stored = Some(value);
+            }
+
+            // impossible positions take the value before them, it compresses best
+            let subtables = values.iter().map(|values| {
+                let mut last = values.iter().flatten().next().copied().unwrap_or(draw);
+                let values: Vec<u16> = values.iter().map(|&value| {
+                    last = value.unwrap_or(last);
+                    last
+                }).collect();
+                compress(&values, flags)
+            }).collect::<Vec<_>>();
+            let extension = if dtz { "rtbz" } else { "rtbw" };
+            fs::write(directory.join(format!("{name}.{extension}")), get_file(&table, dtz, &codes, &subtables)).unwrap();
+        }
+    }
+
+    // cargo test --release generate_tables -- --ignored, checks every position against the solution afterwards
+    #[test]
+    #[ignore]
+    fn generate_tables() {
+        let queen = solve(Queen, &[]);
+        let rook = solve(Rook, &[]);
+        let pawn = solve(Pawn, &[(Queen, &queen), (Rook, &rook)]);
+
+        let directory = Path::new(TABLES);
+        fs::create_dir_all(directory).unwrap();
+        for (r#type, solution) in [(Queen, Some(&queen)), (Rook, Some(&rook)), (Bishop, None), (Knight, None), (Pawn, Some(&pawn))] {
+            write_tables(directory, r#type, solution);
+        }
+
+        let tablebase = get_tablebase();
+        for (r#type, solution) in [(Queen, &queen), (Rook, &rook), (Pawn, &pawn)] {
+            for state in 0..STATES {
+                let (Some(board), Some(flipped)) = (get_position(r#type, state, false), get_position(r#type, state, true)) else { continue };
+                let (wdl, dtz) = match solution.wdl[state] {
+                    1 => (Wdl::Win, solution.dtz[state]),
+                    -1 => (Wdl::Loss, -solution.dtz[state].max(1)),
+                    _ => (Wdl::Draw, 0),
+                };
+                for board in [board, flipped] {
+                    assert_eq!(tablebase.probe_wdl(&board), Ok(wdl), "{}", board.get_fen());
+                    assert_eq!(tablebase.probe_dtz(&board), Ok(dtz), "{}", board.get_fen());
+                }
+            }
+        }
+    }
+
+    // the tables have to agree with what the moves from the position lead to
+    fn check_moves(tablebase: &Tablebase, board: &Board) {
+        let wdl = tablebase.probe_wdl(board).unwrap();
+        let dtz = tablebase.probe_dtz(board).unwrap();
+        let moves = board.get_legal_moves();
+        if moves.is_empty() {
+            let expected = if board.in_check(board.get_turn()) { (Wdl::Loss, -1) } else { (Wdl::Draw, 0) };
+            assert_eq!((wdl, dtz), expected, "{}", board.get_fen());
+            return;
+        }
+
+        // plies to mate or the next capture or pawn move through each move
+        let mut results = Vec::new();
+        for r#move in moves {
+            let mut child = *board;
+            child.apply_move(r#move);
+            let mated = child.in_check(child.get_turn()) && child.get_legal_moves().is_empty();
+            let plies = if is_zeroing(board, r#move) || mated { 1 } else { tablebase.probe_dtz(&child).unwrap().abs() + 1 };
+            results.push((-tablebase.probe_wdl(&child).unwrap(), plies));
+        }
+
+        let best = results.iter().map(|&(wdl, _)| wdl).max().unwrap();
+        let expected = match best {
+            Wdl::Win => results.iter().filter(|&&(wdl, _)| wdl == Wdl::Win).map(|&(_, plies)| plies).min().unwrap(),
+            Wdl::Loss => -results.iter().map(|&(_, plies)| plies).max().unwrap(),
+            _ => 0,
+        };
+        assert_eq!((wdl, dtz), (best, expected), "{}", board.get_fen());
+    }
+
+    fn check_known_positions(tablebase: &Tablebase) {
+        let positions = [
+            ("k7/8/1K6/8/8/8/8/7R w - - 0 1", Wdl::Win, Some(1)), // Rh8#
+            ("k6R/8/1K6/8/8/8/8/8 b - - 0 1", Wdl::Loss, Some(-1)), // mated
+            ("k7/8/1Q6/8/8/8/8/7K b - - 0 1", Wdl::Draw, Some(0)), // stalemate
+            ("8/8/8/8/8/8/kR6/7K b - - 0 1", Wdl::Draw, Some(0)), // the rook hangs
+            ("8/8/8/8/8/8/Kr6/7k w - - 0 1", Wdl::Draw, Some(0)),
+            ("7K/5k2/8/8/8/8/8/6q1 w - - 0 1", Wdl::Loss, None),
+            ("8/8/8/4k3/8/8/8/R3K3 w - - 0 1", Wdl::Win, None),
+            ("k7/8/8/8/8/8/P7/K7 w - - 0 1", Wdl::Draw, Some(0)), // the king holds the corner
+            ("7k/8/8/8/8/P7/8/K7 w - - 0 1", Wdl::Win, Some(1)), // outside the square of the pawn
+            ("k7/8/p7/8/8/8/8/7K b - - 0 1", Wdl::Win, Some(1)),
+            ("4k3/8/8/8/8/P7/8/7K w - - 0 1", Wdl::Draw, Some(0)), // inside it
+            ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Wdl::Win, None), // king on the sixth in front of the pawn
+            ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Wdl::Loss, None),
+            ("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", Wdl::Draw, Some(0)), // stalemate
+        ];
+
+        for (fen, wdl, dtz) in positions {
+            let board = get_board(fen);
+            assert_eq!(tablebase.probe_wdl(&board), Ok(wdl), "{fen}");
+            if let Some(dtz) = dtz {
+                assert_eq!(tablebase.probe_dtz(&board), Ok(dtz), "{fen}");
+            }
+        }
+    }
+
+    #[test]
+    fn probes_known_positions() {
+        check_known_positions(&get_tablebase());
+    }
+
+    // positions from a few material combinations, scattered over the board by a fixed sequence
+    fn get_sample(pieces: &[Piece], count: usize) -> Vec<Board> {
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut boards = Vec::new();
+        while boards.len() < count {
+            let mut squares = Vec::new();
+            while squares.len() < pieces.len() {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                let square = (seed % 64) as u8;
+                if !squares.contains(&square) {
+                    squares.push(square);
+                }
+            }
+
+            let mut setup = [None; 64];
+            for (&piece, &square) in pieces.iter().zip(&squares) {
+                setup[square as usize] = Some(piece);
+            }
+            let board = get_setup(&setup, if seed & 1 == 0 { White } else { Black });
+            let pawn_on_edge = pieces.iter().zip(&squares).any(|(piece, &square)| piece.r#type == Pawn && !(8..56).contains(&square));
+            if !pawn_on_edge && !board.in_check(!board.get_turn()) {
+                boards.push(board);
+            }
+        }
+        boards
+    }
+
+    #[test]
+    #[ignore]
+    fn official_tables() {
+        let path = std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH should point at the official tables");
+        let mut official = Tablebase::new();
+        official.add_directory(path).unwrap();
+        assert!(official.max_pieces() >= 4, "the 3 and 4 piece tables are needed");
+        check_known_positions(&official);
+
+        // every position of the generated tables
+        let generated = get_tablebase();
+        for r#type in [Queen, Rook, Bishop, Knight, Pawn] {
+            for flipped in [false, true] {
+                for state in 0..STATES {
+                    let Some(board) = get_position(r#type, state, flipped) else { continue };
+                    assert_eq!(official.probe_wdl(&board), generated.probe_wdl(&board), "{}", board.get_fen());
+                    assert_eq!(official.probe_dtz(&board), generated.probe_dtz(&board), "{}", board.get_fen());
+                }
+            }
+        }
+
+        // 4 pieces, with and without pawns, against the moves from each position
+        let piece = |c: char| Piece::from_char(c).unwrap();
+        for material in ["KQkr", "KRkn", "KRkb", "KBNk", "KRRk", "KNNk", "KPkp", "KPPk", "KQkp", "KRkp"] {
+            let pieces: Vec<Piece> = material.chars().map(piece).collect();
+            for board in get_sample(&pieces, 2000) {
+                check_moves(&official, &board);
+            }
+        }
+
+        // mated with a knight that can't help, and a queen that mates or takes the rook
+        for (fen, wdl, dtz) in [("k6R/8/1K6/8/8/8/8/7n b - - 0 1", Wdl::Loss, -1), ("k7/8/1K6/8/8/8/r7/Q7 w - - 0 1", Wdl::Win, 1)] {
+            let board = get_board(fen);
+            assert_eq!((official.probe_wdl(&board), official.probe_dtz(&board)), (Ok(wdl), Ok(dtz)), "{fen}");
+        }
+    }
+
+    #[test]
+    fn agrees_with_the_moves_from_each_position() {
+        let tablebase = get_tablebase();
+        for r#type in [Queen, Rook, Pawn] {
+            for flipped in [false, true] {
+                for state in (0..STATES).step_by(1709) {
+                    if let Some(board) = get_position(r#type, state, flipped) {
+                        check_moves(&tablebase, &board);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filters_root_moves() {
+        let tablebase = get_tablebase();
+        let board = get_board("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+        assert_eq!(tablebase.filter_root_moves(&board), Ok(vec![Move::from_uci("h1h8").unwrap()]));
+
+        // the winning moves that get closest to mate
+        for fen in ["8/8/8/4k3/8/8/8/R3K3 w - - 0 1", "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", "8/8/2k5/8/8/8/5p2/7K b - - 0 1"] {
+            let board = get_board(fen);
+            let mut results = Vec::new();
+            for r#move in board.get_legal_moves() {
+                let mut child = board;
+                child.apply_move(r#move);
+                if tablebase.probe_wdl(&child) == Ok(Wdl::Loss) {
+                    let plies = if is_zeroing(&board, r#move) { 1 } else { 1 - tablebase.probe_dtz(&child).unwrap() };
+                    results.push((plies, r#move));
+                }
+            }
+            let quickest = results.iter().map(|&(plies, _)| plies).min().unwrap();
+            let expected: Vec<Move> = results.into_iter().filter(|&(plies, _)| plies == quickest).map(|(_, r#move)| r#move).collect();
+
+            let mut moves = tablebase.filter_root_moves(&board).unwrap();
+            moves.sort_by_key(|r#move| r#move.to_string());
+            assert_eq!(moves.iter().map(Move::to_string).collect::<Vec<_>>(), expected.iter().map(Move::to_string).collect::<Vec<_>>(), "{fen}");
+        }
+
+        // all moves draw
+        let board = get_board("k7/8/8/8/8/8/P7/K7 b - - 0 1");
+        assert_eq!(tablebase.filter_root_moves(&board).unwrap().len(), board.get_legal_moves().len());
+    }
+
+    #[test]
+    fn curses_wins_past_the_fifty_move_rule() {
+        let tablebase = get_tablebase();
+        let board = get_board("8/8/8/4k3/8/8/8/R3K3 w - - 0 1");
+        let dtz = tablebase.probe_dtz(&board).unwrap();
+        let best = tablebase.rank_root_moves(&board).unwrap()[0];
+        assert_eq!((best.wdl, best.dtz, best.rank), (Wdl::Win, dtz, 1000));
+
+        // the mate is still there, but not before the 50 move rule runs out
+        let board = get_board(&format!("8/8/8/4k3/8/8/8/R3K3 w - - {} 60", 100 - dtz));
+        assert_eq!(tablebase.probe_wdl(&board), Ok(Wdl::Win));
+        let best = tablebase.rank_root_moves(&board).unwrap()[0];
+        assert_eq!((best.wdl, best.dtz), (Wdl::CursedWin, dtz));
+        assert!(best.rank > 0 && best.rank < 1000);
+        assert_eq!(tablebase.filter_root_moves(&board), tablebase.filter_root_moves(&get_board("8/8/8/4k3/8/8/8/R3K3 w - - 0 1")));
+
+        // one ply earlier it can still be won
+        let board = get_board(&format!("8/8/8/4k3/8/8/8/R3K3 w - - {} 60", 99 - dtz));
+        assert_eq!(tablebase.rank_root_moves(&board).unwrap()[0].wdl, Wdl::Win);
+    }
+}
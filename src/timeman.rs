@@ -0,0 +1,227 @@
+use std::{
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+use crate::{board::Color, search::Limits};
+
+// turns the clock into time limits for a single search
+
+// kept in reserve for GUI and network lag so the flag doesn't fall while the move is in transit
+pub const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+
+// assumed number of moves left in the game when the time control doesn't say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+// stop and ponderhit can be signalled from another thread while a search is running
+#[derive(Clone, Debug, Default)]
+pub struct Signals {
+    stop: Arc<AtomicBool>,
+    ponderhit: Arc<AtomicBool>,
+}
+
+impl Signals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the search returns the best move found so far as soon as it notices
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    // the opponent played the expected move, so the ponder search switches to the normal time limits
+    pub fn ponderhit(&self) {
+        self.ponderhit.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_ponderhit(&self) -> bool {
+        self.ponderhit.load(Ordering::Relaxed)
+    }
+
+    // for reusing the same signals on the next search
+    pub fn reset(&self) {
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponderhit.store(false, Ordering::Relaxed);
+    }
+}
+
+pub struct TimeManager {
+    start: Instant,
+    soft: Option<Duration>, // don't start another iteration after this
+    hard: Option<Duration>, // abort the running iteration after this
+    infinite: bool,
+    pondering: bool,
+    signals: Option<Signals>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &Limits, turn: Color) -> Self {
+        let (soft, hard) = Self::allocate(limits, turn);
+        TimeManager {
+            start: Instant::now(),
+            soft,
+            hard,
+            infinite: limits.infinite,
+            pondering: limits.ponder,
+            signals: limits.signals.clone(),
+        }
+    }
+
+    // soft and hard limits for the side to move, None for unlimited
+    pub fn allocate(limits: &Limits, turn: Color) -> (Option<Duration>, Option<Duration>) {
+        if let Some(movetime) = limits.movetime {
+            return (Some(movetime), Some(movetime));
+        }
+
+        let (remaining, increment) = match turn {
+            Color::White => (limits.wtime, limits.winc),
+            Color::Black => (limits.btime, limits.binc),
+        };
+        let Some(remaining) = remaining else { return (None, None) };
+        let increment = increment.unwrap_or_default();
+
+        let available = remaining.saturating_sub(MOVE_OVERHEAD);
+        let moves_to_go = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, 50);
+
+        // an even share of the clock plus most of the increment, with room to overrun on hard positions
+        let soft = available / moves_to_go + increment * 3 / 4;
+        let hard = if moves_to_go == 1 { available * 9 / 10 } else { (soft * 4).min(available / 2) };
+
+        (Some(soft.min(hard)), Some(hard))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn update_ponder(&mut self) {
+        if self.pondering && self.signals.as_ref().is_some_and(Signals::is_ponderhit) {
+            // the clock starts now that the opponent has actually moved
+            self.pondering = false;
+            self.start = Instant::now();
+        }
+    }
+
+    // checked between nodes, the search throws away the unfinished iteration
+    pub fn should_abort(&mut self) -> bool {
+        if self.signals.as_ref().is_some_and(Signals::is_stopped) {
+            return true;
+        }
+
+        self.update_ponder();
+        !self.pondering && self.hard.is_some_and(|hard| self.elapsed() >= hard)
+    }
+
+    // checked between iterations, a new one is unlikely to finish once the soft limit has passed
+    pub fn should_start_iteration(&mut self) -> bool {
+        if self.should_abort() {
+            return false;
+        }
+
+        self.pondering || self.soft.is_none_or(|soft| self.elapsed() < soft)
+    }
+
+    // infinite and ponder searches must not return until told to stop, even once they run out of depth
+    pub fn must_wait(&mut self) -> bool {
+        self.update_ponder();
+        self.infinite || self.pondering
+    }
+
+    pub fn wait_for_stop(&mut self) {
+        while self.must_wait() && !self.signals.as_ref().is_some_and(Signals::is_stopped) {
+            if self.signals.is_none() {
+                // nobody could ever stop us
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_limits(wtime: u64, winc: u64, movestogo: Option<u32>) -> Limits {
+        Limits {
+            wtime: Some(Duration::from_millis(wtime)),
+            winc: Some(Duration::from_millis(winc)),
+            btime: Some(Duration::from_millis(3030)),
+            movestogo,
+            ..Default::default()
+        }
+    }
+
+    fn allocate(limits: &Limits, turn: Color) -> (u128, u128) {
+        let (soft, hard) = TimeManager::allocate(limits, turn);
+        (soft.unwrap().as_millis(), hard.unwrap().as_millis())
+    }
+
+    #[test]
+    fn allocates_time() {
+        // a thirtieth of the clock less the overhead, plus three quarters of the increment
+        assert_eq!(allocate(&get_limits(60_000, 1000, None), Color::White), (1999 + 750, (1999 + 750) * 4));
+        assert_eq!(allocate(&get_limits(60_000, 0, None), Color::White), (1999, 1999 * 4));
+        assert_eq!(allocate(&get_limits(60_000, 0, None), Color::Black), (100, 400));
+
+        // until the time control, but never more than half the clock
+        assert_eq!(allocate(&get_limits(10_030, 0, Some(10)), Color::White), (1000, 4000));
+        assert_eq!(allocate(&get_limits(10_030, 3000, Some(2)), Color::White), (5000, 5000));
+        // the last move before the time control can use almost everything
+        assert_eq!(allocate(&get_limits(10_030, 0, Some(1)), Color::White), (9000, 9000));
+        assert_eq!(allocate(&get_limits(10_030, 0, Some(0)), Color::White), (9000, 9000));
+        assert_eq!(allocate(&get_limits(10_030, 0, Some(500)), Color::White), (200, 800));
+
+        // nothing left but the increment
+        assert_eq!(allocate(&get_limits(20, 1000, None), Color::White), (0, 0));
+
+        let movetime = Limits::movetime(Duration::from_millis(250));
+        assert_eq!(allocate(&movetime, Color::Black), (250, 250));
+        assert_eq!(TimeManager::allocate(&Limits::depth(5), Color::White), (None, None));
+    }
+
+    #[test]
+    fn stops_on_signals() {
+        let signals = Signals::new();
+        let limits = Limits { infinite: true, signals: Some(signals.clone()), ..Limits::movetime(Duration::ZERO) };
+        let mut manager = TimeManager::new(&limits, Color::White);
+        assert!(manager.must_wait());
+
+        let stopper = thread::spawn({
+            let signals = signals.clone();
+            move || {
+                thread::sleep(Duration::from_millis(50));
+                signals.stop();
+            }
+        });
+        manager.wait_for_stop();
+        assert!(signals.is_stopped() && manager.elapsed() >= Duration::from_millis(50));
+        assert!(manager.should_abort() && !manager.should_start_iteration());
+        stopper.join().unwrap();
+
+        // an infinite search nobody can stop doesn't wait
+        let mut manager = TimeManager::new(&Limits { infinite: true, ..Default::default() }, Color::White);
+        manager.wait_for_stop();
+        assert!(!manager.should_abort() && manager.should_start_iteration());
+    }
+
+    #[test]
+    fn ponderhit_starts_the_clock() {
+        let signals = Signals::new();
+        let limits = Limits { ponder: true, signals: Some(signals.clone()), ..Limits::movetime(Duration::ZERO) };
+        let mut manager = TimeManager::new(&limits, Color::White);
+        assert!(manager.must_wait() && !manager.should_abort() && manager.should_start_iteration());
+
+        signals.ponderhit();
+        assert!(!manager.must_wait() && manager.should_abort());
+        manager.wait_for_stop();
+
+        signals.reset();
+        assert!(!signals.is_stopped() && !signals.is_ponderhit());
+    }
+}
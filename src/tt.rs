@@ -0,0 +1,298 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::{board::Move, pieces::Pieces::{self, *}, search::{MAX_PLY, TB_WIN}};
+
+// transposition table, caches search results by position hash (see Board::get_hash)
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Bound {
+    #[default]
+    Exact,
+    Lower, // the real score is at least this, the search failed high
+    Upper, // the real score is at most this, the search failed low
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64, // 0 for empty slots
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+    age: u8,
+}
+
+// each bucket has a depth-preferred slot and an always-replace slot
+const BUCKET_SIZE: usize = 2;
+
+// every field of an entry packed into one word, so it can be written with a single atomic store
+fn pack(entry: &Entry) -> u64 {
+    let r#move = entry.best_move.map_or(0, |r#move| {
+        let promotion = r#move.promotion.map_or(0, |r#type| r#type as u64 + 1);
+        let drop = r#move.drop.map_or(0, |r#type| r#type as u64 + 1);
+        1 | (r#move.from as u64) << 1 | (r#move.to as u64) << 9 | promotion << 17 | drop << 54
+    });
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    r#move | (entry.score as i16 as u16 as u64) << 20 | (entry.depth as u64) << 36 | bound << 44 | (entry.age as u64) << 46
+}
+
+// 3 bits, 0 for none and Pieces + 1 otherwise
+fn unpack_piece(bits: u64) -> Option<Pieces> {
+    match bits & 7 {
+        1 => Some(Pawn),
+        2 => Some(Rook),
+        3 => Some(Knight),
+        4 => Some(Bishop),
+        5 => Some(Queen),
+        6 => Some(King),
+        _ => None,
+    }
+}
+
+fn unpack(key: u64, data: u64) -> Entry {
+    let best_move = (data & 1 != 0).then_some(Move {
+        from: (data >> 1) as u8,
+        to: (data >> 9) as u8,
+        promotion: unpack_piece(data >> 17),
+        drop: unpack_piece(data >> 54),
+    });
+    let bound = match (data >> 44) & 3 {
+        1 => Bound::Lower,
+        2 => Bound::Upper,
+        _ => Bound::Exact,
+    };
+    Entry { key, best_move, score: (data >> 20) as u16 as i16 as i32, depth: (data >> 36) as u8, bound, age: (data >> 46) as u8 }
+}
+
+// threads read and write slots without locking, the key is stored xored with the data so a slot
+// that was half written by one thread while another read it just looks like a different position
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    fn load(&self) -> Entry {
+        let data = self.data.load(Ordering::Relaxed);
+        unpack(self.key.load(Ordering::Relaxed) ^ data, data)
+    }
+
+    fn save(&self, entry: &Entry) {
+        let data = pack(entry);
+        self.key.store(entry.key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+// shared between the threads of a parallel search, see search::Limits::threads
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    age: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> Self {
+        let mut table = TranspositionTable { slots: Vec::new(), age: 0 };
+        table.resize(megabytes);
+        table
+    }
+
+    // also clears the table
+    pub fn resize(&mut self, megabytes: usize) {
+        let buckets = (megabytes.max(1) * 1024 * 1024 / (std::mem::size_of::<Slot>() * BUCKET_SIZE)).max(1);
+        self.slots = (0..buckets * BUCKET_SIZE).map(|_| Slot::default()).collect();
+        self.age = 0;
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot = Slot::default();
+        }
+        self.age = 0;
+    }
+
+    // entries from older searches get replaced first
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    fn bucket(&self, hash: u64) -> usize {
+        (hash % (self.slots.len() / BUCKET_SIZE) as u64) as usize * BUCKET_SIZE
+    }
+
+    // ply is the distance from the search root to this position, for adjusting mate scores
+    pub fn probe(&self, hash: u64, ply: u8) -> Option<Entry> {
+        self.find(hash).map(|entry| Entry { score: score_from_tt(entry.score, ply), ..entry })
+    }
+
+    fn find(&self, hash: u64) -> Option<Entry> {
+        let bucket = self.bucket(hash);
+        self.slots[bucket..bucket + BUCKET_SIZE].iter().map(Slot::load).find(|entry| entry.key == hash && hash != 0)
+    }
+
+    pub fn store(&self, hash: u64, best_move: Option<Move>, score: i32, depth: u8, bound: Bound, ply: u8) {
+        let bucket = self.bucket(hash);
+        let age = self.age;
+
+        // keep the old best move if this search didn't find one, it's still the best guess we have
+        let existing = self.find(hash);
+        let best_move = best_move.or(existing.and_then(|entry| entry.best_move));
+
+        let preferred = self.slots[bucket].load();
+        let slot = if preferred.key == hash || preferred.key == 0 || preferred.age != age || depth >= preferred.depth {
+            bucket
+        } else {
+            bucket + 1
+        };
+
+        self.slots[slot].save(&Entry { key: hash, best_move, score: score_to_tt(score, ply), depth, bound, age });
+
+        // don't leave a stale copy of this position in the other slot
+        for i in bucket..bucket + BUCKET_SIZE {
+            if i != slot && self.slots[i].load().key == hash {
+                self.slots[i].save(&Entry::default());
+            }
+        }
+    }
+
+    // permill of slots used by the current search, as reported by uci hashfull
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample].iter().map(Slot::load).filter(|entry| entry.key != 0 && entry.age == self.age).count();
+        (used * 1000 / sample) as u32
+    }
+}
+
+// mate and tablebase scores are stored as distance from the stored position instead of from the root,
+// so they stay correct when the position is reached again at a different ply
+pub fn score_to_tt(score: i32, ply: u8) -> i32 {
+    match score {
+        score if score >= TB_WIN - MAX_PLY as i32 => score + ply as i32,
+        score if score <= -TB_WIN + MAX_PLY as i32 => score - ply as i32,
+        score => score,
+    }
+}
+
+pub fn score_from_tt(score: i32, ply: u8) -> i32 {
+    match score {
+        score if score >= TB_WIN - MAX_PLY as i32 => score - ply as i32,
+        score if score <= -TB_WIN + MAX_PLY as i32 => score + ply as i32,
+        score => score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{INFINITY, MATE};
+
+    #[test]
+    fn packs_entries() {
+        let moves = [
+            None,
+            Some(Move::new(0x00, 0x77)),
+            Some(Move { promotion: Some(Knight), ..Move::new(0x16, 0x07) }),
+            Some(Move { promotion: Some(King), ..Move::new(0x61, 0x70) }),
+            Some(Move::new_drop(Queen, 0x34)),
+            Some(Move::new_drop(Pawn, 0x45)),
+        ];
+        for (i, best_move) in moves.into_iter().enumerate() {
+            for (score, bound) in [(0, Bound::Exact), (-INFINITY, Bound::Upper), (MATE - 3, Bound::Lower), (i16::MIN as i32, Bound::Exact)] {
+                let entry = Entry { key: 0xdead_beef_0000_0001u64.wrapping_mul(i as u64), best_move, score, depth: 255 - i as u8, bound, age: 200 + i as u8 };
+                assert_eq!(unpack(entry.key, pack(&entry)), entry);
+
+                let slot = Slot::default();
+                slot.save(&entry);
+                assert_eq!(slot.load(), entry);
+            }
+        }
+    }
+
+    #[test]
+    fn adjusts_mate_scores() {
+        // mate in 5 plies from the root, found 3 plies in, is a mate in 2 from there
+        assert_eq!(score_to_tt(MATE - 5, 3), MATE - 2);
+        assert_eq!(score_from_tt(MATE - 2, 3), MATE - 5);
+        assert_eq!(score_from_tt(MATE - 2, 7), MATE - 9);
+        assert_eq!(score_to_tt(-MATE + 6, 4), -MATE + 2);
+        assert_eq!(score_from_tt(-MATE + 2, 1), -MATE + 3);
+        assert_eq!(score_to_tt(TB_WIN - 10, 4), TB_WIN - 6);
+        // normal scores aren't touched
+        for score in [0, 250, -1800, TB_WIN - MAX_PLY as i32 - 1] {
+            assert_eq!(score_to_tt(score, 10), score);
+            assert_eq!(score_from_tt(score, 10), score);
+        }
+
+        let table = TranspositionTable::new(1);
+        table.store(42, None, MATE - 5, 4, Bound::Exact, 3);
+        assert_eq!(table.probe(42, 3).unwrap().score, MATE - 5);
+        assert_eq!(table.probe(42, 1).unwrap().score, MATE - 3);
+    }
+
+    #[test]
+    fn replaces_by_depth_and_age() {
+        let mut table = TranspositionTable::new(1);
+        let buckets = (table.slots.len() / BUCKET_SIZE) as u64;
+        // all in the same bucket
+        let [a, b, c, d, e] = [5, 5 + buckets, 5 + 2 * buckets, 5 + 3 * buckets, 5 + 4 * buckets];
+        let r#move = Some(Move::new(0x14, 0x34));
+
+        table.store(a, r#move, 10, 5, Bound::Exact, 0);
+        table.store(b, None, 20, 3, Bound::Lower, 0);
+        assert_eq!(table.probe(a, 0).map(|entry| (entry.score, entry.depth, entry.best_move)), Some((10, 5, r#move)));
+        assert_eq!(table.probe(b, 0).map(|entry| (entry.score, entry.bound)), Some((20, Bound::Lower)));
+
+        // shallower entries go in the always-replace slot, deeper ones in the depth-preferred one
+        table.store(c, None, 30, 2, Bound::Upper, 0);
+        assert!(table.probe(a, 0).is_some() && table.probe(b, 0).is_none() && table.probe(c, 0).is_some());
+        table.store(d, None, 40, 7, Bound::Exact, 0);
+        assert!(table.probe(a, 0).is_none() && table.probe(c, 0).is_some() && table.probe(d, 0).is_some());
+
+        // a new search replaces deeper entries of the last one
+        table.new_search();
+        table.store(e, None, 50, 1, Bound::Exact, 0);
+        assert!(table.probe(d, 0).is_none() && table.probe(e, 0).is_some());
+        // and only that one counts as used by this search
+        assert_eq!(table.hashfull(), 1);
+
+        // storing a position again keeps its best move, and only one copy of it
+        table.store(c, r#move, 30, 2, Bound::Upper, 0);
+        table.store(c, None, 35, 9, Bound::Exact, 0);
+        assert_eq!(table.probe(c, 0).map(|entry| (entry.score, entry.depth, entry.best_move)), Some((35, 9, r#move)));
+        assert_eq!(table.slots.iter().filter(|slot| slot.load().key == c).count(), 1);
+        assert!(table.probe(e, 0).is_none());
+
+        table.clear();
+        assert!(table.probe(c, 0).is_none());
+        assert_eq!(table.age, 0);
+    }
+
+    #[test]
+    fn resizes() {
+        let mut table = TranspositionTable::new(1);
+        assert_eq!(table.slots.len() * std::mem::size_of::<Slot>(), 1 << 20);
+        table.store(7, None, 1, 1, Bound::Exact, 0);
+        table.new_search();
+
+        table.resize(4);
+        assert_eq!(table.slots.len() * std::mem::size_of::<Slot>(), 4 << 20);
+        assert!(table.probe(7, 0).is_none());
+        assert_eq!(table.age, 0);
+        // there's always at least one bucket
+        table.resize(0);
+        assert_eq!(table.slots.len() * std::mem::size_of::<Slot>(), 1 << 20);
+
+        for hash in 1..=1000 {
+            table.store(hash, None, 0, 1, Bound::Exact, 0);
+        }
+        // hashes 1 to 499 land in the first 1000 slots that get sampled
+        assert_eq!(table.hashfull(), 499);
+        for hash in 1..=1000 {
+            assert_eq!(table.probe(hash, 0).map(|entry| entry.key), Some(hash));
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    execute, queue,
+    style::{Color as TermColor, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use crate::{
+    board::{Board, Color::{self, *}, Move, Square},
+    clock::Clock,
+    game::Game,
+    pieces::{Piece, Pieces::{self, *}},
+    search::{search_with_table, Limits, DEFAULT_HASH_MB},
+    tt::TranspositionTable,
+    variant::Variant,
+};
+
+// full screen board for playing games in the terminal, with the keyboard or the mouse
+
+const LIGHT: TermColor = TermColor::Rgb { r: 240, g: 217, b: 181 };
+const DARK: TermColor = TermColor::Rgb { r: 181, g: 136, b: 99 };
+const LAST_MOVE_LIGHT: TermColor = TermColor::Rgb { r: 205, g: 210, b: 106 };
+const LAST_MOVE_DARK: TermColor = TermColor::Rgb { r: 170, g: 162, b: 58 };
+const SELECTED: TermColor = TermColor::Rgb { r: 130, g: 151, b: 105 };
+const CURSOR: TermColor = TermColor::Rgb { r: 100, g: 140, b: 200 };
+const CHECK: TermColor = TermColor::Rgb { r: 220, g: 80, b: 80 };
+const CAPTURE: TermColor = TermColor::Rgb { r: 200, g: 130, b: 110 };
+const DOT: TermColor = TermColor::Rgb { r: 90, g: 90, b: 90 };
+const WHITE_PIECE: TermColor = TermColor::Rgb { r: 255, g: 255, b: 255 };
+const BLACK_PIECE: TermColor = TermColor::Rgb { r: 0, g: 0, b: 0 };
+
+// where things go on the screen, every square is three columns wide
+const BOARD_LEFT: u16 = 3;
+const BOARD_TOP: u16 = 1;
+const PANEL_LEFT: u16 = BOARD_LEFT + 8 * 3 + 4;
+const PANEL_ROWS: usize = 10;
+const STATUS_ROW: u16 = BOARD_TOP + 11;
+
+// a question waiting for a key
+enum Prompt {
+    Promotion(Vec<Move>), // the same move with each piece it can promote to
+    Drop,
+}
+
+pub struct Tui {
+    game: Game,
+    cursor: u8,
+    selected: Option<u8>,
+    flipped: bool, // black at the bottom
+    prompt: Option<Prompt>,
+    thinking: bool, // the engine moves once the screen shows it's thinking
+    message: String,
+    tt: TranspositionTable, // kept between engine moves
+}
+
+// takes over the terminal until the user quits, and gives it back the way it was
+pub fn run(game: Game) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, event::EnableMouseCapture, cursor::Hide, terminal::Clear(ClearType::All))?;
+    let result = Tui::new(game).event_loop(&mut stdout);
+    execute!(stdout, event::DisableMouseCapture, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn get_index(coord: u8) -> usize {
+    let (x, y) = Square::u8_to_tuple(coord);
+    y as usize * 8 + x as usize
+}
+
+fn get_piece_type(c: char) -> Option<Pieces> {
+    Some(match c.to_ascii_lowercase() {
+        'p' => Pawn,
+        'n' => Knight,
+        'b' => Bishop,
+        'r' => Rook,
+        'q' => Queen,
+        'k' => King,
+        _ => return None,
+    })
+}
+
+// 4:05.3, tenths only once it gets tight
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds < 20 {
+        format!("{}:{:02}.{}", seconds / 60, seconds % 60, time.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+impl Tui {
+    pub fn new(game: Game) -> Self {
+        Tui { game, cursor: 0x44, selected: None, flipped: false, prompt: None, thinking: false, message: String::new(), tt: TranspositionTable::new(DEFAULT_HASH_MB) }
+    }
+
+    fn event_loop(&mut self, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            self.game.check_flag();
+            self.draw(out)?;
+            if self.thinking {
+                self.thinking = false;
+                self.engine_move();
+                continue;
+            }
+
+            // wake up now and then to keep the clocks ticking
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release && !self.handle_key(key) => return Ok(()),
+                Event::Mouse(MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, .. }) => {
+                    if let Some(coord) = self.get_square_at(column, row) {
+                        self.prompt = None;
+                        self.cursor = coord;
+                        self.activate();
+                    }
+                },
+                Event::Resize(..) => queue!(out, terminal::Clear(ClearType::All))?,
+                _ => (),
+            }
+        }
+    }
+
+    // false once it's time to quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
+        }
+        if let Some(prompt) = self.prompt.take() {
+            self.answer(prompt, key.code);
+            return true;
+        }
+
+        self.message.clear();
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Left | KeyCode::Char('h') => self.step(-1, 0),
+            KeyCode::Right | KeyCode::Char('l') => self.step(1, 0),
+            KeyCode::Up | KeyCode::Char('k') => self.step(0, 1),
+            KeyCode::Down | KeyCode::Char('j') => self.step(0, -1),
+            KeyCode::Enter | KeyCode::Char(' ') => self.activate(),
+            KeyCode::Esc => self.selected = None,
+            KeyCode::Char('f') => self.flipped = !self.flipped,
+            KeyCode::Char('g') if !self.game.is_over() => {
+                self.message = "Thinking...".into();
+                self.thinking = true;
+            },
+            // taking back moves would let either side think on the other's time
+            KeyCode::Char('u') if self.game.get_clock().is_some() => self.message = "Can't take back moves in a timed game".into(),
+            KeyCode::Char('u') => {
+                self.selected = None;
+                if let Err(message) = self.game.undo() {
+                    self.message = message.into();
+                }
+            },
+            KeyCode::Char('d') => {
+                if let Err(message) = self.game.claim_draw() {
+                    self.message = message.into();
+                }
+            },
+            KeyCode::Char('r') => {
+                let turn = self.game.get_board().get_turn();
+                if let Err(message) = self.game.resign(turn) {
+                    self.message = message.into();
+                }
+            },
+            KeyCode::Char('n') => self.restart(),
+            KeyCode::Char('@') if self.game.get_board().get_variant() == Variant::Crazyhouse => {
+                self.selected = None;
+                self.message = "Drop which piece (p/n/b/r/q)?".into();
+                self.prompt = Some(Prompt::Drop);
+            },
+            _ => (),
+        }
+        true
+    }
+
+    fn answer(&mut self, prompt: Prompt, code: KeyCode) {
+        self.message.clear();
+        let KeyCode::Char(c) = code else { return };
+        let Some(r#type) = get_piece_type(c) else { return };
+        match prompt {
+            Prompt::Promotion(moves) => {
+                if let Some(&r#move) = moves.iter().find(|r#move| r#move.promotion == Some(r#type)) {
+                    self.play(r#move);
+                }
+            },
+            Prompt::Drop => self.play(Move::new_drop(r#type, self.cursor)),
+        }
+    }
+
+    // the cursor moves the way the arrows point on screen, whichever way the board is turned
+    fn step(&mut self, dx: i8, dy: i8) {
+        let (dx, dy) = if self.flipped { (-dx, -dy) } else { (dx, dy) };
+        let (x, y) = Square::u8_to_tuple(self.cursor);
+        let x = (x as i8 + dx).clamp(0, 7) as u8;
+        let y = (y as i8 + dy).clamp(0, 7) as u8;
+        self.cursor = (x << 4) + y;
+    }
+
+    // enter or a click: move the selected piece here, or select the piece under the cursor
+    fn activate(&mut self) {
+        if self.game.is_over() {
+            return;
+        }
+        let board = *self.game.get_board();
+        if let Some(from) = self.selected {
+            let moves: Vec<Move> = board.get_legal_moves().into_iter()
+                .filter(|r#move| r#move.drop.is_none() && r#move.from == from && r#move.to == self.cursor)
+                .collect();
+            match moves.len() {
+                0 => (),
+                1 => return self.play(moves[0]),
+                _ => {
+                    self.message = "Promote to (q/r/b/n)?".into();
+                    self.prompt = Some(Prompt::Promotion(moves));
+                    return;
+                },
+            }
+        }
+
+        let piece = board.get_state()[get_index(self.cursor)].get_piece();
+        self.selected = piece.filter(|piece| piece.color == board.get_turn() && self.selected != Some(self.cursor)).map(|_| self.cursor);
+    }
+
+    fn play(&mut self, r#move: Move) {
+        self.selected = None;
+        if let Err(message) = self.game.play(r#move) {
+            self.message = message.into();
+        }
+    }
+
+    fn engine_move(&mut self) {
+        self.message.clear();
+        let limits = self.game.get_clock().map_or(Limits::movetime(Duration::from_secs(1)), Clock::get_limits);
+        if let Some(r#move) = search_with_table(self.game.get_board(), &limits, &mut self.tt).best_move {
+            self.play(r#move);
+        }
+    }
+
+    // the same start and time control again
+    fn restart(&mut self) {
+        let mut game = Game::from_board(*self.game.get_start());
+        (game.white, game.black) = (self.game.white.clone(), self.game.black.clone());
+        if let Some(clock) = self.game.get_clock() {
+            game.set_clock(Clock::new(clock.get_time_control().clone()));
+        }
+        self.game = game;
+        self.selected = None;
+    }
+
+    fn get_square_at(&self, column: u16, row: u16) -> Option<u8> {
+        if !(BOARD_LEFT..BOARD_LEFT + 24).contains(&column) || !(BOARD_TOP..BOARD_TOP + 8).contains(&row) {
+            return None;
+        }
+        let (column, row) = (((column - BOARD_LEFT) / 3) as u8, (row - BOARD_TOP) as u8);
+        let (x, y) = if self.flipped { (7 - column, row) } else { (column, 7 - row) };
+        Some((x << 4) + y)
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let board = self.game.get_board();
+        let (top, bottom) = if self.flipped { (White, Black) } else { (Black, White) };
+        self.draw_player(out, top, 0)?;
+        self.draw_board(out, board)?;
+        self.draw_player(out, bottom, BOARD_TOP + 9)?;
+        self.draw_moves(out)?;
+
+        let status = match self.game.get_termination() {
+            Some(termination) => format!("{} {termination}", self.game.get_result_token()),
+            None if !self.message.is_empty() => self.message.clone(),
+            None => format!("{} to move", board.get_turn()),
+        };
+        queue!(out, cursor::MoveTo(1, STATUS_ROW), Print(status), terminal::Clear(ClearType::UntilNewLine))?;
+
+        let mut help = "arrows/hjkl/mouse move, enter select, esc cancel, g engine, u undo, f flip, d claim draw, r resign, n new, q quit".to_string();
+        if board.get_variant() == Variant::Crazyhouse {
+            help.push_str(", @ drop");
+        }
+        queue!(out, cursor::MoveTo(1, STATUS_ROW + 1), Print(help), terminal::Clear(ClearType::UntilNewLine))?;
+        out.flush()
+    }
+
+    fn draw_board(&self, out: &mut impl Write, board: &Board) -> io::Result<()> {
+        let last = self.game.get_moves().last().copied();
+        let turn = board.get_turn();
+        let check = board.get_king(turn).filter(|_| board.in_check(turn));
+        let targets: Vec<u8> = match self.selected {
+            Some(from) => board.get_legal_moves().into_iter().filter(|r#move| r#move.drop.is_none() && r#move.from == from).map(|r#move| r#move.to).collect(),
+            None => Vec::new(),
+        };
+
+        for row in 0..8 {
+            let rank = if self.flipped { row + 1 } else { 8 - row };
+            queue!(out, cursor::MoveTo(0, BOARD_TOP + row), Print(format!("{rank:>2} ")))?;
+            for column in 0..8 {
+                let Some(coord) = self.get_square_at(BOARD_LEFT + column * 3, BOARD_TOP + row) else { continue };
+                let square = board.get_state()[get_index(coord)];
+                let piece = square.get_piece();
+                let light = square.get_color() == White;
+                let moved = last.is_some_and(|last| (last.from == coord && last.drop.is_none()) || last.to == coord);
+
+                let background = if self.cursor == coord {
+                    CURSOR
+                } else if self.selected == Some(coord) {
+                    SELECTED
+                } else if check == Some(coord) {
+                    CHECK
+                } else if piece.is_some() && targets.contains(&coord) {
+                    CAPTURE
+                } else if moved {
+                    if light { LAST_MOVE_LIGHT } else { LAST_MOVE_DARK }
+                } else if light {
+                    LIGHT
+                } else {
+                    DARK
+                };
+                // the filled symbols read best on both colors of square
+                let (foreground, symbol) = match piece {
+                    Some(piece) => (if piece.color == White { WHITE_PIECE } else { BLACK_PIECE }, Piece::from(piece.r#type, Black).get_symbol()),
+                    None if targets.contains(&coord) => (DOT, '•'),
+                    None => (DOT, ' '),
+                };
+                queue!(out, SetBackgroundColor(background), SetForegroundColor(foreground), Print(format!(" {symbol} ")), ResetColor)?;
+            }
+        }
+
+        let files: String = (0..8).map(|column| {
+            let file = if self.flipped { 7 - column } else { column };
+            format!(" {} ", (b'a' + file) as char)
+        }).collect();
+        queue!(out, cursor::MoveTo(0, BOARD_TOP + 8), Print(format!("   {files}")), terminal::Clear(ClearType::UntilNewLine))
+    }
+
+    // the name and clock, and the pocket in crazyhouse
+    fn draw_player(&self, out: &mut impl Write, color: Color, row: u16) -> io::Result<()> {
+        let board = self.game.get_board();
+        let name = &self.game.get_player(color).name;
+        let marker = if board.get_turn() == color && !self.game.is_over() { '●' } else { ' ' };
+        let mut line = format!("{marker} {:<16}", if name.is_empty() { color.to_string() } else { name.clone() });
+        if let Some(clock) = self.game.get_clock() {
+            line.push_str(&format_time(clock.get_remaining(color)));
+        }
+        if board.get_variant() == Variant::Crazyhouse {
+            for r#type in [Pawn, Knight, Bishop, Rook, Queen] {
+                let count = board.get_pocket(color)[r#type as usize];
+                if count > 0 {
+                    line.push_str(&format!("  {}{count}", Piece::from(r#type, color).get_symbol()));
+                }
+            }
+        }
+        queue!(out, cursor::MoveTo(1, row), Print(line), terminal::Clear(ClearType::UntilNewLine))
+    }
+
+    // the last few full moves in SAN, next to the board
+    fn draw_moves(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut board = *self.game.get_start();
+        let mut lines = Vec::new();
+        for &r#move in self.game.get_moves() {
+            let san = board.get_san(r#move);
+            match board.get_turn() {
+                White => lines.push(format!("{:>3}. {san:<8}", board.get_fullmove())),
+                Black if lines.is_empty() => lines.push(format!("{:>3}. {:<8}{san}", board.get_fullmove(), "...")),
+                Black => lines.last_mut().unwrap().push_str(&san),
+            }
+            board.apply_move(r#move);
+        }
+
+        queue!(out, cursor::MoveTo(PANEL_LEFT, BOARD_TOP), Print("Moves"), terminal::Clear(ClearType::UntilNewLine))?;
+        let shown = &lines[lines.len().saturating_sub(PANEL_ROWS)..];
+        for row in 0..PANEL_ROWS {
+            let line = shown.get(row).map_or("", String::as_str);
+            queue!(out, cursor::MoveTo(PANEL_LEFT, BOARD_TOP + 1 + row as u16), Print(line), terminal::Clear(ClearType::UntilNewLine))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TimeControl;
+
+    fn get_tui() -> Tui {
+        let mut board = Board::new();
+        board.init("").unwrap();
+        let mut game = Game::from_board(board);
+        game.play_san("e4").unwrap();
+        Tui::new(game)
+    }
+
+    fn press(tui: &mut Tui, c: char) {
+        assert!(tui.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn undo() {
+        let mut tui = get_tui();
+        press(&mut tui, 'u');
+        assert_eq!(tui.game.get_board().get_fullmove(), 1);
+        assert_eq!(tui.game.get_board().get_turn(), White);
+        assert!(tui.message.is_empty());
+    }
+
+    #[test]
+    fn no_undo_in_timed_games() {
+        let mut tui = get_tui();
+        tui.game.set_clock(Clock::new(TimeControl::fischer(Duration::from_secs(60), Duration::from_secs(2))));
+        let fen = tui.game.get_board().get_fen();
+        press(&mut tui, 'u');
+        assert_eq!(tui.game.get_board().get_fen(), fen);
+        assert_eq!(tui.message, "Can't take back moves in a timed game");
+    }
+}
@@ -0,0 +1,571 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+use crate::{board::{Board, Move}, variant::Variant};
+
+// client side of the Universal Chess Interface, for driving external engines (Stockfish, etc) as subprocesses
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    Cp(i32), // centipawns from the engine's point of view
+    Mate(i32), // moves until mate, negative if the engine is getting mated
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Bound {
+    #[default]
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Info {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    pub bound: Bound,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time: Option<u64>, // milliseconds
+    pub hashfull: Option<u32>, // permill
+    pub tbhits: Option<u64>,
+    pub currmove: Option<Move>,
+    pub currmovenumber: Option<u32>,
+    pub pv: Vec<Move>,
+    pub string: Option<String>,
+}
+
+impl Info {
+    // parse an "info ..." line, unknown tokens are skipped
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace().peekable();
+        if tokens.next() != Some("info") {
+            return None;
+        }
+
+        let mut info = Info::default();
+        while let Some(token) = tokens.next() {
+            match token {
+                "depth" => info.depth = tokens.next().and_then(|t| t.parse().ok()),
+                "seldepth" => info.seldepth = tokens.next().and_then(|t| t.parse().ok()),
+                "multipv" => info.multipv = tokens.next().and_then(|t| t.parse().ok()),
+                "nodes" => info.nodes = tokens.next().and_then(|t| t.parse().ok()),
+                "nps" => info.nps = tokens.next().and_then(|t| t.parse().ok()),
+                "time" => info.time = tokens.next().and_then(|t| t.parse().ok()),
+                "hashfull" => info.hashfull = tokens.next().and_then(|t| t.parse().ok()),
+                "tbhits" => info.tbhits = tokens.next().and_then(|t| t.parse().ok()),
+                "currmove" => info.currmove = tokens.next().and_then(|t| Move::from_uci(t).ok()),
+                "currmovenumber" => info.currmovenumber = tokens.next().and_then(|t| t.parse().ok()),
+                "score" => {
+                    let kind = tokens.next();
+                    let value = tokens.next().and_then(|t| t.parse().ok());
+                    info.score = match (kind, value) {
+                        (Some("cp"), Some(value)) => Some(Score::Cp(value)),
+                        (Some("mate"), Some(value)) => Some(Score::Mate(value)),
+                        _ => None,
+                    };
+
+                    match tokens.peek() {
+                        Some(&"lowerbound") => { info.bound = Bound::Lower; tokens.next(); },
+                        Some(&"upperbound") => { info.bound = Bound::Upper; tokens.next(); },
+                        _ => (),
+                    }
+                },
+                "pv" => {
+                    while let Some(Ok(r#move)) = tokens.peek().map(|t| Move::from_uci(t)) {
+                        info.pv.push(r#move);
+                        tokens.next();
+                    }
+                },
+                "string" => {
+                    // the rest of the line is free text
+                    info.string = Some(tokens.by_ref().collect::<Vec<_>>().join(" "));
+                },
+                _ => (),
+            }
+        }
+
+        Some(info)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BestMove {
+    pub best_move: Option<Move>, // None if the engine had no legal move to play
+    pub ponder: Option<Move>,
+}
+
+impl BestMove {
+    // parse a "bestmove <move> [ponder <move>]" line
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("bestmove") {
+            return None;
+        }
+
+        let best_move = Move::from_uci(tokens.next()?).ok(); // "(none)" and "0000" are both null moves
+        let ponder = match tokens.next() {
+            Some("ponder") => tokens.next().and_then(|t| Move::from_uci(t).ok()),
+            _ => None,
+        };
+
+        Some(BestMove { best_move, ponder })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: String,
+    pub r#type: String, // check, spin, combo, button or string
+    pub default: Option<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub vars: Vec<String>,
+}
+
+impl EngineOption {
+    // parse an "option name <name> type <type> ..." line, names and values may contain spaces
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("option") {
+            return None;
+        }
+
+        let mut option = EngineOption::default();
+        let mut key = "";
+        let mut value: Vec<&str> = Vec::new();
+        for token in tokens.chain(["var"]) { // trailing keyword flushes the last value
+            if !matches!(token, "name" | "type" | "default" | "min" | "max" | "var") {
+                value.push(token);
+                continue;
+            }
+
+            let joined = value.join(" ");
+            match key {
+                "name" => option.name = joined,
+                "type" => option.r#type = joined,
+                "default" => option.default = Some(joined).filter(|v| v != "<empty>"),
+                "min" => option.min = joined.parse().ok(),
+                "max" => option.max = joined.parse().ok(),
+                "var" if !value.is_empty() => option.vars.push(joined),
+                _ => (),
+            }
+
+            key = token;
+            value.clear();
+        }
+
+        if option.name.is_empty() { None } else { Some(option) }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GoParams {
+    pub searchmoves: Vec<Move>,
+    pub ponder: bool,
+    pub wtime: Option<u64>, // milliseconds
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u32>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub mate: Option<u32>,
+    pub movetime: Option<u64>,
+    pub infinite: bool,
+}
+
+impl GoParams {
+    pub fn depth(depth: u32) -> Self {
+        GoParams { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn movetime(milliseconds: u64) -> Self {
+        GoParams { movetime: Some(milliseconds), ..Default::default() }
+    }
+
+    pub fn to_command(&self) -> String {
+        let mut command = String::from("go");
+        if !self.searchmoves.is_empty() {
+            command.push_str(" searchmoves");
+            for r#move in &self.searchmoves {
+                command.push_str(&format!(" {move}"));
+            }
+        }
+        if self.ponder {
+            command.push_str(" ponder");
+        }
+
+        for (name, value) in [
+            ("wtime", self.wtime),
+            ("btime", self.btime),
+            ("winc", self.winc),
+            ("binc", self.binc),
+            ("movestogo", self.movestogo.map(u64::from)),
+            ("depth", self.depth.map(u64::from)),
+            ("nodes", self.nodes),
+            ("mate", self.mate.map(u64::from)),
+            ("movetime", self.movetime),
+        ] {
+            if let Some(value) = value {
+                command.push_str(&format!(" {name} {value}"));
+            }
+        }
+
+        if self.infinite {
+            command.push_str(" infinite");
+        }
+        command
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchOutput {
+    pub best_move: Option<Move>,
+    pub ponder: Option<Move>,
+    pub info: Vec<Info>, // every info line the engine sent during the search, in order
+}
+
+impl SearchOutput {
+    // the last reported score and pv, from the deepest completed iteration
+    pub fn last_info(&self) -> Option<&Info> {
+        self.info.iter().rev().find(|info| info.score.is_some() && !info.pv.is_empty())
+    }
+}
+
+pub struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub options: Vec<EngineOption>,
+    pub timeout: Duration, // how long to wait for uciok/readyok before giving up on the engine
+    chess960: bool, // what UCI_Chess960 was last set to
+    variant: Variant, // what UCI_Variant was last set to
+}
+
+impl Engine {
+    // spawn the engine binary and complete the uci handshake
+    pub fn spawn(path: &str, args: &[&str]) -> Result<Self, &'static str> {
+        let mut child = Command::new(path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|_| "Unable to start engine")?;
+
+        let stdin = child.stdin.take().ok_or("Unable to open engine stdin")?;
+        let stdout = child.stdout.take().ok_or("Unable to open engine stdout")?;
+
+        // read on a separate thread so we can time out on unresponsive engines
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Engine {
+            child,
+            stdin,
+            lines,
+            name: None,
+            author: None,
+            options: Vec::new(),
+            timeout: Duration::from_secs(10),
+            chess960: false,
+            variant: Variant::Standard,
+        };
+
+        engine.send("uci")?;
+        loop {
+            let line = engine.read_line(Some(engine.timeout))?;
+            let line = line.trim();
+            if line == "uciok" {
+                break;
+            }
+
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = Some(name.to_string());
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                engine.author = Some(author.to_string());
+            } else if let Some(option) = EngineOption::parse(line) {
+                engine.options.push(option);
+            }
+        }
+
+        Ok(engine)
+    }
+
+    // send a raw command line to the engine
+    pub fn send(&mut self, command: &str) -> Result<(), &'static str> {
+        writeln!(self.stdin, "{command}")
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| "Unable to write to engine")
+    }
+
+    // read the next line of engine output, waiting forever if timeout is None
+    pub fn read_line(&mut self, timeout: Option<Duration>) -> Result<String, &'static str> {
+        match timeout {
+            Some(timeout) => self.lines.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => "Engine timed out",
+                RecvTimeoutError::Disconnected => "Engine closed its output",
+            }),
+            None => self.lines.recv().map_err(|_| "Engine closed its output"),
+        }
+    }
+
+    pub fn is_ready(&mut self) -> Result<(), &'static str> {
+        self.send("isready")?;
+        while self.read_line(Some(self.timeout))?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    // value is ignored for button options
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), &'static str> {
+        if !self.options.iter().any(|option| option.name.eq_ignore_ascii_case(name)) {
+            return Err("Engine does not support this option");
+        }
+
+        if value.is_empty() {
+            self.send(&format!("setoption name {name}"))
+        } else {
+            self.send(&format!("setoption name {name} value {value}"))
+        }
+    }
+
+    pub fn new_game(&mut self) -> Result<(), &'static str> {
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+
+    pub fn set_position(&mut self, board: &Board) -> Result<(), &'static str> {
+        self.set_chess960(board.is_chess960())?;
+        self.set_variant(board.get_variant())?;
+        self.send(&format!("position fen {}", board.get_fen()))
+    }
+
+    // chess960 engines read and write castling as the king taking its own rook, like chess960 boards do
+    fn set_chess960(&mut self, chess960: bool) -> Result<(), &'static str> {
+        if chess960 == self.chess960 {
+            return Ok(());
+        }
+        if !self.options.iter().any(|option| option.name.eq_ignore_ascii_case("UCI_Chess960")) {
+            return if chess960 { Err("Engine does not support Chess960") } else { Ok(()) };
+        }
+        self.set_option("UCI_Chess960", if chess960 { "true" } else { "false" })?;
+        self.chess960 = chess960;
+        Ok(())
+    }
+
+    // variant engines (Fairy-Stockfish, etc) list the variants they know as a combo option
+    fn set_variant(&mut self, variant: Variant) -> Result<(), &'static str> {
+        if variant == self.variant {
+            return Ok(());
+        }
+        let known = self.options.iter()
+            .find(|option| option.name.eq_ignore_ascii_case("UCI_Variant"))
+            .is_some_and(|option| option.vars.iter().any(|var| var.eq_ignore_ascii_case(variant.get_name())));
+        if !known {
+            return if variant == Variant::Standard { Ok(()) } else { Err("Engine does not support this variant") };
+        }
+        self.set_option("UCI_Variant", variant.get_name())?;
+        self.variant = variant;
+        Ok(())
+    }
+
+    // position after playing moves from board, lets the engine see the game history for repetitions
+    pub fn set_position_moves(&mut self, board: &Board, moves: &[Move]) -> Result<(), &'static str> {
+        self.set_chess960(board.is_chess960())?;
+        self.set_variant(board.get_variant())?;
+        let mut command = format!("position fen {}", board.get_fen());
+        if !moves.is_empty() {
+            command.push_str(" moves");
+            for r#move in moves {
+                command.push_str(&format!(" {move}"));
+            }
+        }
+        self.send(&command)
+    }
+
+    // start searching without waiting for the result, for infinite and ponder searches
+    pub fn start(&mut self, params: &GoParams) -> Result<(), &'static str> {
+        self.send(&params.to_command())
+    }
+
+    // collect info lines until the engine sends bestmove
+    pub fn wait(&mut self) -> Result<SearchOutput, &'static str> {
+        let mut output = SearchOutput::default();
+        loop {
+            let line = self.read_line(None)?;
+            if let Some(info) = Info::parse(&line) {
+                output.info.push(info);
+            } else if let Some(best_move) = BestMove::parse(&line) {
+                output.best_move = best_move.best_move;
+                output.ponder = best_move.ponder;
+                return Ok(output);
+            }
+        }
+    }
+
+    pub fn go(&mut self, params: &GoParams) -> Result<SearchOutput, &'static str> {
+        self.start(params)?;
+        self.wait()
+    }
+
+    pub fn stop(&mut self) -> Result<SearchOutput, &'static str> {
+        self.send("stop")?;
+        self.wait()
+    }
+
+    pub fn ponderhit(&mut self) -> Result<(), &'static str> {
+        self.send("ponderhit")
+    }
+
+    pub fn quit(mut self) -> Result<(), &'static str> {
+        self.send("quit")?;
+        self.child.wait().map_err(|_| "Unable to wait for engine to exit")?;
+        Ok(())
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        if let Ok(Some(_)) = self.child.try_wait() {
+            return;
+        }
+
+        let _ = self.send("quit");
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::Square, pieces::Pieces::Queen};
+
+    // answers like a real engine, reports the last setoption in an info string and stops
+    // answering isready once Silent is switched on
+    #[cfg(unix)]
+    const MOCK_ENGINE: &str = r#"
+        while read -r line; do
+            case "$line" in
+                uci) echo "id name Mock Engine"; echo "id author Someone"
+                     echo "option name Hash type spin default 16 min 1 max 1024"
+                     echo "option name Silent type check default false"
+                     echo "uciok" ;;
+                "setoption name Silent value true") silent=1 ;;
+                setoption*) last="$line" ;;
+                isready) [ -z "$silent" ] && echo "readyok" ;;
+                go*) echo "info string $last"
+                     echo "info depth 1 seldepth 2 score cp 34 nodes 20 nps 2000 time 10 pv e2e4"
+                     echo "info depth 2 score mate -3 upperbound pv e2e4 e7e5 d1h5"
+                     echo "bestmove e2e4 ponder e7e5" ;;
+                quit) exit 0 ;;
+            esac
+        done
+    "#;
+
+    fn get_move(notation: &str) -> Move {
+        Move::from_uci(notation).unwrap()
+    }
+
+    #[test]
+    fn parses_info() {
+        let info = Info::parse("info depth 12 seldepth 18 multipv 1 score cp -25 lowerbound nodes 1000 nps 5000 hashfull 3 tbhits 0 time 200 pv e2e4 e7e5 g1f3").unwrap();
+        assert_eq!(info.depth, Some(12));
+        assert_eq!(info.seldepth, Some(18));
+        assert_eq!(info.score, Some(Score::Cp(-25)));
+        assert_eq!(info.bound, Bound::Lower);
+        assert_eq!(info.nodes, Some(1000));
+        assert_eq!(info.time, Some(200));
+        assert_eq!(info.pv, vec![get_move("e2e4"), get_move("e7e5"), get_move("g1f3")]);
+
+        let info = Info::parse("info score mate 4 currmove a7a8q currmovenumber 3").unwrap();
+        assert_eq!(info.score, Some(Score::Mate(4)));
+        assert_eq!(info.currmove, Some(Move { promotion: Some(Queen), ..Move::new(Square::string_to_u8("a7"), Square::string_to_u8("a8")) }));
+        assert_eq!(Info::parse("info string hello  there").unwrap().string.as_deref(), Some("hello there"));
+        assert!(Info::parse("bestmove e2e4").is_none());
+    }
+
+    #[test]
+    fn parses_best_move() {
+        let best_move = BestMove::parse("bestmove g1f3 ponder d7d5").unwrap();
+        assert_eq!(best_move.best_move, Some(get_move("g1f3")));
+        assert_eq!(best_move.ponder, Some(get_move("d7d5")));
+        assert_eq!(BestMove::parse("bestmove (none)").unwrap().best_move, None);
+        assert_eq!(BestMove::parse("bestmove 0000").unwrap().best_move, None);
+        assert!(BestMove::parse("info depth 1").is_none());
+    }
+
+    #[test]
+    fn parses_options() {
+        let option = EngineOption::parse("option name Skill Level type spin default 20 min 0 max 20").unwrap();
+        assert_eq!(option.name, "Skill Level");
+        assert_eq!((option.min, option.max), (Some(0), Some(20)));
+        let option = EngineOption::parse("option name Style type combo default Normal var Solid var Normal var Risky").unwrap();
+        assert_eq!(option.vars, vec!["Solid", "Normal", "Risky"]);
+        assert_eq!(EngineOption::parse("option name Path type string default <empty>").unwrap().default, None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn drives_mock_engine() {
+        let mut engine = Engine::spawn("sh", &["-c", MOCK_ENGINE]).unwrap();
+        assert_eq!(engine.name.as_deref(), Some("Mock Engine"));
+        assert_eq!(engine.author.as_deref(), Some("Someone"));
+        assert_eq!(engine.options.len(), 2);
+        assert_eq!(engine.options[0].default.as_deref(), Some("16"));
+        engine.is_ready().unwrap();
+
+        engine.set_option("Hash", "64").unwrap();
+        assert_eq!(engine.set_option("Threads", "2"), Err("Engine does not support this option"));
+        let mut board = Board::new();
+        board.init("").unwrap();
+        engine.set_position(&board).unwrap();
+
+        let output = engine.go(&GoParams::depth(2)).unwrap();
+        assert_eq!(output.info.len(), 3);
+        assert_eq!(output.info[0].string.as_deref(), Some("setoption name Hash value 64"));
+        assert_eq!(output.info[1].score, Some(Score::Cp(34)));
+        assert_eq!(output.info[1].nps, Some(2000));
+        assert_eq!(output.info[2].score, Some(Score::Mate(-3)));
+        assert_eq!(output.info[2].bound, Bound::Upper);
+        assert_eq!(output.last_info().unwrap().pv, vec![get_move("e2e4"), get_move("e7e5"), get_move("d1h5")]);
+        assert_eq!(output.best_move, Some(get_move("e2e4")));
+        assert_eq!(output.ponder, Some(get_move("e7e5")));
+        engine.quit().unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn times_out_on_silent_engine() {
+        let mut engine = Engine::spawn("sh", &["-c", MOCK_ENGINE]).unwrap();
+        engine.timeout = Duration::from_millis(200);
+        engine.set_option("Silent", "true").unwrap();
+        assert_eq!(engine.is_ready(), Err("Engine timed out"));
+
+        // an engine that goes away before finishing the handshake
+        assert_eq!(Engine::spawn("sh", &["-c", "read -r line; echo 'id name Broken'"]).err(), Some("Engine closed its output"));
+        assert_eq!(Engine::spawn("./no-such-engine", &[]).err(), Some("Unable to start engine"));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{board::{Board, Color::{self, *}, STARTING_FEN}, pieces::Pieces::*};
+
+// rule sets on top of regular chess, a board keeps its variant when set up from a FEN
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Variant {
+    #[default]
+    Standard,
+    Crazyhouse, // captured pieces change sides and can be dropped back onto the board
+    ThreeCheck, // giving check for the third time wins
+    KingOfTheHill, // bringing the king to one of the four center squares wins
+    Atomic, // captures explode everything but pawns around the target square, exploding the enemy king wins
+    Antichess, // captures are forced, kings are ordinary pieces and running out of moves wins
+    Horde, // white has 36 pawns and no king, and loses when they are all gone
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win(Color),
+    Draw,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 7] = [
+        Variant::Standard,
+        Variant::Crazyhouse,
+        Variant::ThreeCheck,
+        Variant::KingOfTheHill,
+        Variant::Atomic,
+        Variant::Antichess,
+        Variant::Horde,
+    ];
+
+    // names as used for the UCI_Variant option
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "chess",
+            Variant::Crazyhouse => "crazyhouse",
+            Variant::ThreeCheck => "3check",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::Atomic => "atomic",
+            Variant::Antichess => "antichess",
+            Variant::Horde => "horde",
+        }
+    }
+
+    // also takes the PGN names, spaces and dashes don't matter
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name: String = name.to_ascii_lowercase().chars().filter(|&c| c != ' ' && c != '-').collect();
+        match name.as_str() {
+            "standard" | "normal" => Some(Variant::Standard),
+            "threecheck" => Some(Variant::ThreeCheck),
+            "koth" => Some(Variant::KingOfTheHill),
+            _ => Self::ALL.into_iter().find(|variant| variant.get_name() == name),
+        }
+    }
+
+    // for the PGN Variant tag, as lichess writes them
+    pub fn get_pgn_name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::Crazyhouse => "Crazyhouse",
+            Variant::ThreeCheck => "Three-check",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::Atomic => "Atomic",
+            Variant::Antichess => "Antichess",
+            Variant::Horde => "Horde",
+        }
+    }
+
+    pub fn get_starting_fen(&self) -> &'static str {
+        match self {
+            Variant::Crazyhouse => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1",
+            Variant::ThreeCheck => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1",
+            Variant::Antichess => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            Variant::Horde => "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1",
+            _ => STARTING_FEN,
+        }
+    }
+}
+
+impl Board {
+    // the game is over once the side to move has no legal moves, or the variant says so
+    pub fn get_outcome(&self) -> Option<Outcome> {
+        self.get_variant_outcome().or_else(|| self.get_legal_moves().is_empty().then(|| self.get_outcome_without_moves()))
+    }
+
+    // the result when the side to move has no legal moves: checkmate or stalemate,
+    // except in antichess where that is the goal
+    pub fn get_outcome_without_moves(&self) -> Outcome {
+        if self.get_variant() == Variant::Antichess {
+            Outcome::Win(self.get_turn())
+        } else if self.in_check(self.get_turn()) {
+            Outcome::Win(!self.get_turn())
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    // whether the side can't possibly checkmate whatever the other side plays: a lone king, a knight
+    // when the other side has nothing to block its king in with, or bishops that all stand on one square
+    // color with no knights or pawns around, variants have other ways to win so they never count
+    pub fn has_insufficient_material(&self, color: Color) -> bool {
+        if self.get_variant() != Variant::Standard {
+            return false;
+        }
+
+        let pieces = |color: Color| self.get_state().into_iter()
+            .filter_map(|square| square.get_piece().filter(|piece| piece.color == color && piece.r#type != King).map(|piece| (piece.r#type, square)))
+            .collect::<Vec<_>>();
+        let (ours, theirs) = (pieces(color), pieces(!color));
+        if ours.is_empty() {
+            return true;
+        }
+
+        if ours.iter().any(|(r#type, _)| matches!(r#type, Pawn | Rook | Queen)) {
+            return false;
+        }
+        if ours.iter().any(|(r#type, _)| *r#type == Knight) {
+            return ours.len() == 1 && theirs.iter().all(|(r#type, _)| *r#type == Queen);
+        }
+
+        let bishops = ours.iter().chain(&theirs).filter(|(r#type, _)| *r#type == Bishop).map(|(_, square)| {
+            let (x, y) = square.get_coords_tuple();
+            (x + y) % 2
+        });
+        let same_color = bishops.clone().all(|shade| shade == 0) || bishops.clone().all(|shade| shade == 1);
+        same_color && !theirs.iter().any(|(r#type, _)| matches!(r#type, Pawn | Knight))
+    }
+
+    // wins the variant adds on top of checkmate, whoever is to move
+    pub fn get_variant_outcome(&self) -> Option<Outcome> {
+        match self.get_variant() {
+            Variant::ThreeCheck => [White, Black].into_iter().find(|&color| self.get_checks(color) >= 3).map(Outcome::Win),
+            Variant::KingOfTheHill => [White, Black].into_iter().find(|&color| {
+                // d4, d5, e4 and e5
+                self.get_king(color).is_some_and(|king| matches!(king, 0x33 | 0x34 | 0x43 | 0x44))
+            }).map(Outcome::Win),
+            // whoever still has a king wins, both can't explode at once
+            Variant::Atomic => [White, Black].into_iter().find(|&color| self.get_king(color).is_none()).map(|color| Outcome::Win(!color)),
+            Variant::Horde => {
+                let white_left = self.get_state().iter().any(|square| square.get_piece().is_some_and(|piece| piece.color == White));
+                (!white_left).then_some(Outcome::Win(Black))
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Move;
+
+    fn get_board(variant: Variant, fen: &str) -> Board {
+        let mut board = Board::new();
+        board.set_variant(variant);
+        board.init(fen).unwrap();
+        board
+    }
+
+    #[test]
+    fn insufficient_material() {
+        // white's result, then black's
+        let positions = [
+            ("8/8/8/4k3/8/8/8/4K3 w - - 0 1", true, true),
+            ("8/8/8/4k3/8/8/8/2B1K3 w - - 0 1", true, true),
+            ("8/8/8/4k3/8/8/8/1N2K3 w - - 0 1", true, true),
+            ("8/8/8/4k3/8/4B3/8/2B1K3 w - - 0 1", true, true), // both bishops on dark squares
+            ("8/8/8/4k3/8/4b3/8/2B1K3 w - - 0 1", true, true),
+            ("8/8/8/4k3/8/5b2/8/2B1K3 w - - 0 1", false, false), // the other bishop can block the king in
+            ("8/8/8/4k3/8/8/5n2/1N2K3 w - - 0 1", false, false), // so can the other knight
+            ("8/8/8/4k3/8/8/3n4/2B1K3 w - - 0 1", false, false),
+            ("8/8/8/4k3/8/8/4P3/4K3 w - - 0 1", false, true),
+            ("8/8/8/4k3/8/8/8/R3K3 w - - 0 1", false, true),
+        ];
+
+        for (fen, white, black) in positions {
+            let board = get_board(Variant::Standard, fen);
+            assert_eq!((board.has_insufficient_material(White), board.has_insufficient_material(Black)), (white, black), "{fen}");
+        }
+
+        // variants have other ways to win
+        let board = get_board(Variant::Atomic, "8/8/8/4k3/8/8/8/2B1K3 w - - 0 1");
+        assert!(!board.has_insufficient_material(White));
+    }
+
+    fn play(board: &mut Board, moves: &[&str]) {
+        for san in moves {
+            let r#move = board.parse_san(san).unwrap_or_else(|error| panic!("{san}: {error}"));
+            board.play(r#move).unwrap();
+        }
+    }
+
+    #[test]
+    fn crazyhouse() {
+        let mut board = get_board(Variant::Crazyhouse, "");
+        assert_eq!(board.get_fen(), Variant::Crazyhouse.get_starting_fen());
+        play(&mut board, &["e4", "d5", "exd5", "Qxd5", "Nc3", "Qxg2", "Bxg2"]);
+        assert_eq!((board.get_pocket(White), board.get_pocket(Black)), ([1, 0, 0, 0, 1, 0], [2, 0, 0, 0, 0, 0]));
+        assert_eq!(board.get_fen(), "rnb1kbnr/ppp1pppp/8/8/8/2N5/PPPP1PBP/R1BQK1NR[QPpp] b KQkq - 0 4");
+
+        // drops go on any empty square, but pawns not on the first or last rank
+        play(&mut board, &["P@f3", "Q@e4"]);
+        assert_eq!((board.get_pocket(White), board.get_pocket(Black)), ([1, 0, 0, 0, 0, 0], [1, 0, 0, 0, 0, 0]));
+        assert!(board.parse_san("P@d8").is_err() && board.play(Move::new_drop(Pawn, 0x37)).is_err());
+        assert_eq!(board.play(Move::new_drop(Knight, 0x44)), Err("Illegal drop"));
+        assert!(board.get_legal_moves().contains(&Move::new_drop(Pawn, 0x44)));
+
+        // the pocket can also be written as a ninth rank
+        let ninth_rank = get_board(Variant::Crazyhouse, "rnb1kbnr/ppp1pppp/8/8/8/2N5/PPPP1PBP/R1BQK1NR/pQpP b KQkq - 0 4");
+        assert_eq!(ninth_rank.get_fen(), "rnb1kbnr/ppp1pppp/8/8/8/2N5/PPPP1PBP/R1BQK1NR[QPpp] b KQkq - 0 4");
+        assert!(Board::new().set_fen("8/8/8/8/8/8/8/4K2k[Kq] w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn crazyhouse_promoted_pieces() {
+        let mut board = get_board(Variant::Crazyhouse, "4k3/P7/8/8/8/8/r7/4K3[] w - - 0 1");
+        play(&mut board, &["a8=Q+"]);
+        assert_eq!(board.get_fen(), "Q~3k3/8/8/8/8/8/r7/4K3[] b - - 0 1");
+
+        // a promoted piece keeps its mark when it moves, and goes back to the pocket as a pawn
+        play(&mut board, &["Kd7", "Qa7+", "Kc6"]);
+        assert_eq!(board.get_fen(), "8/Q~7/2k5/8/8/8/r7/4K3[] w - - 3 3");
+        play(&mut board, &["Qa4+", "Rxa4"]);
+        assert_eq!(board.get_pocket(Black), [1, 0, 0, 0, 0, 0]);
+
+        let mut board = get_board(Variant::Crazyhouse, "4k3/8/8/8/8/8/r~7/4K2R[] w K - 0 1");
+        play(&mut board, &["Rh8+", "Kd7", "Rh2", "Kc8", "Rxa2"]);
+        assert_eq!(board.get_fen(), "2k5/8/8/8/8/8/R7/4K3[P] b - - 0 3");
+
+        // a mark needs a piece in front of it
+        assert!(Board::new().set_fen("~4k3/8/8/8/8/8/8/4K3[] w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn three_check() {
+        let mut board = get_board(Variant::ThreeCheck, "");
+        assert_eq!(board.get_fen(), Variant::ThreeCheck.get_starting_fen());
+        play(&mut board, &["e4", "e5", "Bc4", "Nc6", "Bxf7+", "Kxf7", "Qh5+", "Ke6"]);
+        assert_eq!((board.get_checks(White), board.get_checks(Black)), (2, 0));
+        assert_eq!(board.get_fen(), "r1bq1bnr/pppp2pp/2n1k3/4p2Q/4P3/8/PPPP1PPP/RNB1K1NR w KQ - 1+3 2 5");
+        assert_eq!(board.get_variant_outcome(), None);
+        play(&mut board, &["Qf5+"]);
+        assert_eq!(board.get_variant_outcome(), Some(Outcome::Win(White)));
+        assert!(board.play(Move::from_uci("e6d6").unwrap()).is_err());
+
+        // checks left to give before the clocks, or checks given after them
+        let before = get_board(Variant::ThreeCheck, "4k3/8/8/8/8/8/8/4K2R w K - 2+1 0 1");
+        let after = get_board(Variant::ThreeCheck, "4k3/8/8/8/8/8/8/4K2R w K - 0 1 +1+2");
+        assert_eq!((before.get_checks(White), before.get_checks(Black)), (1, 2));
+        assert_eq!(before.get_fen(), after.get_fen());
+        assert_eq!(after.get_fen(), "4k3/8/8/8/8/8/8/4K2R w K - 2+1 0 1");
+        assert!(Board::new().set_fen("4k3/8/8/8/8/8/8/4K2R w K - 4+1 0 1").is_err());
+        assert!(Board::new().set_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1 1+0").is_err());
+    }
+
+    #[test]
+    fn king_of_the_hill() {
+        let mut board = get_board(Variant::KingOfTheHill, "8/8/8/8/8/3K4/8/k7 w - - 0 1");
+        assert_eq!(board.get_outcome(), None);
+        play(&mut board, &["Ke4"]);
+        assert_eq!(board.get_outcome(), Some(Outcome::Win(White)));
+        assert_eq!(board.play(Move::from_uci("a1a2").unwrap()), Err("Game is over"));
+
+        // only the four center squares count
+        for (fen, outcome) in [
+            ("8/8/8/8/3k4/8/8/7K w - - 0 1", true),
+            ("8/8/8/3k4/8/8/8/7K w - - 0 1", true),
+            ("8/8/8/4k3/8/8/8/7K w - - 0 1", true),
+            ("8/8/8/8/2k5/8/8/7K w - - 0 1", false),
+            ("8/8/8/5k2/8/8/8/7K w - - 0 1", false),
+            ("8/8/4k3/8/8/8/8/7K w - - 0 1", false),
+        ] {
+            let board = get_board(Variant::KingOfTheHill, fen);
+            assert_eq!(board.get_variant_outcome(), outcome.then_some(Outcome::Win(Black)), "{fen}");
+        }
+        // not in standard chess
+        assert_eq!(get_board(Variant::Standard, "8/8/8/8/4K3/8/8/k7 b - - 0 1").get_outcome(), None);
+    }
+}
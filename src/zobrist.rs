@@ -0,0 +1,145 @@
+use crate::{board::{Board, Color::*}, variant::Variant};
+
+// zobrist keys for hashing positions, generated at compile time so they're the same on every run
+
+struct Keys {
+    pieces: [[u64; 64]; 12], // white pieces first, in Pieces order
+    castling: [u64; 16], // one per combination of CASTLE_* bits
+    enpassant: [u64; 8], // by file
+    black_to_move: u64,
+    pockets: [[u64; 17]; 12], // crazyhouse, by piece like pieces and then by count
+    checks: [[u64; 4]; 2], // three-check, by Color and checks given
+    variants: [u64; Variant::ALL.len()], // by Variant, standard chess hashes to nothing
+}
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    (state, z ^ (z >> 31))
+}
+
+const KEYS: Keys = {
+    let mut keys = Keys { pieces: [[0; 64]; 12], castling: [0; 16], enpassant: [0; 8], black_to_move: 0, pockets: [[0; 17]; 12], checks: [[0; 4]; 2], variants: [0; Variant::ALL.len()] };
+    let mut state = 0x5253_4368_6573_7321; // "RSChess!"
+    let mut key;
+
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            (state, key) = splitmix64(state);
+            keys.pieces[piece][square] = key;
+            square += 1;
+        }
+        piece += 1;
+    }
+
+    let mut i = 0;
+    while i < 16 {
+        (state, key) = splitmix64(state);
+        keys.castling[i] = key;
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < 8 {
+        (state, key) = splitmix64(state);
+        keys.enpassant[i] = key;
+        i += 1;
+    }
+
+    (state, keys.black_to_move) = splitmix64(state);
+
+    let mut piece = 0;
+    while piece < 12 {
+        let mut count = 0;
+        while count < 17 {
+            (state, key) = splitmix64(state);
+            keys.pockets[piece][count] = key;
+            count += 1;
+        }
+        piece += 1;
+    }
+
+    let mut color = 0;
+    while color < 2 {
+        let mut count = 0;
+        while count < 4 {
+            (state, key) = splitmix64(state);
+            keys.checks[color][count] = key;
+            count += 1;
+        }
+        color += 1;
+    }
+
+    let mut i = 1;
+    while i < Variant::ALL.len() {
+        (state, key) = splitmix64(state);
+        keys.variants[i] = key;
+        i += 1;
+    }
+
+    keys
+};
+
+impl Board {
+    // identifies the position for transposition tables and repetition detection
+    pub fn get_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for (index, square) in self.get_state().iter().enumerate() {
+            let Some(piece) = square.get_piece() else { continue };
+            let offset = if piece.color == White { 0 } else { 6 };
+            hash ^= KEYS.pieces[offset + piece.r#type as usize][index];
+        }
+
+        hash ^= KEYS.castling[self.get_castling() as usize & 0x0F];
+
+        if let Some(&x) = self.get_enpassantable(!self.get_turn()).first() {
+            hash ^= KEYS.enpassant[x as usize];
+        }
+
+        if self.get_turn() == Black {
+            hash ^= KEYS.black_to_move;
+        }
+
+        // both are empty outside their variants, and hash to nothing then
+        for color in [White, Black] {
+            let offset = if color == White { 0 } else { 6 };
+            for (r#type, &count) in self.get_pocket(color).iter().enumerate().filter(|(_, &count)| count > 0) {
+                hash ^= KEYS.pockets[offset + r#type][count.min(16) as usize];
+            }
+
+            let checks = self.get_checks(color);
+            if checks > 0 {
+                hash ^= KEYS.checks[color as usize][checks.min(3) as usize];
+            }
+        }
+
+        // atomic, king of the hill and others start from the standard position, a table shared
+        // between variants mustn't mix up their scores
+        hash ^= KEYS.variants[self.get_variant() as usize];
+
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_the_variant() {
+        let mut hashes: Vec<u64> = Variant::ALL.into_iter().map(|variant| {
+            let mut board = Board::new();
+            board.set_variant(variant);
+            board.init("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+            board.get_hash()
+        }).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        assert_eq!(hashes.len(), Variant::ALL.len());
+    }
+}